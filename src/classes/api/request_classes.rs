@@ -2,14 +2,62 @@ use serde::{Deserialize, Serialize};
 use phf::phf_map;
 
 use crate::globals::GRANULARITY;
+use crate::kernel::events::MarketEvent;
+use crate::kernel::export::ExportFormat;
+use crate::scenario::Scenario;
 
-use super::super::shared::order::Stock;
+use super::super::shared::order::{OrderType, PegReference, Stock, TimeInForce, Trail};
 
 #[derive(Deserialize, Serialize)]
 pub struct OrderDTO {
     pub stock_name: String,
     pub amount: u64,
-    pub price: Option<f64>
+    pub price: Option<f64>,
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+    // limit orders only: an iceberg shows display_amount at a time, a hidden order shows nothing
+    pub display_amount: Option<u64>,
+    #[serde(default)]
+    pub hidden: bool,
+    // a limit order that must not take liquidity, rejected if it would unless a slide reprices it
+    // that far behind the best price on the other side
+    #[serde(default)]
+    pub post_only: bool,
+    pub slide: Option<f64>,
+    // instead of a price, follow the best bid/ask `offset` behind the reference
+    pub peg: Option<PegReference>,
+    #[serde(default)]
+    pub offset: f64,
+    // instead of a price, a stop trailing the last price that sends a market order once hit
+    pub trail: Option<Trail>,
+    // an id of the client's choosing, its orders don't trade with each other where self-trade prevention is on
+    pub owner: Option<u64>
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct OcoLegDTO {
+    pub side: OrderType,
+    pub amount: u64,
+    // a limit at price, or a stop that sends a market order once the price reaches it, market without either
+    pub price: Option<f64>,
+    pub stop: Option<f64>
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct OcoDTO {
+    pub stock_name: String,
+    pub legs: Vec<OcoLegDTO>
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct BracketDTO {
+    pub stock_name: String,
+    pub side: OrderType,
+    pub amount: u64,
+    // the entry's limit, a market order when left out
+    pub price: Option<f64>,
+    pub take_profit: f64,
+    pub stop_loss: f64
 }
 
 #[derive(Deserialize, Serialize)]
@@ -24,6 +72,80 @@ pub struct StockQuery {
     pub stock_name: String
 }
 
+#[derive(Deserialize)]
+pub struct DepthQuery {
+    pub stock_name: String,
+    pub levels: Option<usize>
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct PriceBandDTO {
+    pub stock_name: String,
+    // fraction either side of the reference price, None removes the band
+    pub band: Option<f64>,
+    pub halt_seconds: f64,
+    // average the reference over this window rather than following the last trade
+    pub window_seconds: Option<f64>
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct AgentDTO {
+    pub stock_name: String,
+    pub kind: String,
+    #[serde(default)]
+    pub params: serde_json::Value
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct AgentParamsDTO {
+    pub params: serde_json::Value
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct AlgoDTO {
+    pub stock_name: String,
+    // twap, vwap or pov
+    pub algo: String,
+    // everything else configures the algo: side, amount, limit_price, duration_seconds...
+    #[serde(flatten)]
+    pub params: serde_json::Value
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct EventDTO {
+    pub stock_name: String,
+    #[serde(flatten)]
+    pub event: MarketEvent,
+    // simulated seconds from now, fires straight away when left out
+    #[serde(default)]
+    pub delay_seconds: f64
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SnapshotDTO {
+    pub path: String
+}
+
+#[derive(Deserialize)]
+pub struct NewSessionDTO {
+    pub name: String,
+    // the built in market when left out
+    #[serde(default)]
+    pub scenario: Scenario
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    pub stock_name: String,
+    // RFC 3339 simulated times, the whole session when left out
+    pub from: Option<String>,
+    pub to: Option<String>,
+    #[serde(default)]
+    pub format: ExportFormat,
+    // candles only, minutes by default
+    pub granularity: Option<GRANULARITY>
+}
+
 #[derive(Deserialize)]
 pub struct PriceHistoryDTO {
     pub stock_name: String,
@@ -1,16 +1,91 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{globals::GRANULARITY, kernel::order_book::record::ObStat};
+use crate::kernel::market_time::session::SessionPhase;
+use crate::kernel::order_book::circuit_breaker::HaltReason;
+use crate::kernel::agents::registry::AgentInfo;
+use crate::kernel::events::{EventRecord, MarketEvent};
+use crate::kernel::market_time::market_time::MTime;
 
 #[derive(Deserialize, Serialize)]
 pub struct PriceDTO {
     pub price: f64,
-    pub timestamp: i64
+    pub timestamp: i64,
+    pub halt: Option<HaltReason>
 }
 
 #[derive(Deserialize, Serialize)]
-pub struct StockHistoryDTO {
+pub struct LevelDTO {
+    pub price: f64,
+    pub volume: u64
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DepthDTO {
+    pub bids: Vec<LevelDTO>,
+    pub asks: Vec<LevelDTO>,
+    pub phase: SessionPhase,
+    pub halt: Option<HaltReason>
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SessionDTO {
+    pub phase: SessionPhase,
+    pub simulated_time: String
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SessionInfoDTO {
+    pub name: String,
+    pub stocks: Vec<String>,
+    pub simulated_time: String
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct AgentInfoDTO {
+    pub id: u64,
+    pub stock_name: String,
+    pub kind: String,
+    pub config: serde_json::Value,
+    pub state: serde_json::Value
+}
+
+impl From<AgentInfo> for AgentInfoDTO {
+    fn from(info: AgentInfo) -> Self {
+        AgentInfoDTO {
+            id: info.id,
+            stock_name: format!("{:?}", info.stock),
+            kind: info.kind.to_string(),
+            config: info.config,
+            state: info.state
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct EventRecordDTO {
+    // the second bar the event falls in, to line it up with the stock history
     pub tick: u64,
+    pub timestamp: i64,
+    pub simulated_time: String,
+    #[serde(flatten)]
+    pub event: MarketEvent
+}
+
+impl EventRecordDTO {
+    pub fn new(record: EventRecord, clock: &MTime) -> Self {
+        EventRecordDTO {
+            tick: MTime::which_second(record.timestamp),
+            timestamp: record.timestamp,
+            simulated_time: clock.simulated_datetime(record.timestamp).to_rfc3339(),
+            event: record.event
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct StockHistoryDTO {
+    pub tick: i64,
     pub granularity: GRANULARITY,
     pub volume: u64,
     pub high: f64,
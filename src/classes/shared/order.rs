@@ -1,6 +1,8 @@
-use std::cmp::Ordering;
+use std::cmp::{self, Ordering};
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum Stock {
     AAPL,
     GOOGL,
@@ -13,27 +15,99 @@ pub enum OrderStatus {
     Executed {price: f64}
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum OrderType {
     Buy,
     Sell
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Copy, Clone, Default, Serialize, Deserialize)]
+pub enum TimeInForce {
+    #[default]
+    GoodTillCancel,
+    // expires when the instrument's trading session closes
+    Day
+}
+
+// how much of a resting limit order the book shows
+#[derive(Debug, PartialEq, Copy, Clone, Default, Serialize, Deserialize)]
+pub enum Visibility {
+    #[default]
+    Displayed,
+    // shows `peak` at a time, `shown` is what is left of the slice on display. once it trades away the
+    // next slice comes out of the reserve and goes to the back of the queue at its price
+    Iceberg { peak: u64, shown: u64 },
+    // never shown, and behind every displayed order at the same price
+    Hidden
+}
+
+impl Visibility {
+    pub fn iceberg(peak: u64) -> Self {
+        Visibility::Iceberg { peak: peak, shown: peak }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum PegReference {
+    // the best price on the order's own side
+    Primary,
+    // halfway between the best bid and ask
+    Mid,
+    // the best price on the other side
+    Market
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum Trail {
+    // a fixed distance from the price
+    Offset(f64),
+    // a fraction of the price
+    Percent(f64)
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum OrderVariant {
     Market,
-    Limit { price: f64 }
+    Limit { price: f64 },
+    // a limit that only ever adds liquidity: one that would trade on arrival is rejected, or with a
+    // slide repriced that far behind the best price on the other side
+    PostOnly { price: f64, slide: Option<f64> },
+    // follows the best bid/ask, `offset` behind its reference. price is where it rests, None until
+    // there is something to peg to
+    Pegged { reference: PegReference, offset: f64, price: Option<f64> },
+    // a market order held back until the price falls (sells) or rises (buys) to its stop, the stop
+    // follows the price by `trail` whenever it moves the other way
+    TrailingStop { trail: Trail, stop: Option<f64> },
+    // the same held back market order with a stop that stays put
+    Stop { stop: f64 }
+}
+
+impl OrderVariant {
+    // the worst price a resting order trades at, None for one that takes whatever price there is
+    pub fn limit(&self) -> Option<f64> {
+        match *self {
+            OrderVariant::Limit { price } | OrderVariant::PostOnly { price, .. } => Some(price),
+            OrderVariant::Pegged { price, .. } => price,
+            OrderVariant::Market | OrderVariant::TrailingStop { .. } | OrderVariant::Stop { .. } => None
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct OrderDetails {
     pub time: i64,
     pub stock: Stock,
     pub amount: u64,
-    pub lifetime_nanos: Option<i64>
+    pub lifetime_nanos: Option<i64>,
+    pub time_in_force: TimeInForce,
+    #[serde(default)]
+    pub visibility: Visibility,
+    // whoever placed the order, the book can keep an owner from trading with itself
+    #[serde(default)]
+    pub owner: Option<u64>
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Order {
     pub id: Option<u64>,
     pub order_type: OrderType,
@@ -41,34 +115,140 @@ pub struct Order {
     pub details: OrderDetails
 }
 
+impl Order {
+    // what the book shows of the order
+    pub fn displayed(&self) -> u64 {
+        match self.details.visibility {
+            Visibility::Displayed => self.details.amount,
+            Visibility::Iceberg { shown, .. } => shown,
+            Visibility::Hidden => 0
+        }
+    }
+
+    // how much can trade before an iceberg has to show more
+    pub fn tradable(&self) -> u64 {
+        match self.details.visibility {
+            Visibility::Iceberg { shown, .. } => shown,
+            _ => self.details.amount
+        }
+    }
+
+    /// Takes a fill off the order. An iceberg whose slice is used up shows the next one from its
+    /// reserve, losing its time priority to `now`
+    pub fn fill(&mut self, volume: u64, now: i64) {
+        self.details.amount -= volume;
+        if let Visibility::Iceberg { peak, shown } = &mut self.details.visibility {
+            *shown -= volume;
+            if *shown == 0 && self.details.amount > 0 {
+                *shown = cmp::min(*peak, self.details.amount);
+                self.details.time = now;
+            }
+        }
+    }
+
+    /// Moves a pegged order to its reference, losing its time priority to `now` if the price changes.
+    /// A price between ticks goes to the passive one, down for a buy and up for a sell.
+    /// It stays where it is while there is nothing to peg to
+    pub fn repeg(&mut self, best_bid: Option<f64>, best_ask: Option<f64>, tick_size: Option<f64>, now: i64) {
+        let OrderVariant::Pegged { reference, offset, price } = &mut self.variant else {
+            return
+        };
+        let side = self.order_type;
+        let (own, other, behind) = match side {
+            OrderType::Buy => (best_bid, best_ask, -*offset),
+            OrderType::Sell => (best_ask, best_bid, *offset)
+        };
+        let target = match reference {
+            PegReference::Primary => own,
+            PegReference::Market => other,
+            PegReference::Mid => best_bid.zip(best_ask).map(|(bid, ask)| (bid + ask) / 2.0)
+        };
+        let on_tick = |t: f64| match (tick_size, side) {
+            (None, _) => t,
+            // a hair of slack so a price already on the tick isn't pushed off it by float error
+            (Some(tick), OrderType::Buy) => (t / tick + 1e-9).floor() * tick,
+            (Some(tick), OrderType::Sell) => (t / tick - 1e-9).ceil() * tick
+        };
+        if let Some(target) = target.map(|t| on_tick(t + behind)).filter(|t| *t > 0.0) {
+            if *price != Some(target) {
+                *price = Some(target);
+                self.details.time = now;
+            }
+        }
+    }
+
+    /// Trails a stop behind the last price, returning whether the price has reached it. A stop placed
+    /// before the first trade starts trailing once there is a price, a fixed stop never moves
+    pub fn trail(&mut self, last: f64) -> bool {
+        if last <= 0.0 {
+            return false;
+        }
+        if let OrderVariant::Stop { stop } = self.variant {
+            return match self.order_type {
+                OrderType::Sell => last <= stop,
+                OrderType::Buy => last >= stop
+            };
+        }
+        let OrderVariant::TrailingStop { trail, stop } = &mut self.variant else {
+            return false
+        };
+        let distance = match *trail {
+            Trail::Offset(offset) => offset,
+            Trail::Percent(percent) => last * percent
+        };
+        match self.order_type {
+            // a sell stop sits below the price and only ever moves up
+            OrderType::Sell => {
+                if stop.is_some_and(|s| last <= s) {
+                    return true;
+                }
+                *stop = Some(stop.map_or(last - distance, |s| s.max(last - distance)));
+            },
+            OrderType::Buy => {
+                if stop.is_some_and(|s| last >= s) {
+                    return true;
+                }
+                *stop = Some(stop.map_or(last + distance, |s| s.min(last + distance)));
+            }
+        }
+        false
+    }
+
+    fn is_hidden(&self) -> bool {
+        self.details.visibility == Visibility::Hidden
+    }
+}
+
 impl PartialOrd for Order {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        use OrderVariant::*;
         use OrderType::*;
+        // orders without a limit (market orders) go ahead of every priced one
         match &self.order_type {
-            Buy => match (&self.variant, &other.variant) {
-                (Market, Market) => self.details.time.partial_cmp(&other.details.time),
-                (Limit { price: price1 }, Limit { price: price2 }) => {
-                    // First compare by price, then by time if prices are equal
-                    match price1.partial_cmp(price2) {
-                        Some(Ordering::Equal) => other.details.time.partial_cmp(&self.details.time),
+            Buy => match (self.variant.limit(), other.variant.limit()) {
+                (None, None) => self.details.time.partial_cmp(&other.details.time),
+                (Some(price1), Some(price2)) => {
+                    // First compare by price, then displayed ahead of hidden, then by time
+                    match price1.partial_cmp(&price2) {
+                        Some(Ordering::Equal) => Some(other.is_hidden().cmp(&self.is_hidden())
+                            .then(other.details.time.cmp(&self.details.time))),
                         other => other,
                     }
                 },
-                (Market, Limit { .. }) => Some(Ordering::Greater),
-                (Limit { .. }, Market) => Some(Ordering::Less),
+                (None, Some(_)) => Some(Ordering::Greater),
+                (Some(_), None) => Some(Ordering::Less),
             }
-            Sell => match (&self.variant, &other.variant) {
-                (Market, Market) => other.details.time.partial_cmp(&self.details.time),
-                (Limit { price: price1 }, Limit { price: price2 }) => {
+            Sell => match (self.variant.limit(), other.variant.limit()) {
+                (None, None) => other.details.time.partial_cmp(&self.details.time),
+                (Some(price1), Some(price2)) => {
                     // Reverse price comparison: lower price has higher priority
-                    match price2.partial_cmp(price1) {
-                        Some(Ordering::Equal) => other.details.time.partial_cmp(&self.details.time),
+                    match price2.partial_cmp(&price1) {
+                        Some(Ordering::Equal) => Some(other.is_hidden().cmp(&self.is_hidden())
+                            .then(other.details.time.cmp(&self.details.time))),
                         other => other
                     }
                 },
-                (Market, Limit { .. }) => Some(Ordering::Greater),
-                (Limit { .. }, Market) => Some(Ordering::Less),
+                (None, Some(_)) => Some(Ordering::Greater),
+                (Some(_), None) => Some(Ordering::Less),
             }
         }
     }
@@ -76,21 +256,12 @@ impl PartialOrd for Order {
 
 impl PartialEq for Order {
     fn eq(&self, other: &Self) -> bool {
-        use OrderType::*;
-        match &self.order_type {
-            Buy => match (&self.variant, &other.variant) {
-                (OrderVariant::Market, OrderVariant::Market) => self.details.time == other.details.time,
-                (OrderVariant::Limit { price: price1 }, OrderVariant::Limit { price: price2 }) => price1 == price2 && self.details.time == other.details.time,
-                _ => false,
-            }
-            Sell => match (&self.variant, &other.variant) {
-                (OrderVariant::Market, OrderVariant::Market) => self.details.time == other.details.time,
-                (OrderVariant::Limit { price: price1 }, OrderVariant::Limit { price: price2 }) 
-                    => price1 == price2 && self.details.time == other.details.time,
-                _ => false,
-            }
+        match (self.variant.limit(), other.variant.limit()) {
+            (None, None) => self.details.time == other.details.time,
+            (Some(price1), Some(price2)) 
+                => price1 == price2 && self.is_hidden() == other.is_hidden() && self.details.time == other.details.time,
+            _ => false,
         }
-        
     }
 }
 
@@ -0,0 +1,128 @@
+use std::fs;
+use std::path::Path;
+
+use actix_web::{web, HttpResponse, Error};
+
+use crate::classes::api::{request_classes::*, response_classes::*};
+use crate::kernel::market::Market;
+use crate::kernel::order_book::circuit_breaker::PriceBand;
+use crate::kernel::agents::registry;
+use crate::kernel::{events, replay, snapshot};
+
+pub fn handle_halt(market: web::Data<Market>, req: web::Json<StockQuery>) -> Result<HttpResponse, Error> {
+    match STOCKMAP.get(&req.stock_name).filter(|stock| market.is_listed(**stock)) {
+        Some(stock) => {
+            market.halt(*stock);
+            Ok(HttpResponse::Ok().finish())
+        }
+        None => Ok(HttpResponse::NotFound().body("Stock not found"))
+    }
+}
+
+pub fn handle_resume(market: web::Data<Market>, req: web::Json<StockQuery>) -> Result<HttpResponse, Error> {
+    match STOCKMAP.get(&req.stock_name).filter(|stock| market.is_listed(**stock)) {
+        Some(stock) => {
+            market.resume(*stock);
+            Ok(HttpResponse::Ok().finish())
+        }
+        None => Ok(HttpResponse::NotFound().body("Stock not found"))
+    }
+}
+
+pub fn handle_price_band(market: web::Data<Market>, req: web::Json<PriceBandDTO>) -> Result<HttpResponse, Error> {
+    match STOCKMAP.get(&req.stock_name).filter(|stock| market.is_listed(**stock)) {
+        Some(stock) => {
+            let band = req.band.map(|band| PriceBand {
+                band: band,
+                halt_nanos: events::seconds_to_nanos(req.halt_seconds),
+                window_nanos: req.window_seconds.map(events::seconds_to_nanos)
+            });
+            if let Some(Err(e)) = band.map(|band| band.validate()) {
+                return Ok(HttpResponse::BadRequest().body(e));
+            }
+            market.set_price_band(*stock, band);
+            Ok(HttpResponse::Ok().finish())
+        }
+        None => Ok(HttpResponse::NotFound().body("Stock not found"))
+    }
+}
+
+pub fn handle_list_agents(market: web::Data<Market>) -> Result<HttpResponse, Error> {
+    let agents: Vec<AgentInfoDTO> = registry::list(&market).into_iter().map(Into::into).collect();
+    Ok(HttpResponse::Ok().json(agents))
+}
+
+pub fn handle_add_agent(market: web::Data<Market>, req: web::Json<AgentDTO>) -> Result<HttpResponse, Error> {
+    let Some(stock) = STOCKMAP.get(&req.stock_name).filter(|stock| market.is_listed(**stock)) else {
+        return Ok(HttpResponse::NotFound().body("Stock not found"))
+    };
+    match registry::create_agent(&req.kind, &req.params) {
+        Ok(agent) => {
+            let id = registry::add(&market, *stock, agent);
+            let info: AgentInfoDTO = registry::info(&market, id).unwrap().into();
+            Ok(HttpResponse::Ok().json(info))
+        }
+        Err(e) => Ok(HttpResponse::BadRequest().body(e))
+    }
+}
+
+pub fn handle_configure_agent(market: web::Data<Market>, id: web::Path<u64>, req: web::Json<AgentParamsDTO>) -> Result<HttpResponse, Error> {
+    match registry::configure(&market, *id, &req.params) {
+        Some(Ok(())) => {
+            let info: AgentInfoDTO = registry::info(&market, *id).unwrap().into();
+            Ok(HttpResponse::Ok().json(info))
+        }
+        Some(Err(e)) => Ok(HttpResponse::BadRequest().body(e)),
+        None => Ok(HttpResponse::NotFound().body("Agent not found"))
+    }
+}
+
+pub fn handle_remove_agent(market: web::Data<Market>, id: web::Path<u64>) -> Result<HttpResponse, Error> {
+    if registry::remove(&market, *id) {
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::NotFound().body("Agent not found"))
+    }
+}
+
+pub fn handle_events(market: web::Data<Market>, req: web::Json<Vec<EventDTO>>) -> Result<HttpResponse, Error> {
+    // check the whole script before any of it fires
+    let mut script = Vec::new();
+    for e in req.iter() {
+        let Some(stock) = STOCKMAP.get(&e.stock_name).filter(|stock| market.is_listed(**stock)) else {
+            return Ok(HttpResponse::NotFound().body(format!("Stock {} not found", e.stock_name)))
+        };
+        if let Err(err) = e.event.validate() {
+            return Ok(HttpResponse::BadRequest().body(err));
+        }
+        script.push((*stock, e.event, e.delay_seconds));
+    }
+
+    for (stock, event, delay_seconds) in script {
+        if delay_seconds > 0.0 {
+            events::schedule(&market, stock, event, delay_seconds);
+        } else {
+            events::trigger(&market, stock, event);
+        }
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+pub fn handle_snapshot(market: web::Data<Market>, dir: &Path, req: web::Json<SnapshotDTO>) -> Result<HttpResponse, Error> {
+    let path = match snapshot::resolve(dir, &req.path) {
+        Ok(path) => path,
+        Err(e) => return Ok(HttpResponse::BadRequest().body(e))
+    };
+    let saved = path.parent().map_or(Ok(()), fs::create_dir_all).and_then(|_| snapshot::save(&market, &path));
+    match saved {
+        Ok(()) => Ok(HttpResponse::Ok().finish()),
+        Err(e) => Ok(HttpResponse::InternalServerError().body(format!("could not write snapshot {}: {}", req.path, e)))
+    }
+}
+
+pub fn handle_replay_status(market: web::Data<Market>) -> Result<HttpResponse, Error> {
+    match replay::status(&market) {
+        Some(report) => Ok(HttpResponse::Ok().json(report)),
+        None => Ok(HttpResponse::NotFound().body("No replay has been run"))
+    }
+}
@@ -5,44 +5,198 @@ use crate::classes::{
     api::{request_classes::*, response_classes::*},
     shared::order::*
 };
-use crate::kernel::market::*;
+use crate::kernel::agents::registry;
+use crate::kernel::market::Market;
 
-pub fn handle_order(req: web::Json<OrderDTO>, order_type: OrderType) -> Result<HttpResponse, Error> {
-    match STOCKMAP.get(&req.stock_name) {
+// the agent kinds that work a parent order for a client
+const ALGOS: [&str; 3] = ["twap", "vwap", "pov"];
+
+pub fn handle_order(market: web::Data<Market>, req: web::Json<OrderDTO>, order_type: OrderType) -> Result<HttpResponse, Error> {
+    match STOCKMAP.get(&req.stock_name).filter(|stock| market.is_listed(**stock)) {
         Some(stock) => {
-            match order_type {
-                OrderType::Buy => buy(*stock, req.amount,req.price, None, None),
-                OrderType::Sell => sell(*stock, req.amount, req.price, None, None)
+            let visibility = match (req.display_amount, req.hidden) {
+                (None, false) => Visibility::Displayed,
+                (Some(peak), false) => Visibility::iceberg(peak),
+                (None, true) => Visibility::Hidden,
+                (Some(_), true) => return Ok(HttpResponse::BadRequest().body("an order is either hidden or has a display_amount"))
+            };
+            let variant = match (req.price, req.post_only, req.peg, req.trail) {
+                (Some(price), false, None, None) => OrderVariant::Limit { price: price },
+                (Some(price), true, None, None) => OrderVariant::PostOnly { price: price, slide: req.slide },
+                (None, false, Some(reference), None) => OrderVariant::Pegged { reference: reference, offset: req.offset, price: None },
+                (None, false, None, Some(trail)) => OrderVariant::TrailingStop { trail: trail, stop: None },
+                (None, false, None, None) => OrderVariant::Market,
+                (None, true, ..) => return Ok(HttpResponse::BadRequest().body("a post_only order needs a price")),
+                _ => return Ok(HttpResponse::BadRequest().body("an order has at most one of a price, a peg or a trail"))
+            };
+            if req.slide.is_some() && !req.post_only {
+                return Ok(HttpResponse::BadRequest().body("only post_only orders slide"));
+            }
+            if visibility != Visibility::Displayed && matches!(variant, OrderVariant::Market | OrderVariant::TrailingStop { .. }) {
+                return Ok(HttpResponse::BadRequest().body("only orders that rest on the book can be hidden or have a display_amount"));
             }
-            let price = get_price(*stock).to_string();
+            if !market.place_variant(*stock, req.amount, order_type, variant, None, req.time_in_force, visibility, req.owner, None) {
+                return Ok(HttpResponse::Conflict().body("order rejected"));
+            }
+            let price = market.get_price(*stock).to_string();
             Ok(HttpResponse::Ok().body(price))
         },
         None => Ok(HttpResponse::NotFound().body("Stock not found")),
     }
 }
 
-pub fn handle_stock_history(req: web::Json<PriceHistoryDTO>) -> Result<HttpResponse, Error> {
-    match STOCKMAP.get(&req.stock_name) {
+pub fn handle_oco(market: web::Data<Market>, req: web::Json<OcoDTO>) -> Result<HttpResponse, Error> {
+    let Some(stock) = STOCKMAP.get(&req.stock_name).filter(|stock| market.is_listed(**stock)) else {
+        return Ok(HttpResponse::NotFound().body("Stock not found"))
+    };
+    let mut legs = Vec::new();
+    for leg in &req.legs {
+        let variant = match (leg.price, leg.stop) {
+            (Some(price), None) => OrderVariant::Limit { price: price },
+            (None, Some(stop)) => OrderVariant::Stop { stop: stop },
+            (None, None) => OrderVariant::Market,
+            (Some(_), Some(_)) => return Ok(HttpResponse::BadRequest().body("a leg has a price or a stop, not both"))
+        };
+        legs.push((leg.side, leg.amount, variant));
+    }
+    match market.place_oco(*stock, &legs) {
+        Ok(id) => Ok(HttpResponse::Ok().json(market.group_status(id))),
+        Err(e) => Ok(HttpResponse::BadRequest().body(e))
+    }
+}
+
+pub fn handle_bracket(market: web::Data<Market>, req: web::Json<BracketDTO>) -> Result<HttpResponse, Error> {
+    let Some(stock) = STOCKMAP.get(&req.stock_name).filter(|stock| market.is_listed(**stock)) else {
+        return Ok(HttpResponse::NotFound().body("Stock not found"))
+    };
+    let entry = match req.price {
+        Some(price) => OrderVariant::Limit { price: price },
+        None => OrderVariant::Market
+    };
+    match market.place_bracket(*stock, req.side, req.amount, entry, req.take_profit, req.stop_loss) {
+        Ok(id) => Ok(HttpResponse::Ok().json(market.group_status(id))),
+        Err(e) => Ok(HttpResponse::BadRequest().body(e))
+    }
+}
+
+pub fn handle_group_status(market: web::Data<Market>, id: web::Path<u64>) -> Result<HttpResponse, Error> {
+    match market.group_status(*id) {
+        Some(status) => Ok(HttpResponse::Ok().json(status)),
+        None => Ok(HttpResponse::NotFound().body("Group not found"))
+    }
+}
+
+// takes what is left of the group off the book, answering with where it got to
+pub fn handle_cancel_group(market: web::Data<Market>, id: web::Path<u64>) -> Result<HttpResponse, Error> {
+    market.cancel_group(*id);
+    handle_group_status(market, id)
+}
+
+pub fn handle_stock_history(market: web::Data<Market>, req: web::Json<PriceHistoryDTO>) -> Result<HttpResponse, Error> {
+    match STOCKMAP.get(&req.stock_name).filter(|stock| market.is_listed(**stock)) {
         Some(stock) => {
-            let ret = get_stock_history(*stock, req.granularity, req.count);
+            let ret = market.get_stock_history(*stock, req.granularity, req.count);
             Ok(HttpResponse::Ok().json(ret))
         }
         None => Ok(HttpResponse::NotFound().body("Stock not found"))
     }
 }
 
-pub fn handle_ipo(req: web::Json<IpoDTO>) -> Result<HttpResponse, Error> {
-    let stock = STOCKMAP.get(&req.stock_name).unwrap();
-    ipo(*stock, req.amount, req.price, None);
+pub fn handle_ipo(market: web::Data<Market>, req: web::Json<IpoDTO>) -> Result<HttpResponse, Error> {
+    // listing is what an ipo does, so the stock only has to be one the server knows
+    let Some(stock) = STOCKMAP.get(&req.stock_name) else {
+        return Ok(HttpResponse::NotFound().body("Stock not found"))
+    };
+    market.ipo(*stock, req.amount, req.price, None);
     Ok(HttpResponse::Ok().finish())
 }
 
-pub fn handle_price(req: web::Query<StockQuery>) -> Result<HttpResponse, Error>{
-    let stock = STOCKMAP.get(&req.stock_name).unwrap();
-    
+pub fn handle_price(market: web::Data<Market>, req: web::Query<StockQuery>) -> Result<HttpResponse, Error>{
+    let Some(stock) = STOCKMAP.get(&req.stock_name).filter(|stock| market.is_listed(**stock)) else {
+        return Ok(HttpResponse::NotFound().body("Stock not found"))
+    };
+
     let res = PriceDTO {
-        price: get_price(*stock),
-        timestamp: Utc::now().timestamp_millis()
+        price: market.get_price(*stock),
+        timestamp: Utc::now().timestamp_millis(),
+        halt: market.get_halt(*stock)
     };
     Ok(HttpResponse::Ok().content_type("text/plain").body(serde_json::to_string(&res).unwrap()))
-}
\ No newline at end of file
+}
+
+pub fn handle_session(market: web::Data<Market>, req: web::Query<StockQuery>) -> Result<HttpResponse, Error> {
+    match STOCKMAP.get(&req.stock_name).filter(|stock| market.is_listed(**stock)) {
+        Some(stock) => {
+            let res = SessionDTO {
+                phase: market.get_session_phase(*stock),
+                simulated_time: market.clock.simulated_datetime(market.clock.now()).to_rfc3339()
+            };
+            Ok(HttpResponse::Ok().json(res))
+        }
+        None => Ok(HttpResponse::NotFound().body("Stock not found"))
+    }
+}
+
+pub fn handle_depth(market: web::Data<Market>, req: web::Query<DepthQuery>) -> Result<HttpResponse, Error> {
+    match STOCKMAP.get(&req.stock_name).filter(|stock| market.is_listed(**stock)) {
+        Some(stock) => {
+            let (bids, asks) = market.get_depth(*stock, req.levels.unwrap_or(10));
+            let to_levels = |levels: Vec<(f64, u64)>| levels.into_iter()
+                .map(|(price, volume)| LevelDTO { price, volume })
+                .collect();
+
+            let res = DepthDTO {
+                bids: to_levels(bids),
+                asks: to_levels(asks),
+                phase: market.get_session_phase(*stock),
+                halt: market.get_halt(*stock)
+            };
+            Ok(HttpResponse::Ok().json(res))
+        }
+        None => Ok(HttpResponse::NotFound().body("Stock not found"))
+    }
+}
+pub fn handle_events(market: web::Data<Market>, req: web::Query<StockQuery>) -> Result<HttpResponse, Error> {
+    match STOCKMAP.get(&req.stock_name).filter(|stock| market.is_listed(**stock)) {
+        Some(stock) => {
+            let events: Vec<EventRecordDTO> = market.get_events(*stock).into_iter()
+                .map(|record| EventRecordDTO::new(record, &market.clock))
+                .collect();
+            Ok(HttpResponse::Ok().json(events))
+        }
+        None => Ok(HttpResponse::NotFound().body("Stock not found"))
+    }
+}
+
+pub fn handle_algo(market: web::Data<Market>, req: web::Json<AlgoDTO>) -> Result<HttpResponse, Error> {
+    let Some(stock) = STOCKMAP.get(&req.stock_name).filter(|stock| market.is_listed(**stock)) else {
+        return Ok(HttpResponse::NotFound().body("Stock not found"))
+    };
+    if !ALGOS.contains(&req.algo.as_str()) {
+        return Ok(HttpResponse::BadRequest().body(format!("unknown algo '{}', use twap, vwap or pov", req.algo)));
+    }
+    match registry::create_agent(&req.algo, &req.params) {
+        Ok(agent) => {
+            let id = registry::add(&market, *stock, agent);
+            let info: AgentInfoDTO = registry::info(&market, id).unwrap().into();
+            Ok(HttpResponse::Ok().json(info))
+        }
+        Err(e) => Ok(HttpResponse::BadRequest().body(e))
+    }
+}
+
+pub fn handle_algo_status(market: web::Data<Market>, id: web::Path<u64>) -> Result<HttpResponse, Error> {
+    match registry::info(&market, *id).filter(|info| ALGOS.contains(&info.kind)) {
+        Some(info) => Ok(HttpResponse::Ok().json(AgentInfoDTO::from(info))),
+        None => Ok(HttpResponse::NotFound().body("Algo not found"))
+    }
+}
+
+// cancels the parent along with its working child order, answering with where it got to
+pub fn handle_cancel_algo(market: web::Data<Market>, id: web::Path<u64>) -> Result<HttpResponse, Error> {
+    let Some(info) = registry::info(&market, *id).filter(|info| ALGOS.contains(&info.kind)) else {
+        return Ok(HttpResponse::NotFound().body("Algo not found"))
+    };
+    registry::remove(&market, *id);
+    Ok(HttpResponse::Ok().json(AgentInfoDTO::from(info)))
+}
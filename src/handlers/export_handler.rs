@@ -0,0 +1,50 @@
+use actix_web::{web, web::Bytes, HttpResponse, Error};
+use chrono::{DateTime, Utc};
+use futures_util::stream;
+
+use crate::classes::api::request_classes::*;
+use crate::globals::GRANULARITY;
+use crate::kernel::export;
+use crate::kernel::market::Market;
+use crate::kernel::market_time::market_time::MTime;
+
+// market time bounds of an export, unbounded where the query leaves them out
+fn time_range(clock: &MTime, req: &ExportQuery) -> Result<(i64, i64), String> {
+    let parse = |time: &Option<String>, unbounded: i64| match time {
+        Some(time) => DateTime::parse_from_rfc3339(time)
+            .map(|t| clock.from_simulated_datetime(t.with_timezone(&Utc)))
+            .map_err(|_| format!("'{}' is not an RFC 3339 date time", time)),
+        None => Ok(unbounded)
+    };
+    Ok((parse(&req.from, i64::MIN)?, parse(&req.to, i64::MAX)?))
+}
+
+fn stream_chunks(format: export::ExportFormat, chunks: impl Iterator<Item = Vec<u8>> + 'static) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .streaming(stream::iter(chunks.map(|chunk| Ok::<Bytes, Error>(Bytes::from(chunk)))))
+}
+
+pub fn handle_export_trades(market: web::Data<Market>, req: web::Query<ExportQuery>) -> Result<HttpResponse, Error> {
+    let Some(stock) = STOCKMAP.get(&req.stock_name).filter(|stock| market.is_listed(**stock)) else {
+        return Ok(HttpResponse::NotFound().body("Stock not found"))
+    };
+    match time_range(&market.clock, &req) {
+        Ok((from, to)) => Ok(stream_chunks(req.format, export::trades(market.into_inner(), *stock, from, to, req.format))),
+        Err(e) => Ok(HttpResponse::BadRequest().body(e))
+    }
+}
+
+pub fn handle_export_candles(market: web::Data<Market>, req: web::Query<ExportQuery>) -> Result<HttpResponse, Error> {
+    let Some(stock) = STOCKMAP.get(&req.stock_name).filter(|stock| market.is_listed(**stock)) else {
+        return Ok(HttpResponse::NotFound().body("Stock not found"))
+    };
+    let granularity = req.granularity.unwrap_or(GRANULARITY::MINUTE);
+    if matches!(granularity, GRANULARITY::INSTANT) {
+        return Ok(HttpResponse::BadRequest().body("granularity must be SECOND, MINUTE, HOUR or DAY"));
+    }
+    match time_range(&market.clock, &req) {
+        Ok((from, to)) => Ok(stream_chunks(req.format, export::candles(market.into_inner(), *stock, granularity, from, to, req.format))),
+        Err(e) => Ok(HttpResponse::BadRequest().body(e))
+    }
+}
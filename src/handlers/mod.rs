@@ -1 +1,4 @@
-pub mod api_handler;
\ No newline at end of file
+pub mod api_handler;
+pub mod admin_handler;
+pub mod export_handler;
+pub mod session_handler;
\ No newline at end of file
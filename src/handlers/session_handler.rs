@@ -0,0 +1,63 @@
+use std::future::{ready, Ready};
+
+use actix_web::{dev::Payload, error, web, Error, FromRequest, HttpRequest, HttpResponse};
+
+use crate::classes::api::{request_classes::*, response_classes::*};
+use crate::kernel::market::Market;
+use crate::sessions::{Sessions, DEFAULT_SESSION};
+
+/// The market a request trades on and the session's name, the session named in its
+/// /sessions/{name} path or the default session for the routes outside one
+pub struct SessionMarket(pub web::Data<Market>, pub String);
+
+impl FromRequest for SessionMarket {
+    type Error = Error;
+    type Future = Ready<Result<Self, Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let name = req.match_info().get("name").unwrap_or(DEFAULT_SESSION);
+        let market = req.app_data::<web::Data<Sessions>>().and_then(|sessions| sessions.get(name));
+        ready(match market {
+            Some(market) => Ok(SessionMarket(web::Data::from(market), name.to_string())),
+            None => Err(error::ErrorNotFound(format!("Session {} not found", name)))
+        })
+    }
+}
+
+fn info(name: &str, market: &Market) -> SessionInfoDTO {
+    let mut stocks: Vec<String> = market.listed().iter().map(|stock| format!("{:?}", stock)).collect();
+    stocks.sort();
+    SessionInfoDTO {
+        name: name.to_string(),
+        stocks: stocks,
+        simulated_time: market.clock.simulated_datetime(market.clock.now()).to_rfc3339()
+    }
+}
+
+pub fn handle_list_sessions(sessions: web::Data<Sessions>) -> Result<HttpResponse, Error> {
+    let infos: Vec<SessionInfoDTO> = sessions.names().iter()
+        .filter_map(|name| sessions.get(name).map(|market| info(name, &market)))
+        .collect();
+    Ok(HttpResponse::Ok().json(infos))
+}
+
+pub fn handle_create_session(sessions: web::Data<Sessions>, req: web::Json<NewSessionDTO>) -> Result<HttpResponse, Error> {
+    if sessions.get(&req.name).is_some() {
+        return Ok(HttpResponse::Conflict().body(format!("Session {} already exists", req.name)));
+    }
+    match sessions.create(&req.name, &req.scenario) {
+        Ok(market) => Ok(HttpResponse::Ok().json(info(&req.name, &market))),
+        Err(e) => Ok(HttpResponse::BadRequest().body(e))
+    }
+}
+
+pub fn handle_remove_session(sessions: web::Data<Sessions>, name: web::Path<String>) -> Result<HttpResponse, Error> {
+    if name.as_str() == DEFAULT_SESSION {
+        return Ok(HttpResponse::BadRequest().body("The default session can't be removed"));
+    }
+    match sessions.remove(&name) {
+        Some(Ok(())) => Ok(HttpResponse::Ok().finish()),
+        Some(Err(e)) => Ok(HttpResponse::InternalServerError().body(format!("Session {} stopped but its journal failed to close: {}", name, e))),
+        None => Ok(HttpResponse::NotFound().body(format!("Session {} not found", name)))
+    }
+}
@@ -0,0 +1,148 @@
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::classes::shared::{order::*, transaction::Transaction};
+use crate::kernel::{events::MarketEvent, market::Market};
+
+use super::registry;
+
+pub type AgentId = u64;
+
+// a slice of one of the agent's own orders being executed
+#[derive(Debug, Copy, Clone)]
+pub struct Fill {
+    pub order_id: u64,
+    pub order_type: OrderType,
+    pub price: f64,
+    pub volume: u64,
+    pub timestamp: i64
+}
+
+pub struct MarketData<'a> {
+    pub price: f64,
+    pub transactions: &'a [Transaction]
+}
+
+/// Handed to an agent on every callback, identifies it and lets it trade on its instrument.
+/// Orders placed through the context are tracked so that their fills come back through on_fill.
+pub struct AgentContext<'a> {
+    pub id: AgentId,
+    pub stock: Stock,
+    pub market: &'a Market
+}
+
+impl AgentContext<'_> {
+    pub fn price(&self) -> f64 {
+        self.market.get_price(self.stock)
+    }
+
+    /// Halfway between the best bid and ask, the last price while either side is empty
+    pub fn mid(&self) -> f64 {
+        match self.market.get_quotes(self.stock) {
+            (Some(bid), Some(ask)) => (bid + ask) / 2.0,
+            _ => self.price()
+        }
+    }
+
+    pub fn now(&self) -> i64 {
+        self.market.clock.now()
+    }
+
+    pub fn buy(&self, amount: u64, price: Option<f64>, lifetime: Option<i64>) -> Option<u64> {
+        self.place(OrderType::Buy, amount, price, lifetime)
+    }
+
+    pub fn sell(&self, amount: u64, price: Option<f64>, lifetime: Option<i64>) -> Option<u64> {
+        self.place(OrderType::Sell, amount, price, lifetime)
+    }
+
+    pub fn cancel(&self, order_id: u64) -> bool {
+        self.market.cancel(self.stock, order_id)
+    }
+
+    /// An order in the agent's name that isn't tracked, its fills don't come back through on_fill
+    pub fn submit(&self, order_type: OrderType, amount: u64, price: Option<f64>, lifetime: Option<i64>) {
+        self.send(order_type, amount, price, lifetime, None);
+    }
+
+    fn place(&self, order_type: OrderType, amount: u64, price: Option<f64>, lifetime: Option<i64>) -> Option<u64> {
+        if amount == 0 {
+            return None;
+        }
+        let order_id = self.market.next_order_id();
+        registry::track_order(self.market, order_id, self.id, self.stock, self.now());
+        self.send(order_type, amount, price, lifetime, Some(order_id));
+        Some(order_id)
+    }
+
+    // the agent owns everything it sends, so that self-trade prevention can keep it from trading with itself
+    fn send(&self, order_type: OrderType, amount: u64, price: Option<f64>, lifetime: Option<i64>, id: Option<u64>) {
+        let variant = match price {
+            Some(p) => OrderVariant::Limit { price: p },
+            None => OrderVariant::Market
+        };
+        self.market.place_variant(self.stock, amount, order_type, variant, lifetime, TimeInForce::GoodTillCancel, Visibility::Displayed, Some(self.id), id);
+    }
+}
+
+pub trait Agent: Send {
+    fn kind(&self) -> &'static str;
+
+    fn init(&mut self, _ctx: &AgentContext) {}
+
+    // in deterministic mode an agent without a seed of its own is handed one drawn from the simulation seed
+    fn reseed(&mut self, _seed: u64) {}
+
+    fn on_tick(&mut self, ctx: &AgentContext);
+
+    fn on_fill(&mut self, _ctx: &AgentContext, _fill: &Fill) {}
+
+    fn on_market_data(&mut self, _ctx: &AgentContext, _data: &MarketData) {}
+
+    fn on_event(&mut self, _ctx: &AgentContext, _event: &MarketEvent) {}
+
+    // called when the agent is taken out of the registry, a chance to pull resting orders
+    fn on_remove(&mut self, _ctx: &AgentContext) {}
+
+    fn config(&self) -> Value;
+
+    fn configure(&mut self, params: &Value) -> Result<(), String>;
+
+    // runtime state worth reporting (inventory, P&L...) alongside the config
+    fn state(&self) -> Value {
+        Value::Null
+    }
+
+    // everything besides the config the agent needs to carry on after a restart,
+    // random generators are reseeded rather than saved
+    fn snapshot(&self) -> Value {
+        Value::Null
+    }
+
+    fn restore(&mut self, _state: &Value) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Reads one field of a snapshot taken by Agent::snapshot
+pub fn snapshot_field<T: DeserializeOwned>(state: &Value, field: &str) -> Result<T, String> {
+    serde_json::from_value(state[field].clone()).map_err(|e| format!("{}: {}", field, e))
+}
+
+/// Overlays the (possibly partial) params object onto an agent's current config
+pub fn merge_config<T: Serialize + DeserializeOwned>(config: &T, params: &Value) -> Result<T, String> {
+    let mut merged = serde_json::to_value(config).map_err(|e| e.to_string())?;
+    match (merged.as_object_mut(), params) {
+        (_, Value::Null) => {},
+        (Some(current), Value::Object(updates)) => {
+            for (key, value) in updates {
+                if !current.contains_key(key) {
+                    return Err(format!("unknown parameter '{}'", key));
+                }
+                current.insert(key.clone(), value.clone());
+            }
+        },
+        _ => return Err("agent parameters must be an object".to_string())
+    }
+    serde_json::from_value(merged).map_err(|e| e.to_string())
+}
@@ -1,7 +1,7 @@
-use crate::kernel::market;
+use crate::kernel::market::Market;
 use crate::classes::shared::order::*;
 
-pub fn clean_books(stock: Stock) {
-    let book = market::clean_books(stock);
+pub fn clean_books(market: &Market, stock: Stock) {
+    let book = market.clean_books(stock);
     // TODO: Report cleaned trades
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
-use crate::kernel::market;
+use crate::kernel::market::Market;
 use crate::classes::shared::order::*;
 
-pub fn find_trades(stock: Stock) {
-    market::find_trades(stock);
+pub fn find_trades(market: &Market, stock: Stock) {
+    market.find_trades(stock);
 }
\ No newline at end of file
@@ -0,0 +1,7 @@
+use crate::kernel::events;
+use crate::kernel::market::Market;
+use crate::classes::shared::order::*;
+
+pub fn fire_events(market: &Market, stock: Stock) {
+    events::fire_due(market, stock);
+}
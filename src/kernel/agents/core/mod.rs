@@ -1,4 +1,7 @@
 pub mod clean_books; 
 pub mod report_transactions;
 pub mod find_trades;
-pub mod update_stats;
\ No newline at end of file
+pub mod update_stats;
+pub mod update_session;
+pub mod run_agents;
+pub mod fire_events;
\ No newline at end of file
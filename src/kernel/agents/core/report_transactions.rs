@@ -1,7 +1,10 @@
-use crate::kernel::market;
-use crate::classes::shared::{order::*,  transaction::*};
+use crate::kernel::market::Market;
+use crate::kernel::agents::registry;
+use crate::classes::shared::{order::*, transaction::Transaction};
 
-pub fn report_transactions(stock: Stock) {
-    let transactions = market::report_transactions(stock);
-    // TODO: Record these transactions somewhere
-}
\ No newline at end of file
+pub fn report_transactions(market: &Market, stock: Stock) -> Vec<Transaction> {
+    let transactions = market.report_transactions(stock);
+    // trades are journaled as they match, see kernel::journal
+    registry::publish(market, stock, &transactions);
+    transactions
+}
@@ -0,0 +1,7 @@
+use crate::kernel::agents::registry;
+use crate::kernel::market::Market;
+use crate::classes::shared::order::*;
+
+pub fn run_agents(market: &Market, stock: Stock) {
+    registry::tick(market, stock);
+}
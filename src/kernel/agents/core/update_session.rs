@@ -0,0 +1,6 @@
+use crate::kernel::market::Market;
+use crate::classes::shared::order::*;
+
+pub fn update_session(market: &Market, stock: Stock) {
+    market.update_session(stock);
+}
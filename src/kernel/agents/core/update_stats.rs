@@ -1,6 +1,6 @@
-use crate::kernel::market;
+use crate::kernel::market::Market;
 use crate::classes::shared::order::*;
 
-pub fn update_stats(stock: Stock) {
-    market::update_stats(stock);
+pub fn update_stats(market: &Market, stock: Stock) {
+    market.update_stats(stock);
 }
\ No newline at end of file
@@ -1,33 +1,145 @@
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use std::thread::{spawn, sleep};
 
-use crate::classes::shared::order::*;
+use crate::classes::shared::{order::*, transaction::Transaction};
+use crate::kernel::market::Market;
+use crate::kernel::snapshot::Checkpoint;
 
-use super::core::update_stats::update_stats;
+use super::core::{update_stats::update_stats, update_session::update_session, run_agents::run_agents, fire_events::fire_events};
+use super::{agent::Agent, registry};
 use super::trend::{chaotic_trend_generator::*, market_maker::*};
+use super::population::{canceller::*, hawkes::*, noise_trader::*, zero_intelligence::*};
+use super::strategies::{chartist::*, fundamental_trader::*};
 // TODO: Refactor this into somewhere else
 use super::core::{clean_books::*, report_transactions::*, find_trades::*};
 
 // ticks per second, should describe the max tickrate
 const TICKRATE: f64 = 10000.0;
 
-pub fn make_market(stock: Stock) {
-    dispatch(generate_trend, stock, TICKRATE);
-    dispatch(straddle, stock, TICKRATE);
-    dispatch(find_trades, stock, TICKRATE);
-    dispatch(clean_books, stock, TICKRATE/100.0);
-    dispatch(report_transactions, stock, TICKRATE/10.0);
-    dispatch(update_stats, stock, TICKRATE/10.0);
+// the population a market runs with when the scenario does not name one. the inventory maker
+// took the place of `Straddle`, which scenarios can still ask for as "straddle"
+pub fn default_agents() -> Vec<Box<dyn Agent>> {
+    vec![
+        Box::new(ChaoticTrendGenerator::default()),
+        Box::new(InventoryMarketMaker::default()),
+        Box::new(ZeroIntelligenceTrader::default()),
+        Box::new(NoiseTrader::default()),
+        Box::new(RandomCanceller::default()),
+        Box::new(HawkesTrader::default()),
+        Box::new(FundamentalTrader::default()),
+        Box::new(Chartist::new(ChartistStyle::Momentum)),
+        Box::new(Chartist::new(ChartistStyle::MeanReversion))
+    ]
 }
 
-fn dispatch(f: fn(Stock) -> (), stock: Stock, tickrate: f64){
+pub fn populate(market: &Market, stock: Stock, agents: Vec<Box<dyn Agent>>) {
+    for agent in agents {
+        registry::add(market, stock, agent);
+    }
+}
+
+pub fn make_market(market: &Arc<Market>, stock: Stock, agents: Vec<Box<dyn Agent>>) {
+    populate(market, stock, agents);
+    run_market(market, stock);
+}
+
+// starts the cycle for a stock already listed and populated
+pub fn run_market(market: &Arc<Market>, stock: Stock) {
+    dispatch(run_agents, market, stock, TICKRATE);
+    dispatch(find_trades, market, stock, TICKRATE);
+    dispatch(clean_books, market, stock, TICKRATE/100.0);
+    dispatch(|market, stock| { report_transactions(market, stock); }, market, stock, TICKRATE/10.0);
+    dispatch(update_stats, market, stock, TICKRATE/10.0);
+    dispatch(update_session, market, stock, TICKRATE/10.0);
+    dispatch(fire_events, market, stock, TICKRATE/10.0);
+}
+
+/// The same cycle as make_market, run on a single thread in a fixed order against the virtual clock.
+/// Every tick advances market time by `tick_nanos`, the slower functions run every 10th and
+/// 100th tick as they do under dispatch, so the same scenario and seed always give the same tape.
+pub struct DeterministicCycle {
+    market: Arc<Market>,
+    stocks: Vec<Stock>,
+    tick_nanos: i64,
+    ticks: u64,
+    checkpoint: Option<Checkpoint>
+}
+
+impl DeterministicCycle {
+    pub fn new(market: Arc<Market>, stocks: Vec<Stock>, tick_nanos: i64) -> Self {
+        DeterministicCycle {
+            market: market,
+            stocks: stocks,
+            tick_nanos: tick_nanos,
+            ticks: 0,
+            checkpoint: None
+        }
+    }
+
+    // snapshots are taken between ticks, so a restored loop picks up on a tick boundary
+    pub fn set_checkpoint(&mut self, checkpoint: Checkpoint) {
+        self.checkpoint = Some(checkpoint);
+    }
+
+    // one market tick's worth of real time at the dispatch tickrate
+    pub fn default_tick_nanos() -> i64 {
+        (1e9 / TICKRATE) as i64
+    }
+
+    /// Runs one tick for every stock, returning the transactions reported during it
+    pub fn step(&mut self) -> Vec<Transaction> {
+        let market = &self.market;
+        market.clock.advance(self.tick_nanos);
+        let mut reported = Vec::new();
+        for &stock in &self.stocks {
+            if self.ticks.is_multiple_of(10) {
+                fire_events(market, stock);
+                update_session(market, stock);
+            }
+            run_agents(market, stock);
+            find_trades(market, stock);
+            if self.ticks.is_multiple_of(10) {
+                reported.extend(report_transactions(market, stock));
+                update_stats(market, stock);
+            }
+            if self.ticks.is_multiple_of(100) {
+                clean_books(market, stock);
+            }
+        }
+        self.ticks += 1;
+        if let Some(checkpoint) = self.checkpoint.as_mut() {
+            checkpoint.poll(market);
+        }
+        reported
+    }
+
+    /// Steps until the market is shut down, keeping no tape
+    pub fn run(&mut self) {
+        while !self.market.is_stopped() {
+            self.step();
+        }
+    }
+
+    /// Steps until market time reaches `end` (or the market is shut down), returning the trade tape
+    pub fn run_until(&mut self, end: i64) -> Vec<Transaction> {
+        let mut tape = Vec::new();
+        while self.market.clock.now() < end && !self.market.is_stopped() {
+            tape.extend(self.step());
+        }
+        tape
+    }
+}
+
+fn dispatch(f: fn(&Market, Stock) -> (), market: &Arc<Market>, stock: Stock, tickrate: f64){
     // dispatches a function f acting on a stock stock, tickrate times per second.
     // designed to be ran in it's own thread
+    let market = Arc::clone(market);
     spawn(move || {
         let tick_interval = Duration::new(0, (1_000_000_000.0 / tickrate) as u32);
         let mut last_tick = Instant::now();
-        loop {
-            f(stock);
+        while !market.is_stopped() {
+            f(&market, stock);
 
             // RATELIMIT
             let now = Instant::now();
@@ -37,4 +149,4 @@ fn dispatch(f: fn(Stock) -> (), stock: Stock, tickrate: f64){
             last_tick += tick_interval;
         }
     });
-}
\ No newline at end of file
+}
@@ -0,0 +1,293 @@
+use std::collections::BTreeSet;
+
+use chrono::Timelike;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::classes::shared::order::OrderType;
+use crate::globals::GRANULARITY;
+use crate::kernel::agents::agent::*;
+use crate::kernel::events::seconds_to_nanos;
+
+const SECONDS_PER_HOUR: i64 = 60 * 60;
+const SECONDS_PER_DAY: i64 = 24 * SECONDS_PER_HOUR;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionStyle {
+    // even slices over the duration
+    Twap,
+    // slices sized by the volume the stock traded at the same time of day the day before
+    Vwap,
+    // keeps up with a fraction of everyone else's volume as it prints
+    Pov
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ExecutionConfig {
+    pub side: OrderType,
+    // the size of the parent order
+    pub amount: u64,
+    // buys never pay more and sells never take less, child orders are market orders when left out
+    pub limit_price: Option<f64>,
+    // simulated seconds to work the parent over, whatever hasn't filled by then is abandoned
+    pub duration_seconds: f64,
+    // twap and vwap, how many child orders the parent is cut into
+    pub slices: u64,
+    // pov, the fraction of everyone else's volume to trade
+    pub participation: f64,
+    // pov, simulated seconds between child orders
+    pub interval_seconds: f64
+}
+
+impl Default for ExecutionConfig {
+    fn default() -> Self {
+        ExecutionConfig {
+            side: OrderType::Buy,
+            amount: 0,
+            limit_price: None,
+            duration_seconds: 600.0,
+            slices: 10,
+            participation: 0.1,
+            interval_seconds: 5.0
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionStatus {
+    #[default]
+    Working,
+    Completed,
+    // the duration ran out first
+    Expired
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct ExecutionState {
+    start: i64,
+    status: ExecutionStatus,
+    filled: u64,
+    // value of the fills, for the average price
+    cost: f64,
+    // traded by everyone else since the start
+    market_volume: u64,
+    // twap and vwap, each slice's share of the parent
+    weights: Vec<f64>,
+    // the slice (or pov interval) the working child order went out in
+    last_slice: Option<u64>,
+    child: Option<u64>,
+    children: BTreeSet<u64>
+}
+
+/// Works a parent order on the server, slicing it into child orders over simulated time. Only one child
+/// is ever working, it is cancelled and replaced by the next, sized to catch up with the schedule.
+/// Fills are counted off the stock's trades as they match, so the schedule never waits on a report.
+pub struct ExecutionAlgo {
+    style: ExecutionStyle,
+    config: ExecutionConfig,
+    state: ExecutionState,
+    // how many of the stock's trades have been looked through, found again after a restore
+    trades_seen: Option<usize>
+}
+
+impl ExecutionAlgo {
+    pub fn new(style: ExecutionStyle) -> Self {
+        ExecutionAlgo {
+            style: style,
+            config: ExecutionConfig::default(),
+            state: ExecutionState::default(),
+            trades_seen: None
+        }
+    }
+
+    fn end(&self) -> i64 {
+        self.state.start + seconds_to_nanos(self.config.duration_seconds)
+    }
+
+    fn slice_nanos(&self) -> i64 {
+        (seconds_to_nanos(self.config.duration_seconds) / self.config.slices as i64).max(1)
+    }
+
+    fn count_fills(&mut self, ctx: &AgentContext) {
+        let seen = *self.trades_seen.get_or_insert_with(|| ctx.market.trade_count(ctx.stock));
+        let trades = ctx.market.trades_since(ctx.stock, seen);
+        self.trades_seen = Some(seen + trades.len());
+
+        let state = &mut self.state;
+        for t in trades {
+            let mine = |id: Option<u64>| id.is_some_and(|id| state.children.contains(&id));
+            let (bought, sold) = (mine(t.buy_id), mine(t.sell_id));
+            let filled = match self.config.side {
+                OrderType::Buy => bought,
+                OrderType::Sell => sold
+            };
+            if filled {
+                state.filled += t.volume;
+                state.cost += t.price * t.volume as f64;
+            } else if !bought && !sold {
+                state.market_volume += t.volume;
+            }
+        }
+    }
+
+    // where the parent should have got to by now, and the slice that puts it in
+    fn target(&mut self, ctx: &AgentContext) -> (u64, u64) {
+        let elapsed = ctx.now() - self.state.start;
+        let amount = self.config.amount;
+        match self.style {
+            ExecutionStyle::Twap | ExecutionStyle::Vwap => {
+                if self.state.weights.len() != self.config.slices as usize {
+                    self.state.weights = self.weights(ctx);
+                }
+                let slice = ((elapsed / self.slice_nanos()) as u64).min(self.config.slices - 1);
+                let share: f64 = self.state.weights[..=slice as usize].iter().sum();
+                (slice, ((amount as f64 * share).round() as u64).min(amount))
+            },
+            ExecutionStyle::Pov => {
+                let slice = (elapsed / seconds_to_nanos(self.config.interval_seconds).max(1)) as u64;
+                let target = (self.state.market_volume as f64 * self.config.participation) as u64;
+                (slice, target.min(amount))
+            }
+        }
+    }
+
+    // each slice's share of the parent, adding up to 1
+    fn weights(&self, ctx: &AgentContext) -> Vec<f64> {
+        let slices = self.config.slices as usize;
+        let mut weights = vec![0.0; slices];
+        if self.style == ExecutionStyle::Vwap {
+            // the history keeps hour bars back to the last whole day, each spreads its volume over
+            // the slices covering the same hour of the day
+            let clock = &ctx.market.clock;
+            let time_of_day = |t: i64| clock.simulated_datetime(t).num_seconds_from_midnight() as i64;
+            let slice_seconds = (self.slice_nanos() / GRANULARITY::SECOND as i64).clamp(1, SECONDS_PER_DAY);
+            let starts: Vec<i64> = (0..slices).map(|k| time_of_day(self.state.start + k as i64 * self.slice_nanos())).collect();
+            for bar in ctx.market.get_bars(ctx.stock, GRANULARITY::HOUR, usize::MAX) {
+                let hour = time_of_day(bar.tick * GRANULARITY::HOUR as i64);
+                for (k, &start) in starts.iter().enumerate() {
+                    let offset = (start - hour).rem_euclid(SECONDS_PER_DAY);
+                    // the slice may wrap round midnight back into the hour
+                    let overlap = [offset, offset - SECONDS_PER_DAY].iter()
+                        .map(|&from| ((from + slice_seconds).min(SECONDS_PER_HOUR) - from.max(0)).max(0))
+                        .sum::<i64>();
+                    weights[k] += bar.volume as f64 * overlap as f64 / SECONDS_PER_HOUR as f64;
+                }
+            }
+        }
+        let total: f64 = weights.iter().sum();
+        if total > 0.0 {
+            weights.iter().map(|w| w / total).collect()
+        } else {
+            // no history to go on, the same as twap
+            vec![1.0 / slices as f64; slices]
+        }
+    }
+
+    fn finish(&mut self, ctx: &AgentContext, status: ExecutionStatus) {
+        if let Some(child) = self.state.child.take() {
+            ctx.cancel(child);
+        }
+        self.state.status = status;
+    }
+}
+
+impl Agent for ExecutionAlgo {
+    fn kind(&self) -> &'static str {
+        match self.style {
+            ExecutionStyle::Twap => "twap",
+            ExecutionStyle::Vwap => "vwap",
+            ExecutionStyle::Pov => "pov"
+        }
+    }
+
+    fn init(&mut self, ctx: &AgentContext) {
+        self.state.start = ctx.now();
+    }
+
+    fn on_tick(&mut self, ctx: &AgentContext) {
+        if self.state.status != ExecutionStatus::Working {
+            return;
+        }
+        self.count_fills(ctx);
+        if self.state.filled >= self.config.amount {
+            return self.finish(ctx, ExecutionStatus::Completed);
+        }
+        if ctx.now() >= self.end() {
+            return self.finish(ctx, ExecutionStatus::Expired);
+        }
+
+        let (slice, target) = self.target(ctx);
+        if self.state.last_slice == Some(slice) {
+            return;
+        }
+        self.state.last_slice = Some(slice);
+        if let Some(child) = self.state.child.take() {
+            ctx.cancel(child);
+        }
+        let size = target.saturating_sub(self.state.filled);
+        self.state.child = match self.config.side {
+            OrderType::Buy => ctx.buy(size, self.config.limit_price, None),
+            OrderType::Sell => ctx.sell(size, self.config.limit_price, None)
+        };
+        self.state.children.extend(self.state.child);
+    }
+
+    fn on_remove(&mut self, ctx: &AgentContext) {
+        if let Some(child) = self.state.child.take() {
+            ctx.cancel(child);
+        }
+    }
+
+    fn config(&self) -> Value {
+        serde_json::to_value(&self.config).unwrap()
+    }
+
+    fn configure(&mut self, params: &Value) -> Result<(), String> {
+        let config: ExecutionConfig = merge_config(&self.config, params)?;
+        if config.amount == 0 {
+            return Err("amount must be positive".to_string());
+        }
+        if matches!(config.limit_price, Some(p) if !(p > 0.0 && p.is_finite())) {
+            return Err("limit_price must be positive".to_string());
+        }
+        if !(config.duration_seconds > 0.0 && config.duration_seconds.is_finite()) {
+            return Err("duration_seconds must be positive".to_string());
+        }
+        if config.slices == 0 {
+            return Err("slices must be positive".to_string());
+        }
+        if !(config.participation > 0.0 && config.participation <= 1.0) {
+            return Err("participation must be in (0, 1]".to_string());
+        }
+        if !(config.interval_seconds > 0.0 && config.interval_seconds.is_finite()) {
+            return Err("interval_seconds must be positive".to_string());
+        }
+        self.config = config;
+        Ok(())
+    }
+
+    fn state(&self) -> Value {
+        let state = &self.state;
+        serde_json::json!({
+            "status": state.status,
+            "filled": state.filled,
+            "remaining": self.config.amount.saturating_sub(state.filled),
+            "average_price": if state.filled > 0 { Some(state.cost / state.filled as f64) } else { None },
+            "market_volume": state.market_volume,
+            "child_order": state.child
+        })
+    }
+
+    fn snapshot(&self) -> Value {
+        serde_json::to_value(&self.state).unwrap()
+    }
+
+    fn restore(&mut self, state: &Value) -> Result<(), String> {
+        self.state = serde_json::from_value(state.clone()).map_err(|e| e.to_string())?;
+        self.trades_seen = None;
+        Ok(())
+    }
+}
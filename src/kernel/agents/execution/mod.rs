@@ -0,0 +1 @@
+pub mod algo;
\ No newline at end of file
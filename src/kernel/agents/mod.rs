@@ -1,3 +1,8 @@
 pub mod trend;
+pub mod population;
+pub mod strategies;
+pub mod execution;
 pub mod core;
 pub mod digest_cycle;
+pub mod agent;
+pub mod registry;
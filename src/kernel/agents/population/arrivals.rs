@@ -0,0 +1,101 @@
+use std::sync::Mutex;
+
+use rand::{distributions::Distribution, rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use statrs::distribution::{self as dist, Poisson};
+
+use crate::globals::GRANULARITY;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "distribution", rename_all = "snake_case")]
+pub enum SizeDistribution {
+    Fixed { size: u64 },
+    Uniform { min: u64, max: u64 },
+    LogNormal { location: f64, scale: f64 },
+    Exponential { mean: f64 }
+}
+
+impl SizeDistribution {
+    pub fn validate(&self) -> Result<(), String> {
+        use SizeDistribution::*;
+        match self {
+            Uniform { min, max } if min > max => Err("uniform size needs min <= max".to_string()),
+            LogNormal { scale, .. } if *scale <= 0.0 => Err("log normal size needs a positive scale".to_string()),
+            Exponential { mean } if *mean <= 0.0 => Err("exponential size needs a positive mean".to_string()),
+            _ => Ok(())
+        }
+    }
+
+    pub fn sample(&self, rng: &mut StdRng) -> u64 {
+        use SizeDistribution::*;
+        let size = match self {
+            Fixed { size } => *size as f64,
+            Uniform { min, max } => rng.gen_range(*min..=*max) as f64,
+            LogNormal { location, scale } => dist::LogNormal::new(*location, *scale).unwrap().sample(rng),
+            Exponential { mean } => dist::Exp::new(1.0 / mean).unwrap().sample(rng)
+        };
+        size.round().max(1.0) as u64
+    }
+}
+
+/// Counts Poisson arrivals at `rate` per simulated second over the market time since the last call
+#[derive(Default, Serialize, Deserialize)]
+pub struct PoissonArrivals {
+    last_tick: Option<i64>
+}
+
+impl PoissonArrivals {
+    pub fn arrivals(&mut self, rate: f64, now: i64, rng: &mut StdRng) -> u64 {
+        let elapsed = match self.last_tick {
+            Some(last) => (now - last) as f64 / GRANULARITY::SECOND as i64 as f64,
+            None => 0.0
+        };
+        self.last_tick = Some(now);
+
+        // a rate gone bad (intensity arithmetic, a surge) sends nothing rather than taking the market down
+        let lambda = rate * elapsed;
+        if !lambda.is_finite() || lambda <= 0.0 {
+            return 0;
+        }
+        Poisson::new(lambda).unwrap().sample(rng) as u64
+    }
+}
+
+// what configure accepts for a rate per simulated second
+pub fn valid_rate(rate: f64) -> bool {
+    rate.is_finite() && rate >= 0.0
+}
+
+/// In deterministic mode agents and value processes without a seed of their own
+/// draw one from the simulation seed, in the order they join the market
+#[derive(Default)]
+pub struct SimulationSeed {
+    rng: Mutex<Option<StdRng>>
+}
+
+impl SimulationSeed {
+    pub fn set(&self, seed: Option<u64>) {
+        *self.rng.lock().unwrap() = seed.map(StdRng::seed_from_u64);
+    }
+
+    pub fn draw(&self) -> Option<u64> {
+        self.rng.lock().unwrap().as_mut().map(|rng| rng.gen())
+    }
+}
+
+pub fn seeded_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy()
+    }
+}
+
+// order lifetimes are configured in simulated seconds, the book counts market nanoseconds
+pub fn lifetime_nanos(seconds: Option<f64>) -> Option<i64> {
+    seconds.map(|s| (s * GRANULARITY::SECOND as i64 as f64) as i64)
+}
+
+// keep random prices on a cent grid so they stack up into levels
+pub fn round_price(price: f64) -> f64 {
+    (price * 100.0).round() / 100.0
+}
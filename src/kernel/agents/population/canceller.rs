@@ -0,0 +1,94 @@
+use rand::{rngs::StdRng, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::kernel::agents::{agent::*, registry};
+
+use super::arrivals::*;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CancellerConfig {
+    // cancellations per simulated second
+    pub cancel_rate: f64,
+    // only orders placed by agents of these kinds are cancelled
+    pub targets: Vec<String>,
+    pub seed: Option<u64>
+}
+
+impl Default for CancellerConfig {
+    fn default() -> Self {
+        CancellerConfig {
+            cancel_rate: 10.0,
+            targets: vec!["zero_intelligence".to_string(), "noise_trader".to_string()],
+            seed: None
+        }
+    }
+}
+
+/// Cancels resting orders of the targeted agents uniformly at random with Poisson arrivals
+pub struct RandomCanceller {
+    config: CancellerConfig,
+    rng: StdRng,
+    arrivals: PoissonArrivals
+}
+
+impl Default for RandomCanceller {
+    fn default() -> Self {
+        let config = CancellerConfig::default();
+        RandomCanceller {
+            rng: seeded_rng(config.seed),
+            config: config,
+            arrivals: PoissonArrivals::default()
+        }
+    }
+}
+
+impl Agent for RandomCanceller {
+    fn kind(&self) -> &'static str {
+        "random_canceller"
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        if self.config.seed.is_none() {
+            self.rng = seeded_rng(Some(seed));
+        }
+    }
+
+    fn on_tick(&mut self, ctx: &AgentContext) {
+        let cancellations = self.arrivals.arrivals(self.config.cancel_rate, ctx.now(), &mut self.rng) as usize;
+        if cancellations == 0 {
+            return;
+        }
+
+        let resting = registry::resting_orders_of(ctx.market, ctx.stock, &self.config.targets);
+        for order_id in resting.choose_multiple(&mut self.rng, cancellations) {
+            ctx.cancel(*order_id);
+        }
+    }
+
+    fn snapshot(&self) -> Value {
+        serde_json::json!({ "arrivals": self.arrivals })
+    }
+
+    fn restore(&mut self, state: &Value) -> Result<(), String> {
+        self.arrivals = snapshot_field(state, "arrivals")?;
+        Ok(())
+    }
+
+    fn config(&self) -> Value {
+        serde_json::to_value(&self.config).unwrap()
+    }
+
+    fn configure(&mut self, params: &Value) -> Result<(), String> {
+        let config: CancellerConfig = merge_config(&self.config, params)?;
+        if !valid_rate(config.cancel_rate) {
+            return Err("cancel_rate must be non negative".to_string());
+        }
+        if config.seed != self.config.seed {
+            self.rng = seeded_rng(config.seed);
+        }
+        self.config = config;
+        Ok(())
+    }
+}
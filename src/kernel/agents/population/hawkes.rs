@@ -0,0 +1,228 @@
+use rand::{distributions::Distribution, rngs::StdRng, Rng};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use statrs::distribution::{Exp, Normal};
+
+use crate::classes::shared::order::OrderType;
+use crate::globals::GRANULARITY;
+use crate::kernel::agents::agent::*;
+use crate::kernel::events::{seconds_to_nanos, MarketEvent, Surge};
+
+use super::arrivals::*;
+
+// keeps a runaway process from stalling the agent thread
+const MAX_EVENTS_PER_TICK: usize = 10_000;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct HawkesConfig {
+    // mu, events per simulated second on each side with no excitation
+    pub baseline_intensity: f64,
+    // alpha, jump in a side's intensity after an event on the same side
+    pub self_excitation: f64,
+    // jump in a side's intensity after an event on the other side
+    pub cross_excitation: f64,
+    // beta, rate at which excitation decays per simulated second
+    pub decay: f64,
+    pub market_order_probability: f64,
+    // std of the distance from the mid a limit order is placed at, as a fraction of the price
+    pub price_offset_std: f64,
+    pub size: SizeDistribution,
+    pub order_lifetime_seconds: Option<f64>,
+    pub seed: Option<u64>
+}
+
+impl Default for HawkesConfig {
+    fn default() -> Self {
+        HawkesConfig {
+            baseline_intensity: 5.0,
+            self_excitation: 0.6,
+            cross_excitation: 0.2,
+            decay: 1.0,
+            market_order_probability: 0.3,
+            price_offset_std: 0.005,
+            size: SizeDistribution::Exponential { mean: 50.0 },
+            order_lifetime_seconds: Some(30.0),
+            seed: None
+        }
+    }
+}
+
+/// Bivariate Hawkes order flow with exponential kernels, simulated by Ogata's thinning
+/// over the market time elapsed between ticks
+pub struct HawkesTrader {
+    config: HawkesConfig,
+    rng: StdRng,
+    // excitation above the baseline on the (buy, sell) sides
+    excitation: (f64, f64),
+    // simulated seconds
+    last_time: Option<f64>,
+    surge: Surge
+}
+
+impl Default for HawkesTrader {
+    fn default() -> Self {
+        let config = HawkesConfig::default();
+        HawkesTrader {
+            rng: seeded_rng(config.seed),
+            config: config,
+            excitation: (0.0, 0.0),
+            last_time: None,
+            surge: Surge::default()
+        }
+    }
+}
+
+impl HawkesTrader {
+    pub fn intensities(&self) -> (f64, f64) {
+        // a surge is judged as of the last tick
+        let now = self.last_time.map_or(0, seconds_to_nanos);
+        let mu = self.config.baseline_intensity * self.surge.factor(now);
+        (mu + self.excitation.0, mu + self.excitation.1)
+    }
+
+    fn decay(&mut self, dt: f64) {
+        let factor = (-self.config.decay * dt).exp();
+        self.excitation.0 *= factor;
+        self.excitation.1 *= factor;
+    }
+
+    fn excite(&mut self, side: OrderType) {
+        let c = &self.config;
+        match side {
+            OrderType::Buy => {
+                self.excitation.0 += c.self_excitation;
+                self.excitation.1 += c.cross_excitation;
+            },
+            OrderType::Sell => {
+                self.excitation.1 += c.self_excitation;
+                self.excitation.0 += c.cross_excitation;
+            }
+        }
+    }
+
+    /// Events on (start, end], in simulated seconds
+    pub fn simulate(&mut self, start: f64, end: f64) -> Vec<(f64, OrderType)> {
+        let mut events = Vec::new();
+        let mut t = start;
+        while events.len() < MAX_EVENTS_PER_TICK {
+            // intensities only decay until the next event, so the current total bounds them
+            let (buy, sell) = self.intensities();
+            let bound = buy + sell;
+            if !bound.is_finite() || bound <= 0.0 {
+                break;
+            }
+
+            let wait = Exp::new(bound).unwrap().sample(&mut self.rng);
+            if t + wait > end {
+                break;
+            }
+            t += wait;
+            self.decay(wait);
+
+            let (buy, sell) = self.intensities();
+            let u = self.rng.gen::<f64>() * bound;
+            let side = if u <= buy {
+                OrderType::Buy
+            } else if u <= buy + sell {
+                OrderType::Sell
+            } else {
+                continue;
+            };
+            self.excite(side);
+            events.push((t, side));
+        }
+        self.decay(end - t);
+        events
+    }
+}
+
+impl Agent for HawkesTrader {
+    fn kind(&self) -> &'static str {
+        "hawkes_trader"
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        if self.config.seed.is_none() {
+            self.rng = seeded_rng(Some(seed));
+        }
+    }
+
+    fn on_tick(&mut self, ctx: &AgentContext) {
+        let now = ctx.now() as f64 / GRANULARITY::SECOND as i64 as f64;
+        let Some(last_time) = self.last_time.replace(now) else { return };
+
+        let price = ctx.mid();
+        if price <= 0.0 {
+            return;
+        }
+
+        let events = self.simulate(last_time, now);
+        let c = &self.config;
+        let lifetime = lifetime_nanos(c.order_lifetime_seconds);
+        let offset = Normal::new(0.0, c.price_offset_std * price).unwrap();
+
+        for (_, side) in events {
+            let size = c.size.sample(&mut self.rng);
+            let limit = if self.rng.gen_bool(c.market_order_probability) {
+                None
+            } else {
+                let distance = offset.sample(&mut self.rng).abs();
+                Some(round_price(match side {
+                    OrderType::Buy => price - distance,
+                    OrderType::Sell => price + distance
+                }))
+            };
+
+            match side {
+                OrderType::Buy => ctx.buy(size, limit, lifetime),
+                OrderType::Sell => ctx.sell(size, limit, lifetime)
+            };
+        }
+    }
+
+    fn on_event(&mut self, ctx: &AgentContext, event: &MarketEvent) {
+        if let MarketEvent::VolumeSurge { multiplier, seconds } = *event {
+            self.surge = Surge::new(multiplier, seconds, ctx.now());
+        }
+    }
+
+    fn snapshot(&self) -> Value {
+        serde_json::json!({ "excitation": self.excitation, "last_time": self.last_time, "surge": self.surge })
+    }
+
+    fn restore(&mut self, state: &Value) -> Result<(), String> {
+        self.excitation = snapshot_field(state, "excitation")?;
+        self.last_time = snapshot_field(state, "last_time")?;
+        self.surge = snapshot_field(state, "surge")?;
+        Ok(())
+    }
+
+    fn config(&self) -> Value {
+        serde_json::to_value(&self.config).unwrap()
+    }
+
+    fn configure(&mut self, params: &Value) -> Result<(), String> {
+        let config: HawkesConfig = merge_config(&self.config, params)?;
+        config.size.validate()?;
+        if !valid_rate(config.baseline_intensity) || config.self_excitation < 0.0 || config.cross_excitation < 0.0 {
+            return Err("intensities and excitations must be non negative".to_string());
+        }
+        if config.decay <= 0.0 || (config.self_excitation + config.cross_excitation) / config.decay >= 1.0 {
+            return Err("(self_excitation + cross_excitation) / decay must be below 1 for the process to be stationary".to_string());
+        }
+        if !(0.0..=1.0).contains(&config.market_order_probability) || config.price_offset_std <= 0.0 {
+            return Err("market_order_probability must be between 0 and 1, price_offset_std positive".to_string());
+        }
+        if config.seed != self.config.seed {
+            self.rng = seeded_rng(config.seed);
+        }
+        self.config = config;
+        Ok(())
+    }
+
+    fn state(&self) -> Value {
+        let (buy, sell) = self.intensities();
+        serde_json::json!({ "buy_intensity": buy, "sell_intensity": sell })
+    }
+}
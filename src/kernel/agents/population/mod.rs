@@ -0,0 +1,5 @@
+pub mod arrivals;
+pub mod zero_intelligence;
+pub mod noise_trader;
+pub mod canceller;
+pub mod hawkes;
@@ -0,0 +1,133 @@
+use rand::{distributions::Distribution, rngs::StdRng, Rng};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use statrs::distribution::Normal;
+
+use crate::kernel::agents::agent::*;
+use crate::kernel::events::{MarketEvent, Surge};
+
+use super::arrivals::*;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct NoiseTraderConfig {
+    // orders per simulated second
+    pub arrival_rate: f64,
+    pub size: SizeDistribution,
+    pub market_order_probability: f64,
+    // std of the distance from the mid a limit order is placed at, as a fraction of the price
+    pub price_offset_std: f64,
+    pub order_lifetime_seconds: Option<f64>,
+    pub seed: Option<u64>
+}
+
+impl Default for NoiseTraderConfig {
+    fn default() -> Self {
+        NoiseTraderConfig {
+            arrival_rate: 20.0,
+            size: SizeDistribution::Exponential { mean: 50.0 },
+            market_order_probability: 0.1,
+            price_offset_std: 0.01,
+            order_lifetime_seconds: Some(30.0),
+            seed: None
+        }
+    }
+}
+
+/// Poisson arrivals of random limit orders around the mid, on the passive side of it,
+/// with an occasional market order mixed in
+pub struct NoiseTrader {
+    config: NoiseTraderConfig,
+    rng: StdRng,
+    arrivals: PoissonArrivals,
+    surge: Surge
+}
+
+impl Default for NoiseTrader {
+    fn default() -> Self {
+        let config = NoiseTraderConfig::default();
+        NoiseTrader {
+            rng: seeded_rng(config.seed),
+            config: config,
+            arrivals: PoissonArrivals::default(),
+            surge: Surge::default()
+        }
+    }
+}
+
+impl Agent for NoiseTrader {
+    fn kind(&self) -> &'static str {
+        "noise_trader"
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        if self.config.seed.is_none() {
+            self.rng = seeded_rng(Some(seed));
+        }
+    }
+
+    fn on_tick(&mut self, ctx: &AgentContext) {
+        let price = ctx.mid();
+        if price <= 0.0 {
+            return;
+        }
+
+        let c = &self.config;
+        let lifetime = lifetime_nanos(c.order_lifetime_seconds);
+        let offset = Normal::new(0.0, c.price_offset_std * price).unwrap();
+
+        for _ in 0..self.arrivals.arrivals(c.arrival_rate * self.surge.factor(ctx.now()), ctx.now(), &mut self.rng) {
+            let size = c.size.sample(&mut self.rng);
+            let is_buy = self.rng.gen_bool(0.5);
+
+            let limit = if self.rng.gen_bool(c.market_order_probability) {
+                None
+            } else {
+                let distance = offset.sample(&mut self.rng).abs();
+                Some(round_price(if is_buy { price - distance } else { price + distance }))
+            };
+
+            if is_buy {
+                ctx.buy(size, limit, lifetime);
+            } else {
+                ctx.sell(size, limit, lifetime);
+            }
+        }
+    }
+
+    fn on_event(&mut self, ctx: &AgentContext, event: &MarketEvent) {
+        if let MarketEvent::VolumeSurge { multiplier, seconds } = *event {
+            self.surge = Surge::new(multiplier, seconds, ctx.now());
+        }
+    }
+
+    fn snapshot(&self) -> Value {
+        serde_json::json!({ "arrivals": self.arrivals, "surge": self.surge })
+    }
+
+    fn restore(&mut self, state: &Value) -> Result<(), String> {
+        self.arrivals = snapshot_field(state, "arrivals")?;
+        self.surge = snapshot_field(state, "surge")?;
+        Ok(())
+    }
+
+    fn config(&self) -> Value {
+        serde_json::to_value(&self.config).unwrap()
+    }
+
+    fn configure(&mut self, params: &Value) -> Result<(), String> {
+        let config: NoiseTraderConfig = merge_config(&self.config, params)?;
+        config.size.validate()?;
+        if !valid_rate(config.arrival_rate) || config.price_offset_std <= 0.0 {
+            return Err("arrival_rate must be non negative and price_offset_std positive".to_string());
+        }
+        if !(0.0..=1.0).contains(&config.market_order_probability) {
+            return Err("market_order_probability must be between 0 and 1".to_string());
+        }
+        if config.seed != self.config.seed {
+            self.rng = seeded_rng(config.seed);
+        }
+        self.config = config;
+        Ok(())
+    }
+}
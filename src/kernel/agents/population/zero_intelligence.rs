@@ -0,0 +1,124 @@
+use rand::{rngs::StdRng, Rng};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::kernel::agents::agent::*;
+use crate::kernel::events::{MarketEvent, Surge};
+
+use super::arrivals::*;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ZeroIntelligenceConfig {
+    // orders per simulated second
+    pub arrival_rate: f64,
+    pub size: SizeDistribution,
+    // private values are drawn uniformly within this fraction either side of the mid
+    pub value_range: f64,
+    pub order_lifetime_seconds: Option<f64>,
+    pub seed: Option<u64>
+}
+
+impl Default for ZeroIntelligenceConfig {
+    fn default() -> Self {
+        ZeroIntelligenceConfig {
+            arrival_rate: 20.0,
+            size: SizeDistribution::LogNormal { location: 3.0, scale: 1.0 },
+            value_range: 0.05,
+            order_lifetime_seconds: Some(60.0),
+            seed: None
+        }
+    }
+}
+
+/// Gode & Sunder's constrained zero intelligence traders, every arrival draws a private value
+/// and quotes a random price that can never lose money against it: buyers bid at or below
+/// their value, sellers ask at or above their cost.
+pub struct ZeroIntelligenceTrader {
+    config: ZeroIntelligenceConfig,
+    rng: StdRng,
+    arrivals: PoissonArrivals,
+    surge: Surge
+}
+
+impl Default for ZeroIntelligenceTrader {
+    fn default() -> Self {
+        let config = ZeroIntelligenceConfig::default();
+        ZeroIntelligenceTrader {
+            rng: seeded_rng(config.seed),
+            config: config,
+            arrivals: PoissonArrivals::default(),
+            surge: Surge::default()
+        }
+    }
+}
+
+impl Agent for ZeroIntelligenceTrader {
+    fn kind(&self) -> &'static str {
+        "zero_intelligence"
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        if self.config.seed.is_none() {
+            self.rng = seeded_rng(Some(seed));
+        }
+    }
+
+    fn on_tick(&mut self, ctx: &AgentContext) {
+        let price = ctx.mid();
+        if price <= 0.0 {
+            return;
+        }
+
+        let c = &self.config;
+        let lifetime = lifetime_nanos(c.order_lifetime_seconds);
+        let low = price * (1.0 - c.value_range);
+        let high = price * (1.0 + c.value_range);
+
+        for _ in 0..self.arrivals.arrivals(c.arrival_rate * self.surge.factor(ctx.now()), ctx.now(), &mut self.rng) {
+            let value = low + self.rng.gen::<f64>() * (high - low);
+            let size = c.size.sample(&mut self.rng);
+
+            if self.rng.gen_bool(0.5) {
+                let bid = low + self.rng.gen::<f64>() * (value - low);
+                ctx.buy(size, Some(round_price(bid)), lifetime);
+            } else {
+                let ask = value + self.rng.gen::<f64>() * (high - value);
+                ctx.sell(size, Some(round_price(ask)), lifetime);
+            }
+        }
+    }
+
+    fn on_event(&mut self, ctx: &AgentContext, event: &MarketEvent) {
+        if let MarketEvent::VolumeSurge { multiplier, seconds } = *event {
+            self.surge = Surge::new(multiplier, seconds, ctx.now());
+        }
+    }
+
+    fn snapshot(&self) -> Value {
+        serde_json::json!({ "arrivals": self.arrivals, "surge": self.surge })
+    }
+
+    fn restore(&mut self, state: &Value) -> Result<(), String> {
+        self.arrivals = snapshot_field(state, "arrivals")?;
+        self.surge = snapshot_field(state, "surge")?;
+        Ok(())
+    }
+
+    fn config(&self) -> Value {
+        serde_json::to_value(&self.config).unwrap()
+    }
+
+    fn configure(&mut self, params: &Value) -> Result<(), String> {
+        let config: ZeroIntelligenceConfig = merge_config(&self.config, params)?;
+        config.size.validate()?;
+        if !valid_rate(config.arrival_rate) || !(0.0..1.0).contains(&config.value_range) {
+            return Err("arrival_rate must be non negative and value_range within [0, 1)".to_string());
+        }
+        if config.seed != self.config.seed {
+            self.rng = seeded_rng(config.seed);
+        }
+        self.config = config;
+        Ok(())
+    }
+}
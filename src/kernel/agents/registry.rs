@@ -0,0 +1,303 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::classes::shared::{order::*, transaction::Transaction};
+use crate::globals::GRANULARITY;
+use crate::kernel::{events::MarketEvent, market::Market};
+
+use super::agent::*;
+use super::trend::{chaotic_trend_generator::ChaoticTrendGenerator, market_maker::*};
+use super::population::{canceller::RandomCanceller, hawkes::HawkesTrader, noise_trader::NoiseTrader, zero_intelligence::ZeroIntelligenceTrader};
+use super::strategies::{chartist::*, fundamental_trader::FundamentalTrader};
+use super::execution::algo::*;
+
+pub struct AgentHandle {
+    pub id: AgentId,
+    pub stock: Stock,
+    pub kind: &'static str,
+    agent: Mutex<Box<dyn Agent>>
+}
+
+pub struct AgentInfo {
+    pub id: AgentId,
+    pub stock: Stock,
+    pub kind: &'static str,
+    pub config: Value,
+    pub state: Value
+}
+
+#[derive(Serialize, Deserialize)]
+struct OrderOwner {
+    agent: AgentId,
+    stock: Stock,
+    placed: i64
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AgentSnapshot {
+    pub id: AgentId,
+    pub stock: Stock,
+    pub kind: String,
+    pub config: Value,
+    pub state: Value
+}
+
+/// Every agent with its config and state, and who owns which resting order
+#[derive(Serialize, Deserialize)]
+pub struct RegistrySnapshot {
+    pub agents: Vec<AgentSnapshot>,
+    owners: Vec<(u64, OrderOwner)>,
+    next_agent_id: AgentId
+}
+
+/// The agents trading in a market and the resting orders they own
+pub struct Registry {
+    agents: RwLock<BTreeMap<AgentId, Arc<AgentHandle>>>,
+    owners: Mutex<HashMap<u64, OrderOwner>>,
+    next_id: AtomicU64
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry {
+            agents: RwLock::new(BTreeMap::new()),
+            owners: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1)
+        }
+    }
+}
+
+pub fn create_agent(kind: &str, params: &Value) -> Result<Box<dyn Agent>, String> {
+    let mut agent: Box<dyn Agent> = match kind {
+        "chaotic_trend" => Box::new(ChaoticTrendGenerator::default()),
+        "straddle" => Box::new(Straddle::default()),
+        "inventory_market_maker" => Box::new(InventoryMarketMaker::default()),
+        "zero_intelligence" => Box::new(ZeroIntelligenceTrader::default()),
+        "noise_trader" => Box::new(NoiseTrader::default()),
+        "random_canceller" => Box::new(RandomCanceller::default()),
+        "hawkes_trader" => Box::new(HawkesTrader::default()),
+        "fundamental_trader" => Box::new(FundamentalTrader::default()),
+        "momentum_trader" => Box::new(Chartist::new(ChartistStyle::Momentum)),
+        "mean_reversion_trader" => Box::new(Chartist::new(ChartistStyle::MeanReversion)),
+        "twap" => Box::new(ExecutionAlgo::new(ExecutionStyle::Twap)),
+        "vwap" => Box::new(ExecutionAlgo::new(ExecutionStyle::Vwap)),
+        "pov" => Box::new(ExecutionAlgo::new(ExecutionStyle::Pov)),
+        _ => return Err(format!("unknown agent kind '{}'", kind))
+    };
+    agent.configure(params)?;
+    Ok(agent)
+}
+
+pub fn add(market: &Market, stock: Stock, mut agent: Box<dyn Agent>) -> AgentId {
+    let id = market.agents.next_id.fetch_add(1, Ordering::SeqCst);
+    if let Some(seed) = market.seeds.draw() {
+        agent.reseed(seed);
+    }
+    agent.init(&AgentContext { id, stock, market });
+
+    market.agents.agents.write().unwrap().insert(id, Arc::new(AgentHandle {
+        id: id,
+        stock: stock,
+        kind: agent.kind(),
+        agent: Mutex::new(agent)
+    }));
+    id
+}
+
+pub fn remove(market: &Market, id: AgentId) -> bool {
+    let Some(handle) = market.agents.agents.write().unwrap().remove(&id) else {
+        return false
+    };
+    let ctx = handle.context(market);
+    handle.agent.lock().unwrap().on_remove(&ctx);
+    true
+}
+
+pub fn configure(market: &Market, id: AgentId, params: &Value) -> Option<Result<(), String>> {
+    let handle = market.agents.agents.read().unwrap().get(&id).cloned()?;
+    let result = handle.agent.lock().unwrap().configure(params);
+    Some(result)
+}
+
+pub fn info(market: &Market, id: AgentId) -> Option<AgentInfo> {
+    let handle = market.agents.agents.read().unwrap().get(&id).cloned()?;
+    Some(handle.info())
+}
+
+pub fn list(market: &Market) -> Vec<AgentInfo> {
+    let handles: Vec<Arc<AgentHandle>> = market.agents.agents.read().unwrap().values().cloned().collect();
+    handles.iter().map(|h| h.info()).collect()
+}
+
+pub fn snapshot(market: &Market) -> RegistrySnapshot {
+    let handles: Vec<Arc<AgentHandle>> = market.agents.agents.read().unwrap().values().cloned().collect();
+    let agents = handles.iter()
+        .map(|h| {
+            let agent = h.agent.lock().unwrap();
+            AgentSnapshot {
+                id: h.id,
+                stock: h.stock,
+                kind: h.kind.to_string(),
+                config: agent.config(),
+                state: agent.snapshot()
+            }
+        })
+        .collect();
+
+    let mut owners: Vec<(u64, OrderOwner)> = market.agents.owners.lock().unwrap().iter()
+        .map(|(id, o)| (*id, OrderOwner { agent: o.agent, stock: o.stock, placed: o.placed }))
+        .collect();
+    owners.sort_unstable_by_key(|(id, _)| *id);
+
+    RegistrySnapshot {
+        agents: agents,
+        owners: owners,
+        next_agent_id: market.agents.next_id.load(Ordering::SeqCst)
+    }
+}
+
+/// Replaces every agent with the ones in the snapshot, under their old ids. The agents are
+/// not initialised again, their state and any resting orders come back as they were.
+pub fn restore(market: &Market, snapshot: RegistrySnapshot) -> Result<(), String> {
+    let mut handles = Vec::new();
+    for saved in snapshot.agents {
+        let mut agent = create_agent(&saved.kind, &saved.config)
+            .and_then(|mut agent| agent.restore(&saved.state).map(|_| agent))
+            .map_err(|e| format!("agent {} ({}): {}", saved.id, saved.kind, e))?;
+        if let Some(seed) = market.seeds.draw() {
+            agent.reseed(seed);
+        }
+        handles.push(Arc::new(AgentHandle {
+            id: saved.id,
+            stock: saved.stock,
+            kind: agent.kind(),
+            agent: Mutex::new(agent)
+        }));
+    }
+
+    let mut agents = market.agents.agents.write().unwrap();
+    agents.clear();
+    agents.extend(handles.into_iter().map(|h| (h.id, h)));
+    *market.agents.owners.lock().unwrap() = snapshot.owners.into_iter().collect();
+    market.agents.next_id.store(snapshot.next_agent_id, Ordering::SeqCst);
+    Ok(())
+}
+
+pub fn tick(market: &Market, stock: Stock) {
+    for handle in handles_for(market, stock) {
+        let ctx = handle.context(market);
+        handle.agent.lock().unwrap().on_tick(&ctx);
+    }
+}
+
+/// Feeds the transactions reported for a stock to its agents, routing fills to the owner of each order
+pub fn publish(market: &Market, stock: Stock, transactions: &[Transaction]) {
+    let data = MarketData {
+        price: market.get_price(stock),
+        transactions: transactions
+    };
+    let fills = collect_fills(market, stock, transactions);
+
+    for handle in handles_for(market, stock) {
+        let ctx = handle.context(market);
+        let mut agent = handle.agent.lock().unwrap();
+        agent.on_market_data(&ctx, &data);
+        for (_, fill) in fills.iter().filter(|(owner, _)| *owner == handle.id) {
+            agent.on_fill(&ctx, fill);
+        }
+    }
+
+    prune_orders(market, stock);
+}
+
+pub fn broadcast(market: &Market, stock: Stock, event: &MarketEvent) {
+    for handle in handles_for(market, stock) {
+        let ctx = handle.context(market);
+        handle.agent.lock().unwrap().on_event(&ctx, event);
+    }
+}
+
+/// Ids of the orders still on the book that were placed by agents of the given kinds
+pub fn resting_orders_of(market: &Market, stock: Stock, kinds: &[String]) -> Vec<u64> {
+    let agents: HashSet<AgentId> = market.agents.agents.read().unwrap().values()
+        .filter(|h| h.stock == stock && kinds.iter().any(|k| k == h.kind))
+        .map(|h| h.id)
+        .collect();
+    let pending = market.pending_order_ids(stock);
+
+    let mut orders: Vec<u64> = market.agents.owners.lock().unwrap().iter()
+        .filter(|(id, o)| agents.contains(&o.agent) && pending.contains(id))
+        .map(|(id, _)| *id)
+        .collect();
+    // owners are kept in a hash map, sort so random choices are reproducible under a seed
+    orders.sort_unstable();
+    orders
+}
+
+pub(super) fn track_order(market: &Market, order_id: u64, agent: AgentId, stock: Stock, placed: i64) {
+    market.agents.owners.lock().unwrap().insert(order_id, OrderOwner { agent, stock, placed });
+}
+
+fn collect_fills(market: &Market, stock: Stock, transactions: &[Transaction]) -> Vec<(AgentId, Fill)> {
+    let owners = market.agents.owners.lock().unwrap();
+    let mut fills = Vec::new();
+    for t in transactions {
+        for (order_id, order_type) in [(t.buy_id, OrderType::Buy), (t.sell_id, OrderType::Sell)] {
+            let Some(order_id) = order_id else { continue };
+            match owners.get(&order_id) {
+                Some(owner) if owner.stock == stock => fills.push((owner.agent, Fill {
+                    order_id: order_id,
+                    order_type: order_type,
+                    price: t.price,
+                    volume: t.volume,
+                    timestamp: t.timestamp
+                })),
+                _ => {}
+            }
+        }
+    }
+    fills
+}
+
+fn prune_orders(market: &Market, stock: Stock) {
+    // an order is tracked before it reaches the book, so leave new ones alone for a little while
+    let horizon = market.clock.now() - 2 * GRANULARITY::SECOND as i64;
+
+    let mut owners = market.agents.owners.lock().unwrap();
+    if !owners.values().any(|o| o.stock == stock && o.placed < horizon) {
+        return;
+    }
+    // transactions are reported a whole second at a time, an order that has traded since the last
+    // report keeps its owner until its fills have been routed
+    let mut keep = market.pending_order_ids(stock);
+    keep.extend(market.unreported_order_ids(stock));
+    owners.retain(|id, o| o.stock != stock || o.placed >= horizon || keep.contains(id));
+}
+
+fn handles_for(market: &Market, stock: Stock) -> Vec<Arc<AgentHandle>> {
+    market.agents.agents.read().unwrap().values()
+        .filter(|h| h.stock == stock)
+        .cloned()
+        .collect()
+}
+
+impl AgentHandle {
+    fn context<'a>(&self, market: &'a Market) -> AgentContext<'a> {
+        AgentContext { id: self.id, stock: self.stock, market: market }
+    }
+
+    fn info(&self) -> AgentInfo {
+        let agent = self.agent.lock().unwrap();
+        AgentInfo {
+            id: self.id,
+            stock: self.stock,
+            kind: self.kind,
+            config: agent.config(),
+            state: agent.state()
+        }
+    }
+}
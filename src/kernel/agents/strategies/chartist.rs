@@ -0,0 +1,149 @@
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::globals::GRANULARITY;
+use crate::kernel::agents::agent::*;
+use crate::kernel::agents::population::arrivals::*;
+use crate::kernel::order_book::record::ObStat;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ChartistStyle {
+    // follows the return over the lookback window
+    Momentum,
+    // bets on the price returning to its moving average
+    MeanReversion
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ChartistConfig {
+    // decisions per simulated second
+    pub arrival_rate: f64,
+    pub granularity: GRANULARITY,
+    pub lookback: usize,
+    // size of signal, as a fraction, needed before trading
+    pub threshold: f64,
+    pub base_size: u64,
+    pub max_size: u64,
+    pub seed: Option<u64>
+}
+
+impl Default for ChartistConfig {
+    fn default() -> Self {
+        ChartistConfig {
+            arrival_rate: 2.0,
+            granularity: GRANULARITY::SECOND,
+            lookback: 30,
+            threshold: 0.002,
+            base_size: 50,
+            max_size: 1000,
+            seed: None
+        }
+    }
+}
+
+/// Technical trader working off the ObStat bars in the HistoryBuffer. Positive signals buy,
+/// negative ones sell, with market orders sized by the strength of the signal.
+pub struct Chartist {
+    style: ChartistStyle,
+    config: ChartistConfig,
+    rng: StdRng,
+    arrivals: PoissonArrivals
+}
+
+impl Chartist {
+    pub fn new(style: ChartistStyle) -> Self {
+        let config = ChartistConfig::default();
+        Chartist {
+            style: style,
+            rng: seeded_rng(config.seed),
+            config: config,
+            arrivals: PoissonArrivals::default()
+        }
+    }
+}
+
+pub fn signal(style: ChartistStyle, bars: &[ObStat], price: f64) -> f64 {
+    if bars.len() < 2 {
+        return 0.0;
+    }
+
+    match style {
+        ChartistStyle::Momentum => {
+            let first = bars[0].open;
+            (bars.last().unwrap().close - first) / first
+        },
+        ChartistStyle::MeanReversion => {
+            let average = bars.iter().map(|b| b.close).sum::<f64>() / bars.len() as f64;
+            (average - price) / average
+        }
+    }
+}
+
+impl Agent for Chartist {
+    fn kind(&self) -> &'static str {
+        match self.style {
+            ChartistStyle::Momentum => "momentum_trader",
+            ChartistStyle::MeanReversion => "mean_reversion_trader"
+        }
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        if self.config.seed.is_none() {
+            self.rng = seeded_rng(Some(seed));
+        }
+    }
+
+    fn on_tick(&mut self, ctx: &AgentContext) {
+        let decisions = self.arrivals.arrivals(self.config.arrival_rate, ctx.now(), &mut self.rng);
+        if decisions == 0 {
+            return;
+        }
+
+        let c = &self.config;
+        let bars = ctx.market.get_bars(ctx.stock, c.granularity, c.lookback);
+        let signal = signal(self.style, &bars, ctx.price());
+        if signal.abs() < c.threshold {
+            return;
+        }
+
+        let size = ((c.base_size as f64 * signal.abs() / c.threshold) as u64).min(c.max_size);
+        for _ in 0..decisions {
+            if signal > 0.0 {
+                ctx.buy(size, None, None);
+            } else {
+                ctx.sell(size, None, None);
+            }
+        }
+    }
+
+    fn snapshot(&self) -> Value {
+        serde_json::json!({ "arrivals": self.arrivals })
+    }
+
+    fn restore(&mut self, state: &Value) -> Result<(), String> {
+        self.arrivals = snapshot_field(state, "arrivals")?;
+        Ok(())
+    }
+
+    fn config(&self) -> Value {
+        serde_json::to_value(&self.config).unwrap()
+    }
+
+    fn configure(&mut self, params: &Value) -> Result<(), String> {
+        let config: ChartistConfig = merge_config(&self.config, params)?;
+        if !valid_rate(config.arrival_rate) || config.threshold <= 0.0 {
+            return Err("arrival_rate must be non negative and threshold positive".to_string());
+        }
+        if config.lookback < 2 || matches!(config.granularity, GRANULARITY::INSTANT) {
+            return Err("chartists need a lookback of at least 2 bars at second granularity or above".to_string());
+        }
+        if config.seed != self.config.seed {
+            self.rng = seeded_rng(config.seed);
+        }
+        self.config = config;
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use rand::{distributions::Distribution, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use statrs::distribution::Normal;
+
+use crate::kernel::agents::agent::*;
+use crate::kernel::agents::population::arrivals::*;
+
+use super::value_process::*;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct FundamentalTraderConfig {
+    // decisions per simulated second
+    pub arrival_rate: f64,
+    // each trader sees the fundamental value through this much relative noise
+    pub observation_noise: f64,
+    // how far the price has to stray from value, as a fraction, before trading
+    pub threshold: f64,
+    pub base_size: u64,
+    pub max_size: u64,
+    pub order_lifetime_seconds: Option<f64>,
+    // replaces the dynamics of the stock's fundamental value process when set
+    pub value_model: Option<ValueModel>,
+    pub seed: Option<u64>
+}
+
+impl Default for FundamentalTraderConfig {
+    fn default() -> Self {
+        FundamentalTraderConfig {
+            arrival_rate: 5.0,
+            observation_noise: 0.01,
+            threshold: 0.005,
+            base_size: 50,
+            max_size: 1000,
+            order_lifetime_seconds: Some(60.0),
+            value_model: None,
+            seed: None
+        }
+    }
+}
+
+/// Buys below and sells above a noisy read of the stock's latent fundamental value,
+/// sized by how mispriced it thinks the stock is
+pub struct FundamentalTrader {
+    config: FundamentalTraderConfig,
+    rng: StdRng,
+    arrivals: PoissonArrivals
+}
+
+impl Default for FundamentalTrader {
+    fn default() -> Self {
+        let config = FundamentalTraderConfig::default();
+        FundamentalTrader {
+            rng: seeded_rng(config.seed),
+            config: config,
+            arrivals: PoissonArrivals::default()
+        }
+    }
+}
+
+impl FundamentalTrader {
+    fn apply_value_model(&self, ctx: &AgentContext) {
+        if let Some(model) = self.config.value_model {
+            set_value_model(ctx.market, ctx.stock, model, self.config.seed, ctx.price());
+        }
+    }
+}
+
+impl Agent for FundamentalTrader {
+    fn kind(&self) -> &'static str {
+        "fundamental_trader"
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        if self.config.seed.is_none() {
+            self.rng = seeded_rng(Some(seed));
+        }
+    }
+
+    fn init(&mut self, ctx: &AgentContext) {
+        self.apply_value_model(ctx);
+    }
+
+    fn on_tick(&mut self, ctx: &AgentContext) {
+        let price = ctx.price();
+        if price <= 0.0 {
+            return;
+        }
+
+        let c = &self.config;
+        let noise = Normal::new(0.0, c.observation_noise.max(f64::MIN_POSITIVE)).unwrap();
+        let lifetime = lifetime_nanos(c.order_lifetime_seconds);
+
+        for _ in 0..self.arrivals.arrivals(c.arrival_rate, ctx.now(), &mut self.rng) {
+            let value = fundamental_value(ctx.market, ctx.stock, price) * (1.0 + noise.sample(&mut self.rng));
+            let mispricing = (value - price) / price;
+            if mispricing.abs() < c.threshold {
+                continue;
+            }
+
+            let size = ((c.base_size as f64 * mispricing.abs() / c.threshold) as u64).min(c.max_size);
+            if mispricing > 0.0 {
+                ctx.buy(size, Some(round_price(value)), lifetime);
+            } else {
+                ctx.sell(size, Some(round_price(value)), lifetime);
+            }
+        }
+    }
+
+    fn snapshot(&self) -> Value {
+        serde_json::json!({ "arrivals": self.arrivals })
+    }
+
+    fn restore(&mut self, state: &Value) -> Result<(), String> {
+        self.arrivals = snapshot_field(state, "arrivals")?;
+        Ok(())
+    }
+
+    fn config(&self) -> Value {
+        serde_json::to_value(&self.config).unwrap()
+    }
+
+    fn configure(&mut self, params: &Value) -> Result<(), String> {
+        let config: FundamentalTraderConfig = merge_config(&self.config, params)?;
+        if !valid_rate(config.arrival_rate) || config.observation_noise < 0.0 || config.threshold <= 0.0 {
+            return Err("arrival_rate and observation_noise must be non negative, threshold positive".to_string());
+        }
+        if let Some(model) = config.value_model {
+            model.validate()?;
+        }
+        if config.seed != self.config.seed {
+            self.rng = seeded_rng(config.seed);
+        }
+        self.config = config;
+        Ok(())
+    }
+
+    fn state(&self) -> Value {
+        serde_json::json!({ "value_model": self.config.value_model })
+    }
+}
@@ -0,0 +1,3 @@
+pub mod value_process;
+pub mod fundamental_trader;
+pub mod chartist;
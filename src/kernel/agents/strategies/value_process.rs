@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use rand::{distributions::Distribution, rngs::StdRng, Rng};
+use serde::{Deserialize, Serialize};
+use statrs::distribution::Normal;
+
+use crate::classes::shared::order::Stock;
+use crate::globals::GRANULARITY;
+use crate::kernel::agents::population::arrivals::seeded_rng;
+use crate::kernel::market::Market;
+
+/// Dynamics of the latent fundamental value, rates are per simulated day
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(tag = "model", rename_all = "snake_case")]
+pub enum ValueModel {
+    GeometricBrownian { drift: f64, volatility: f64 },
+    // merton jump diffusion, jumps in log value are normally distributed
+    JumpDiffusion { drift: f64, volatility: f64, jump_intensity: f64, jump_mean: f64, jump_std: f64 }
+}
+
+impl Default for ValueModel {
+    fn default() -> Self {
+        ValueModel::GeometricBrownian { drift: 0.0, volatility: 0.02 }
+    }
+}
+
+impl ValueModel {
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            ValueModel::GeometricBrownian { volatility, .. } if volatility < 0.0 => Err("volatility must be non negative".to_string()),
+            ValueModel::JumpDiffusion { volatility, jump_intensity, jump_std, .. }
+                if volatility < 0.0 || jump_intensity < 0.0 || jump_std < 0.0 => Err("volatility, jump_intensity and jump_std must be non negative".to_string()),
+            _ => Ok(())
+        }
+    }
+
+    // log return over dt simulated days
+    fn log_return(&self, dt: f64, rng: &mut StdRng) -> f64 {
+        let standard = Normal::new(0.0, 1.0).unwrap();
+        let diffusion = |drift: f64, volatility: f64, rng: &mut StdRng| {
+            (drift - 0.5 * volatility.powi(2)) * dt + volatility * dt.sqrt() * standard.sample(rng)
+        };
+
+        match *self {
+            ValueModel::GeometricBrownian { drift, volatility } => diffusion(drift, volatility, rng),
+            ValueModel::JumpDiffusion { drift, volatility, jump_intensity, jump_mean, jump_std } => {
+                let mut log_return = diffusion(drift, volatility, rng);
+                // dt is small between updates, at most one jump per step
+                if rng.gen_bool((jump_intensity * dt).min(1.0)) {
+                    log_return += jump_mean + jump_std * standard.sample(rng);
+                }
+                log_return
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FundamentalValue {
+    pub value: f64,
+    pub model: ValueModel,
+    last_update: i64,
+    // a restored process draws from a fresh generator
+    #[serde(skip, default = "fresh_rng")]
+    rng: StdRng
+}
+
+fn fresh_rng() -> StdRng {
+    seeded_rng(None)
+}
+
+impl FundamentalValue {
+    pub fn new(value: f64, model: ValueModel, seed: Option<u64>, now: i64) -> Self {
+        FundamentalValue {
+            value: value,
+            model: model,
+            last_update: now,
+            rng: seeded_rng(seed)
+        }
+    }
+
+    pub fn advance_to(&mut self, now: i64) {
+        let dt = (now - self.last_update) as f64 / GRANULARITY::DAY as i64 as f64;
+        if dt <= 0.0 {
+            return;
+        }
+        self.value *= self.model.log_return(dt, &mut self.rng).exp();
+        self.last_update = now;
+    }
+}
+
+/// The value process of every stock in a market
+#[derive(Default)]
+pub struct Fundamentals {
+    processes: Mutex<HashMap<Stock, FundamentalValue>>
+}
+
+/// The current fundamental value of a stock, the process starts at `initial` the first time it is asked for
+pub fn fundamental_value(market: &Market, stock: Stock, initial: f64) -> f64 {
+    let now = market.clock.now();
+    let mut fundamentals = market.fundamentals.processes.lock().unwrap();
+    let process = fundamentals.entry(stock)
+        .or_insert_with(|| FundamentalValue::new(initial, ValueModel::default(), market.seeds.draw(), now));
+    process.advance_to(now);
+    process.value
+}
+
+pub fn set_fundamental_value(market: &Market, stock: Stock, value: f64) {
+    let now = market.clock.now();
+    let mut fundamentals = market.fundamentals.processes.lock().unwrap();
+    match fundamentals.get_mut(&stock) {
+        Some(process) => {
+            process.value = value;
+            process.last_update = now;
+        },
+        None => {
+            fundamentals.insert(stock, FundamentalValue::new(value, ValueModel::default(), market.seeds.draw(), now));
+        }
+    }
+}
+
+/// Moves the fundamental value by pct percent, if the stock has one yet
+pub fn shock_fundamental_value(market: &Market, stock: Stock, pct: f64) {
+    let mut fundamentals = market.fundamentals.processes.lock().unwrap();
+    if let Some(process) = fundamentals.get_mut(&stock) {
+        process.advance_to(market.clock.now());
+        process.value *= 1.0 + pct / 100.0;
+    }
+}
+
+/// Every stock's value process, for snapshots
+pub fn fundamentals(market: &Market) -> Vec<(Stock, FundamentalValue)> {
+    let fundamentals = market.fundamentals.processes.lock().unwrap();
+    fundamentals.iter()
+        .map(|(stock, p)| (*stock, FundamentalValue { value: p.value, model: p.model, last_update: p.last_update, rng: fresh_rng() }))
+        .collect()
+}
+
+// restored processes carry on from fresh draws, from the simulation seed if there is one
+pub fn set_fundamentals(market: &Market, processes: Vec<(Stock, FundamentalValue)>) {
+    *market.fundamentals.processes.lock().unwrap() = processes.into_iter()
+        .map(|(stock, mut p)| {
+            p.rng = seeded_rng(market.seeds.draw());
+            (stock, p)
+        })
+        .collect();
+}
+
+pub fn set_value_model(market: &Market, stock: Stock, model: ValueModel, seed: Option<u64>, initial: f64) {
+    let seed = seed.or_else(|| market.seeds.draw());
+    let mut fundamentals = market.fundamentals.processes.lock().unwrap();
+    let value = fundamentals.get(&stock).map(|p| p.value).unwrap_or(initial);
+    fundamentals.insert(stock, FundamentalValue::new(value, model, seed, market.clock.now()));
+}
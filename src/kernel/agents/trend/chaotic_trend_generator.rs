@@ -1,84 +1,251 @@
+use std::collections::HashMap;
 use std::sync::Mutex;
-use lazy_static::lazy_static;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use crate::classes::shared::order::*;
-use crate::kernel::market::*;
+use crate::classes::shared::order::OrderType;
+use crate::kernel::agents::agent::*;
+use crate::kernel::agents::population::arrivals::seeded_rng;
+use crate::kernel::market::Market;
 
 const LORENZ_ITERATIONS: u64 = 100;
 const ACTION_ITERATIONS: u64 = 500;
 const VOLUME_MULTIPLIER: u64 = 50;
 
-struct LorenzState {
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct LorenzState {
     x: f64,
     y: f64,
     z: f64 
 }
 
-lazy_static! {
-    static ref _STATE: Mutex<LorenzState> = Mutex::new(LorenzState {x: 1.0, y: 1.0, z: 1.0});
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct TrendConfig {
+    pub sigma: f64,
+    pub rho: f64,
+    pub beta: f64,
+    pub damping_factor: f64,
+    pub lorenz_iterations: u64,
+    pub action_iterations: u64,
+    pub volume_multiplier: u64,
+    // perturbs the initial conditions, the same seed always produces the same trend.
+    // without one they are drawn from the simulation seed, or at random
+    pub seed: Option<u64>,
+    // generators sharing a correlation group mix in a common signal with this weight (0 to 1)
+    pub correlation: f64,
+    pub correlation_group: String
 }
 
-pub fn lorenz_dy() -> f64 {
-    let mut state = _STATE.lock().unwrap();
+impl Default for TrendConfig {
+    fn default() -> Self {
+        TrendConfig {
+            sigma: 10.0,
+            rho: 28.0,
+            beta: 8.0/3.0,
+            damping_factor: 10e2,
+            lorenz_iterations: LORENZ_ITERATIONS,
+            action_iterations: ACTION_ITERATIONS,
+            volume_multiplier: VOLUME_MULTIPLIER,
+            seed: None,
+            correlation: 0.0,
+            correlation_group: "market".to_string()
+        }
+    }
+}
 
-    let sigma = 10.0;
-    let rho = 28.0;
-    let beta = 8.0/3.0;
+/// The shared factor each correlation group in a market mixes into its members' trends
+#[derive(Default)]
+pub struct CommonFactors {
+    groups: Mutex<HashMap<String, CommonFactor>>
+}
 
-    let dx = sigma * (state.y - state.x);
-    let dy = state.x * (rho - state.z) - state.y;
-    let dz = state.x * state.y - beta * state.z;
+// a group moves once per tick whichever member asks first, on the config of the member that started it,
+// so its path doesn't depend on how many stocks are in the group or the order they tick in
+struct CommonFactor {
+    state: LorenzState,
+    config: TrendConfig,
+    // the market time it last moved at and its trend over each of that tick's action iterations
+    tick: Option<i64>,
+    path: Vec<f64>
+}
 
-    let damping_factor = 10e2;
+impl CommonFactor {
+    fn path(&mut self, now: i64) -> &[f64] {
+        if self.tick != Some(now) {
+            let state = &mut self.state;
+            let config = &self.config;
+            self.path = (0..config.action_iterations.max(1)).map(|_| state.lorenz_dy(config)).collect();
+            self.tick = Some(now);
+        }
+        &self.path
+    }
+}
 
-    // sliding window of the lorenz func
-    state.x += dx/damping_factor;
-    state.y += dy/damping_factor;
-    state.z += dz/damping_factor;
+impl CommonFactors {
+    fn group<'a>(groups: &'a mut HashMap<String, CommonFactor>, market: &Market, config: &TrendConfig) -> &'a mut CommonFactor {
+        groups.entry(config.correlation_group.clone()).or_insert_with(|| CommonFactor {
+            state: LorenzState::new(market.seeds.draw()),
+            config: config.clone(),
+            tick: None,
+            path: Vec::new()
+        })
+    }
+}
 
-    let mut x = state.x;
-    let mut y = state.y;
-    let mut z = state.z;
-    let sum_dy: f64 = (0..LORENZ_ITERATIONS).map(|_| {
-        let dx = sigma * (state.y - state.x);
-        let dy = state.x * (rho - state.z) - state.y;
-        let dz = state.x * state.y - beta * state.z;
+impl LorenzState {
+    pub fn new(seed: Option<u64>) -> Self {
+        let mut rng = seeded_rng(seed);
+        LorenzState {
+            x: 1.0 + rng.gen_range(-0.5..0.5),
+            y: 1.0 + rng.gen_range(-0.5..0.5),
+            z: 1.0 + rng.gen_range(-0.5..0.5)
+        }
+    }
 
+    fn derivatives(&self, c: &TrendConfig) -> (f64, f64, f64) {
+        (
+            c.sigma * (self.y - self.x),
+            self.x * (c.rho - self.z) - self.y,
+            self.x * self.y - c.beta * self.z
+        )
+    }
 
-        x += dx/damping_factor;
-        y += dy/damping_factor;
-        z += dz/damping_factor;
-        
-        dy
-    }).sum();
+    /// Advances the attractor one step, then returns the mean dy over a window
+    /// of lorenz_iterations steps ahead of the new position
+    pub fn lorenz_dy(&mut self, c: &TrendConfig) -> f64 {
+        let (dx, dy, dz) = self.derivatives(c);
 
+        // sliding window of the lorenz func
+        self.x += dx/c.damping_factor;
+        self.y += dy/c.damping_factor;
+        self.z += dz/c.damping_factor;
 
-    sum_dy/LORENZ_ITERATIONS as f64
-}
+        let mut window = *self;
+        let sum_dy: f64 = (0..c.lorenz_iterations).map(|_| {
+            let (dx, dy, dz) = window.derivatives(c);
 
+            window.x += dx/c.damping_factor;
+            window.y += dy/c.damping_factor;
+            window.z += dz/c.damping_factor;
+            
+            dy
+        }).sum();
 
-pub fn generate_trend(stock: Stock) {
-    // let mut momentum = *_momentum.lock().unwrap();
-    for _ in 0..ACTION_ITERATIONS {
-        let trend = lorenz_dy();
-        
-        // idea here is to add a component to the trend that inverses the momentum of the past MOMENTUM_MEMORY moves
-        // this kind of acts the same way like the tuned mass damper in tipei 101
+        sum_dy/c.lorenz_iterations.max(1) as f64
+    }
+}
 
-        //  ^ fun idea to play with, might need array of long and short term mass dampers for this to work
-        // or just some equations
+pub struct ChaoticTrendGenerator {
+    config: TrendConfig,
+    state: LorenzState,
+    // the attractor has moved off its initial conditions (or was restored), a reseed leaves it be
+    moved: bool
+}
 
-        // println!("momentum is {momentum}");
-        let size = trend.abs() as u64 * VOLUME_MULTIPLIER;
+impl Default for ChaoticTrendGenerator {
+    fn default() -> Self {
+        ChaoticTrendGenerator {
+            config: TrendConfig::default(),
+            state: LorenzState::new(None),
+            moved: false
+        }
+    }
+}
+
+impl ChaoticTrendGenerator {
+    // this tick's path of the group's common factor, empty when uncorrelated
+    fn common_path(&self, market: &Market, now: i64) -> Vec<f64> {
+        if self.config.correlation == 0.0 {
+            return Vec::new();
+        }
+        let mut groups = market.common_factors.groups.lock().unwrap();
+        CommonFactors::group(&mut groups, market, &self.config).path(now).to_vec()
+    }
 
-        if trend > 0.0 {
-            // println!("CHAOS: bought: {}", size);
-            buy(stock, size, None, None, None);
-        } else {
-            // println!("CHAOS: sold {}", size);
-            sell(stock, size, None, None, None);
+    fn next_trend(&mut self, common: &[f64], iteration: u64) -> f64 {
+        let own = self.state.lorenz_dy(&self.config);
+        if common.is_empty() {
+            return own;
         }
+
+        // spread the group's path over however many iterations this member runs
+        let index = (iteration * common.len() as u64 / self.config.action_iterations) as usize;
+        let rho = self.config.correlation;
+        rho * common[index] + (1.0 - rho * rho).sqrt() * own
     }
 }
 
+impl Agent for ChaoticTrendGenerator {
+    fn kind(&self) -> &'static str {
+        "chaotic_trend"
+    }
+
+    // the group is started as the member joins, so its seed is drawn in the order agents are added
+    fn init(&mut self, ctx: &AgentContext) {
+        if self.config.correlation != 0.0 {
+            CommonFactors::group(&mut ctx.market.common_factors.groups.lock().unwrap(), ctx.market, &self.config);
+        }
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        if self.config.seed.is_none() && !self.moved {
+            self.state = LorenzState::new(Some(seed));
+        }
+    }
+
+    fn on_tick(&mut self, ctx: &AgentContext) {
+        self.moved = true;
+        let common = self.common_path(ctx.market, ctx.now());
+        for iteration in 0..self.config.action_iterations {
+            let trend = self.next_trend(&common, iteration);
+            
+            // idea here is to add a component to the trend that inverses the momentum of the past MOMENTUM_MEMORY moves
+            // this kind of acts the same way like the tuned mass damper in tipei 101
+
+            //  ^ fun idea to play with, might need array of long and short term mass dampers for this to work
+            // or just some equations
+
+            let size = trend.abs() as u64 * self.config.volume_multiplier;
+
+            // the trend is anonymous flow, nobody needs to hear about its fills
+            if trend > 0.0 {
+                ctx.submit(OrderType::Buy, size, None, None);
+            } else {
+                ctx.submit(OrderType::Sell, size, None, None);
+            }
+        }
+    }
 
+    fn snapshot(&self) -> Value {
+        serde_json::json!({ "state": self.state })
+    }
+
+    fn restore(&mut self, state: &Value) -> Result<(), String> {
+        self.state = snapshot_field(state, "state")?;
+        self.moved = true;
+        Ok(())
+    }
+
+    fn config(&self) -> Value {
+        serde_json::to_value(&self.config).unwrap()
+    }
+
+    fn configure(&mut self, params: &Value) -> Result<(), String> {
+        let config: TrendConfig = merge_config(&self.config, params)?;
+        if !(0.0..=1.0).contains(&config.correlation) {
+            return Err("correlation must be between 0 and 1".to_string());
+        }
+        if config.damping_factor <= 0.0 {
+            return Err("damping_factor must be positive".to_string());
+        }
+
+        // a new seed restarts the attractor from the seeded initial conditions
+        if config.seed != self.config.seed {
+            self.state = LorenzState::new(config.seed);
+        }
+        self.config = config;
+        Ok(())
+    }
+}
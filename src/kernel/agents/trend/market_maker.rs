@@ -1,18 +1,40 @@
 extern crate statrs;
 
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use statrs::{distribution::Normal, statistics::Distribution};
 use std::f64::consts::PI;
 
-use crate::classes::shared::order::*;
-use crate::kernel::market::*;
+use crate::classes::shared::order::OrderType;
+use crate::kernel::agents::agent::*;
+use crate::kernel::events::*;
 
 // VALUES CONTROL THE TRAILING BUY/SELLS
 const NUM_TRAIL_LEVELS: u64 = 50;
 const TRAIL_LEVEL_GAPS: f64 = 0.01;
-const VOLUME_MULTIPLIER: f64 = 10.0;
+// was 10 against the reciprocal of the density (about 2.5 near the mean), 60 against the
+// density itself (about 0.4) keeps each level at roughly the same 24 shares
+const VOLUME_MULTIPLIER: f64 = 60.0;
 const TRAIL_GRADIENT: f64 = 0.001; // how far from mean is each buy
 
 const STD: f64 = 1.0;
+const ORDER_LIFETIME: i64 = 100;
+
+// a price shock tells a maker where the price is headed, (from, to). it quotes around `to` until
+// trades have taken the last price there
+fn quote_price(shock: &mut Option<(f64, f64)>, price: f64) -> f64 {
+    match *shock {
+        Some((from, to)) if (to - price) * (to - from) > 0.0 => to,
+        _ => {
+            *shock = None;
+            price
+        }
+    }
+}
+
+fn shock_target(price: f64, pct: f64) -> Option<(f64, f64)> {
+    (price > 0.0).then(|| (price, price * (1.0 + pct / 100.0)))
+}
 
 fn probability_density(distance_from_mean: f64, n: Normal) -> f64 {
     let variance = n.variance().unwrap(); // Standard deviation squared, assuming standard deviation is 1 for standard normal distribution
@@ -20,28 +42,318 @@ fn probability_density(distance_from_mean: f64, n: Normal) -> f64 {
     
     let coefficient = 1.0 / ((2.0 * PI * variance).sqrt());
     let exponent = (-0.5 * (distance_from_mean.powi(2) / variance)).exp();
-    1.0/(coefficient * exponent)
+    coefficient * exponent
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct StraddleConfig {
+    pub levels: u64,
+    pub level_gap: f64,
+    pub volume_multiplier: f64,
+    pub trail_gradient: f64,
+    pub std: f64,
+    pub order_lifetime: i64
+}
+
+impl Default for StraddleConfig {
+    fn default() -> Self {
+        StraddleConfig {
+            levels: NUM_TRAIL_LEVELS,
+            level_gap: TRAIL_LEVEL_GAPS,
+            volume_multiplier: VOLUME_MULTIPLIER,
+            trail_gradient: TRAIL_GRADIENT,
+            std: STD,
+            order_lifetime: ORDER_LIFETIME
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Straddle {
+    config: StraddleConfig,
+    // quoting is suspended until then by a liquidity drought
+    withdrawn_until: i64,
+    shock: Option<(f64, f64)>
+}
+
+impl Agent for Straddle {
+    fn kind(&self) -> &'static str {
+        "straddle"
+    }
+
+    fn on_tick(&mut self, ctx: &AgentContext) {
+        if ctx.now() < self.withdrawn_until {
+            return;
+        }
+        // provide buy and sell limit orders to the market, at a normal distribution
+        // centered at the current stock price
+        let config = &self.config;
+        let normal = Normal::new(0.0, config.std).unwrap();
+        let price = quote_price(&mut self.shock, ctx.price());
+
+        for i in 1..config.levels + 1 {
+            let distance = i as f64 * config.trail_gradient;
+            let volume = probability_density(distance, normal);
+
+            let trade_volume = (volume * config.volume_multiplier) as u64;
+            let distance_from_price = i as f64 * config.level_gap;
+
+            ctx.submit(OrderType::Sell, trade_volume, Some(price + distance_from_price), Some(config.order_lifetime));
+            ctx.submit(OrderType::Buy, trade_volume, Some(price - distance_from_price), Some(config.order_lifetime));
+        }
+    }
+
+    fn snapshot(&self) -> Value {
+        serde_json::json!({ "withdrawn_until": self.withdrawn_until, "shock": self.shock })
+    }
+
+    fn restore(&mut self, state: &Value) -> Result<(), String> {
+        self.withdrawn_until = snapshot_field(state, "withdrawn_until")?;
+        self.shock = snapshot_field(state, "shock")?;
+        Ok(())
+    }
+
+    fn config(&self) -> Value {
+        serde_json::to_value(&self.config).unwrap()
+    }
+
+    fn on_event(&mut self, ctx: &AgentContext, event: &MarketEvent) {
+        match *event {
+            MarketEvent::LiquidityDrought { seconds } => self.withdrawn_until = ctx.now() + seconds_to_nanos(seconds),
+            MarketEvent::PriceShock { pct } => self.shock = shock_target(ctx.price(), pct),
+            _ => {}
+        }
+    }
+
+    fn configure(&mut self, params: &Value) -> Result<(), String> {
+        let config: StraddleConfig = merge_config(&self.config, params)?;
+        if config.std <= 0.0 {
+            return Err("std must be positive".to_string());
+        }
+        self.config = config;
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct InventoryMarketMakerConfig {
+    // gamma, how much inventory risk the maker is willing to carry
+    pub risk_aversion: f64,
+    // k, how quickly fill probability decays with distance from the mid
+    pub order_arrival_decay: f64,
+    // T - t, in simulated seconds the maker expects to hold inventory for
+    pub horizon_seconds: f64,
+    // used while Stats has no volatility yet, per-second return std
+    pub min_volatility: f64,
+    pub max_position: i64,
+    pub base_size: u64,
+    pub levels: u64,
+    pub level_spacing: f64,
+    // quotes only get cancelled and replaced once the target moves this far
+    pub requote_threshold: f64
+}
+
+impl Default for InventoryMarketMakerConfig {
+    fn default() -> Self {
+        InventoryMarketMakerConfig {
+            risk_aversion: 0.1,
+            order_arrival_decay: 100.0,
+            horizon_seconds: 60.0,
+            min_volatility: 0.001,
+            max_position: 10_000,
+            base_size: 500,
+            levels: 5,
+            level_spacing: 0.01,
+            requote_threshold: 0.005
+        }
+    }
 }
 
-pub fn straddle(stock: Stock) {
-    // provide buy and sell limit orders to the market, at a normal distribution
-    // centered at the current stock price
-    let normal = Normal::new(0.0, STD as f64).unwrap();
-    let price = get_price(stock);
+#[derive(Serialize, Deserialize, Clone, Copy)]
+struct Quote {
+    order_id: u64,
+    order_type: OrderType,
+    price: f64,
+    remaining: u64
+}
+
+/// Avellaneda-Stoikov style market maker. Quotes around a reservation price that is
+/// skewed away from its inventory, with a spread that widens with observed volatility.
+#[derive(Default)]
+pub struct InventoryMarketMaker {
+    config: InventoryMarketMakerConfig,
+    inventory: i64,
+    cash: f64,
+    mark_price: f64,
+    quotes: Vec<Quote>,
+    // the (bid, ask) the live quotes were placed around
+    quoted_at: Option<(f64, f64)>,
+    withdrawn_until: i64,
+    // whether the maker sat out its last tick, for reporting
+    withdrawn: bool,
+    shock: Option<(f64, f64)>
+}
+
+impl InventoryMarketMaker {
+    pub fn pnl(&self) -> f64 {
+        self.cash + self.inventory as f64 * self.mark_price
+    }
+
+    fn target_quotes(&self, mid: f64, volatility: f64) -> (f64, f64) {
+        let c = &self.config;
+        let sigma = volatility.max(c.min_volatility) * mid;
+        let risk = c.risk_aversion * sigma.powi(2) * c.horizon_seconds;
+
+        let reservation_price = mid - self.inventory as f64 * risk;
+        let spread = risk + (2.0 / c.risk_aversion) * (1.0 + c.risk_aversion / c.order_arrival_decay).ln();
+        (reservation_price - spread / 2.0, reservation_price + spread / 2.0)
+    }
+
+    fn target_sizes(&self) -> (u64, u64) {
+        // lean sizes against the position, stop adding to it at max_position
+        let c = &self.config;
+        let skew = (self.inventory as f64 / c.max_position as f64).clamp(-1.0, 1.0);
+        let bid_size = (c.base_size as f64 * (1.0 - skew)) as u64;
+        let ask_size = (c.base_size as f64 * (1.0 + skew)) as u64;
+        (bid_size, ask_size)
+    }
 
-    for i in 1..NUM_TRAIL_LEVELS + 1 {
-        let distance = i as f64 * TRAIL_GRADIENT;
-        let volume = probability_density(distance, normal);
-        // println!("MM: volume {volume}");
+    // quotes can leave the book without the maker hearing of it (cancellers, self-trade prevention,
+    // expiry), forget those and say whether a side has been left without any
+    fn reconcile_quotes(&mut self, ctx: &AgentContext) -> bool {
+        let pending = ctx.market.pending_order_ids(ctx.stock);
+        self.quotes.retain(|q| pending.contains(&q.order_id));
+        [OrderType::Buy, OrderType::Sell].iter().any(|side| !self.quotes.iter().any(|q| q.order_type == *side))
+    }
 
-        let trade_volume = (volume * VOLUME_MULTIPLIER) as u64;
-        let distance_from_price = i as f64 * TRAIL_LEVEL_GAPS;
+    fn cancel_quotes(&mut self, ctx: &AgentContext) {
+        for quote in self.quotes.drain(..) {
+            ctx.cancel(quote.order_id);
+        }
+        self.quoted_at = None;
+    }
+}
 
-        sell(stock, trade_volume, Some(price + distance_from_price), Some(100), None);
-        // buy_limit(stock, trade_volume, price + distance_from_price);
-        // sell_limit(stock, trade_volume, price - distance_from_price);
-        buy(stock, trade_volume, Some(price - distance_from_price), Some(100), None);
-        // println!("MARK: Sold {trade_volume} shares at {}", price + distance_from_price);
-        // println!("MARK: Bought {trade_volume} shares at {}", price - distance_from_price);
+impl Agent for InventoryMarketMaker {
+    fn kind(&self) -> &'static str {
+        "inventory_market_maker"
     }
-}
\ No newline at end of file
+
+    fn on_tick(&mut self, ctx: &AgentContext) {
+        let mid = ctx.price();
+        if mid <= 0.0 {
+            return;
+        }
+        self.mark_price = mid;
+        self.withdrawn = ctx.now() < self.withdrawn_until;
+        if self.withdrawn {
+            return;
+        }
+
+        let one_sided = self.reconcile_quotes(ctx);
+        let mid = quote_price(&mut self.shock, mid);
+        let (bid, ask) = self.target_quotes(mid, ctx.market.get_volatility(ctx.stock));
+        let threshold = self.config.requote_threshold;
+        match self.quoted_at {
+            Some((b, a)) if (b - bid).abs() < threshold && (a - ask).abs() < threshold && !one_sided => return,
+            _ => self.cancel_quotes(ctx)
+        }
+
+        let (bid_size, ask_size) = self.target_sizes();
+        for level in 0..self.config.levels {
+            let offset = level as f64 * self.config.level_spacing;
+            for (order_type, price, size) in [(OrderType::Buy, bid - offset, bid_size), (OrderType::Sell, ask + offset, ask_size)] {
+                let order_id = match order_type {
+                    OrderType::Buy => ctx.buy(size, Some(price), None),
+                    OrderType::Sell => ctx.sell(size, Some(price), None)
+                };
+                if let Some(order_id) = order_id {
+                    self.quotes.push(Quote { order_id, order_type, price, remaining: size });
+                }
+            }
+        }
+        self.quoted_at = Some((bid, ask));
+    }
+
+    fn on_fill(&mut self, _ctx: &AgentContext, fill: &Fill) {
+        let signed_volume = match fill.order_type {
+            OrderType::Buy => fill.volume as i64,
+            OrderType::Sell => -(fill.volume as i64)
+        };
+        self.inventory += signed_volume;
+        self.cash -= signed_volume as f64 * fill.price;
+
+        if let Some(quote) = self.quotes.iter_mut().find(|q| q.order_id == fill.order_id) {
+            quote.remaining = quote.remaining.saturating_sub(fill.volume);
+        }
+        self.quotes.retain(|q| q.remaining > 0);
+    }
+
+    fn on_event(&mut self, ctx: &AgentContext, event: &MarketEvent) {
+        match *event {
+            // pull everything off the book and stay out for the duration
+            MarketEvent::LiquidityDrought { seconds } => {
+                self.withdrawn_until = ctx.now() + seconds_to_nanos(seconds);
+                self.withdrawn = true;
+                self.cancel_quotes(ctx);
+            },
+            // the old quotes are stale against where the price is going
+            MarketEvent::PriceShock { pct } => {
+                self.shock = shock_target(ctx.price(), pct);
+                self.cancel_quotes(ctx);
+            },
+            _ => {}
+        }
+    }
+
+    fn on_remove(&mut self, ctx: &AgentContext) {
+        self.cancel_quotes(ctx);
+    }
+
+    fn snapshot(&self) -> Value {
+        serde_json::json!({ "inventory": self.inventory, "cash": self.cash, "mark_price": self.mark_price, "quotes": self.quotes, "quoted_at": self.quoted_at, "withdrawn_until": self.withdrawn_until, "shock": self.shock })
+    }
+
+    fn restore(&mut self, state: &Value) -> Result<(), String> {
+        self.inventory = snapshot_field(state, "inventory")?;
+        self.cash = snapshot_field(state, "cash")?;
+        self.mark_price = snapshot_field(state, "mark_price")?;
+        self.quotes = snapshot_field(state, "quotes")?;
+        self.quoted_at = snapshot_field(state, "quoted_at")?;
+        self.withdrawn_until = snapshot_field(state, "withdrawn_until")?;
+        self.shock = snapshot_field(state, "shock")?;
+        // settled against the clock on the next tick
+        self.withdrawn = self.withdrawn_until > 0;
+        Ok(())
+    }
+
+    fn config(&self) -> Value {
+        serde_json::to_value(&self.config).unwrap()
+    }
+
+    fn configure(&mut self, params: &Value) -> Result<(), String> {
+        let config: InventoryMarketMakerConfig = merge_config(&self.config, params)?;
+        if config.risk_aversion <= 0.0 || config.order_arrival_decay <= 0.0 {
+            return Err("risk_aversion and order_arrival_decay must be positive".to_string());
+        }
+        if config.max_position <= 0 {
+            return Err("max_position must be positive".to_string());
+        }
+        self.config = config;
+        // requote with the new parameters on the next tick
+        self.quoted_at = None;
+        Ok(())
+    }
+
+    fn state(&self) -> Value {
+        serde_json::json!({
+            "inventory": self.inventory,
+            "cash": self.cash,
+            "pnl": self.pnl(),
+            "quotes": self.quotes,
+            "withdrawn": self.withdrawn
+        })
+    }
+}
@@ -0,0 +1,141 @@
+use serde::Serialize;
+
+use crate::kernel::gym::{Action, EnvConfig, Environment, Fill, Observation};
+use crate::scenario::Scenario;
+
+const SECONDS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+// the market trades around the clock unless a session says otherwise
+const DAYS_PER_YEAR: f64 = 365.0;
+
+/// A trading strategy run offline against the simulator. It sees the market every step and the
+/// orders it hands back are placed straight away, ahead of the market running on
+pub trait Strategy {
+    fn on_data(&mut self, observation: &Observation) -> Vec<Action>;
+
+    fn on_fill(&mut self, _fill: &Fill) {}
+}
+
+#[derive(Clone)]
+pub struct BacktestConfig {
+    // the stock, step length, starting cash and fees, its max_steps is worked out from days
+    pub env: EnvConfig,
+    pub days: f64,
+    pub seed: u64
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct EquityPoint {
+    pub time: i64,
+    pub equity: f64
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BacktestReport {
+    // cash plus inventory at the last price, at the start and after every step
+    pub equity_curve: Vec<EquityPoint>,
+    pub pnl: f64,
+    // of the per step returns, annualised
+    pub sharpe: f64,
+    // the largest fall from a peak, as a fraction of the peak
+    pub max_drawdown: f64,
+    pub traded_value: f64,
+    // traded value over starting cash
+    pub turnover: f64,
+    pub orders: u64,
+    pub ordered_volume: u64,
+    pub filled_volume: u64,
+    // filled volume over ordered volume
+    pub fill_ratio: f64,
+    pub fees: f64
+}
+
+/// Runs a strategy inside a scenario for `days` simulated days on the virtual clock. The same
+/// scenario, seed and strategy always give the same report
+pub fn run(strategy: &mut dyn Strategy, scenario: &Scenario, config: &BacktestConfig) -> Result<BacktestReport, String> {
+    if !(config.days > 0.0 && config.days.is_finite()) {
+        return Err("days must be positive".to_string());
+    }
+    let mut env_config = config.env.clone();
+    let steps = (config.days * SECONDS_PER_DAY / env_config.step_seconds).ceil() as u64;
+    env_config.max_steps = Some(steps.max(1));
+    let mut env = Environment::new(env_config);
+
+    let mut observation = env.reset(config.seed, scenario)?;
+    let mut equity_curve = vec![equity(&observation)];
+    let (mut orders, mut ordered_volume, mut filled_volume) = (0, 0, 0);
+    let (mut traded_value, mut fees) = (0.0, 0.0);
+    loop {
+        let actions = strategy.on_data(&observation);
+        for action in &actions {
+            if let Action::Buy { amount, .. } | Action::Sell { amount, .. } = action {
+                orders += 1;
+                ordered_volume += amount;
+            }
+        }
+        let step = env.step(&actions)?;
+        for fill in &step.fills {
+            strategy.on_fill(fill);
+            filled_volume += fill.volume;
+            traded_value += fill.price * fill.volume as f64;
+            fees += fill.fee;
+        }
+        equity_curve.push(equity(&step.observation));
+        observation = step.observation;
+        if step.done {
+            break;
+        }
+    }
+
+    let start = equity_curve[0].equity;
+    let periods_per_year = DAYS_PER_YEAR * SECONDS_PER_DAY / config.env.step_seconds;
+    Ok(BacktestReport {
+        pnl: equity_curve.last().unwrap().equity - start,
+        sharpe: sharpe(&equity_curve, periods_per_year),
+        max_drawdown: max_drawdown(&equity_curve),
+        traded_value: traded_value,
+        turnover: if start > 0.0 { traded_value / start } else { 0.0 },
+        orders: orders,
+        ordered_volume: ordered_volume,
+        filled_volume: filled_volume,
+        fill_ratio: if ordered_volume > 0 { filled_volume as f64 / ordered_volume as f64 } else { 0.0 },
+        fees: fees,
+        equity_curve: equity_curve
+    })
+}
+
+fn equity(observation: &Observation) -> EquityPoint {
+    EquityPoint {
+        time: observation.time,
+        equity: observation.cash + observation.inventory as f64 * observation.price
+    }
+}
+
+// 0 when there are no returns to go on or they never vary
+fn sharpe(curve: &[EquityPoint], periods_per_year: f64) -> f64 {
+    let returns: Vec<f64> = curve.windows(2)
+        .filter(|w| w[0].equity > 0.0)
+        .map(|w| w[1].equity / w[0].equity - 1.0)
+        .collect();
+    if returns.len() < 2 {
+        return 0.0;
+    }
+    let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+    let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+    if variance > 0.0 {
+        mean / variance.sqrt() * periods_per_year.sqrt()
+    } else {
+        0.0
+    }
+}
+
+fn max_drawdown(curve: &[EquityPoint]) -> f64 {
+    let mut peak = f64::MIN;
+    let mut worst: f64 = 0.0;
+    for point in curve {
+        peak = peak.max(point.equity);
+        if peak > 0.0 {
+            worst = worst.max((peak - point.equity) / peak);
+        }
+    }
+    worst
+}
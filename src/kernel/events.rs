@@ -0,0 +1,130 @@
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::classes::shared::order::Stock;
+use crate::globals::GRANULARITY;
+use crate::kernel::agents::{registry, strategies::value_process};
+use crate::kernel::market::Market;
+
+/// Exogenous news hitting a stock, durations are in simulated seconds
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum MarketEvent {
+    // moves the fundamental value by pct percent and the market makers quote towards it,
+    // the price follows as they trade
+    PriceShock { pct: f64 },
+    // market makers pull their quotes for the duration
+    LiquidityDrought { seconds: f64 },
+    // arrival rates of the order flow agents are scaled by multiplier for the duration
+    VolumeSurge { multiplier: f64, seconds: f64 }
+}
+
+impl MarketEvent {
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            MarketEvent::PriceShock { pct } if pct <= -100.0 => Err("a price shock must leave a positive price".to_string()),
+            MarketEvent::LiquidityDrought { seconds } if seconds <= 0.0 => Err("seconds must be positive".to_string()),
+            MarketEvent::VolumeSurge { multiplier, seconds } if multiplier < 0.0 || seconds <= 0.0 => {
+                Err("multiplier must be non negative and seconds positive".to_string())
+            },
+            _ => Ok(())
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct EventRecord {
+    pub timestamp: i64,
+    pub event: MarketEvent
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ScheduledEvent {
+    due: i64,
+    stock: Stock,
+    event: MarketEvent
+}
+
+/// The events a market has queued for later
+#[derive(Default)]
+pub struct EventSchedule {
+    scheduled: Mutex<Vec<ScheduledEvent>>
+}
+
+pub fn seconds_to_nanos(seconds: f64) -> i64 {
+    (seconds * GRANULARITY::SECOND as i64 as f64) as i64
+}
+
+/// Applies an event to the market, records it in the stock's history and lets its agents react
+pub fn trigger(market: &Market, stock: Stock, event: MarketEvent) {
+    market.record_event(stock, event);
+    if let MarketEvent::PriceShock { pct } = event {
+        value_process::shock_fundamental_value(market, stock, pct);
+    }
+    registry::broadcast(market, stock, &event);
+}
+
+/// Queues an event to fire after `delay_seconds` of simulated time
+pub fn schedule(market: &Market, stock: Stock, event: MarketEvent, delay_seconds: f64) {
+    market.events.scheduled.lock().unwrap().push(ScheduledEvent {
+        due: market.clock.now() + seconds_to_nanos(delay_seconds),
+        stock: stock,
+        event: event
+    });
+}
+
+pub fn fire_due(market: &Market, stock: Stock) {
+    let now = market.clock.now();
+    let mut due: Vec<ScheduledEvent> = Vec::new();
+    {
+        let mut scheduled = market.events.scheduled.lock().unwrap();
+        let mut i = 0;
+        while i < scheduled.len() {
+            if scheduled[i].stock == stock && scheduled[i].due <= now {
+                due.push(scheduled.remove(i));
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    due.sort_by_key(|e| e.due);
+    for scheduled in due {
+        trigger(market, stock, scheduled.event);
+    }
+}
+
+pub fn scheduled(market: &Market) -> Vec<ScheduledEvent> {
+    market.events.scheduled.lock().unwrap().clone()
+}
+
+pub fn set_scheduled(market: &Market, scheduled: Vec<ScheduledEvent>) {
+    *market.events.scheduled.lock().unwrap() = scheduled;
+}
+
+/// A temporary scaling of an agent's activity, lapsing at `until`
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Surge {
+    multiplier: f64,
+    until: i64
+}
+
+impl Default for Surge {
+    fn default() -> Self {
+        Surge { multiplier: 1.0, until: 0 }
+    }
+}
+
+impl Surge {
+    pub fn new(multiplier: f64, seconds: f64, now: i64) -> Self {
+        Surge {
+            multiplier: multiplier,
+            until: now + seconds_to_nanos(seconds)
+        }
+    }
+
+    pub fn factor(&self, now: i64) -> f64 {
+        if now < self.until { self.multiplier } else { 1.0 }
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::classes::shared::{order::Stock, transaction::Transaction};
+use crate::globals::GRANULARITY;
+use crate::kernel::market::Market;
+use crate::kernel::market_time::market_time::MTime;
+use crate::kernel::order_book::record::ObStat;
+
+// rows per chunk, a chunk is all an export holds in memory at once
+const CHUNK_ROWS: usize = 10_000;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    // one JSON object per line
+    Ndjson
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Ndjson => "application/x-ndjson"
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TradeRow {
+    pub timestamp: i64,
+    pub simulated_time: String,
+    pub price: f64,
+    pub volume: u64,
+    pub buy_id: Option<u64>,
+    pub sell_id: Option<u64>
+}
+
+impl TradeRow {
+    pub fn new(transaction: Transaction, clock: &MTime) -> Self {
+        TradeRow {
+            timestamp: transaction.timestamp,
+            simulated_time: clock.simulated_datetime(transaction.timestamp).to_rfc3339(),
+            price: transaction.price,
+            volume: transaction.volume,
+            buy_id: transaction.buy_id,
+            sell_id: transaction.sell_id
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct CandleRow {
+    pub tick: i64,
+    pub granularity: GRANULARITY,
+    // when the bar opened
+    pub simulated_time: String,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: u64
+}
+
+impl CandleRow {
+    pub fn new(bar: ObStat, clock: &MTime) -> Self {
+        CandleRow {
+            tick: bar.tick,
+            granularity: bar.granularity,
+            simulated_time: clock.simulated_datetime(bar.tick * bar.granularity as i64).to_rfc3339(),
+            open: bar.open,
+            high: bar.high,
+            low: bar.low,
+            close: bar.close,
+            volume: bar.volume
+        }
+    }
+}
+
+/// The trades of a stock with a timestamp in [from, to), a chunk of rows at a time.
+/// Each chunk is read off the tape as it is asked for, so an export never holds the whole range.
+pub fn trades(market: Arc<Market>, stock: Stock, from: i64, to: i64, format: ExportFormat) -> impl Iterator<Item = Vec<u8>> {
+    let range = market.tape.between(stock, from, to);
+    range.clone().step_by(CHUNK_ROWS).map(move |start| {
+        let rows: Vec<TradeRow> = market.tape.slice(stock, start..(start + CHUNK_ROWS).min(range.end))
+            .into_iter()
+            .map(|transaction| TradeRow::new(transaction, &market.clock))
+            .collect();
+        write_rows(&rows, format, start == range.start)
+    })
+}
+
+/// The bars of a stock's history that opened in [from, to), oldest first
+pub fn candles(market: Arc<Market>, stock: Stock, granularity: GRANULARITY, from: i64, to: i64, format: ExportFormat) -> impl Iterator<Item = Vec<u8>> {
+    let bars: Vec<ObStat> = market.get_bars(stock, granularity, usize::MAX)
+        .into_iter()
+        .filter(|bar| (from..to).contains(&(bar.tick.saturating_mul(granularity as i64))))
+        .collect();
+    (0..bars.len().div_ceil(CHUNK_ROWS)).map(move |i| {
+        let rows: Vec<CandleRow> = bars.iter()
+            .skip(i * CHUNK_ROWS)
+            .take(CHUNK_ROWS)
+            .map(|bar| CandleRow::new(*bar, &market.clock))
+            .collect();
+        write_rows(&rows, format, i == 0)
+    })
+}
+
+fn write_rows<T: Serialize>(rows: &[T], format: ExportFormat, header: bool) -> Vec<u8> {
+    match format {
+        ExportFormat::Csv => {
+            let mut writer = csv::WriterBuilder::new().has_headers(header).from_writer(Vec::new());
+            for row in rows {
+                writer.serialize(row).unwrap();
+            }
+            writer.into_inner().unwrap()
+        },
+        ExportFormat::Ndjson => {
+            let mut out = Vec::new();
+            for row in rows {
+                serde_json::to_writer(&mut out, row).unwrap();
+                out.push(b'\n');
+            }
+            out
+        }
+    }
+}
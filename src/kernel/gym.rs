@@ -0,0 +1,350 @@
+use std::collections::{BTreeSet, HashSet};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::classes::shared::order::{OrderType, Stock, TimeInForce, Visibility};
+use crate::classes::shared::transaction::Transaction;
+use crate::globals::GRANULARITY;
+use crate::kernel::agents::digest_cycle::DeterministicCycle;
+use crate::kernel::events::seconds_to_nanos;
+use crate::kernel::market::Market;
+use crate::kernel::order_book::{book::Level, record::ObStat};
+use crate::scenario::{ClockMode, Scenario};
+
+/// What the environment trades and shows, and how it scores each step
+#[derive(Clone)]
+pub struct EnvConfig {
+    pub stock: Stock,
+    // simulated seconds each step moves the clock by
+    pub step_seconds: f64,
+    // price levels a side in the observed book
+    pub depth_levels: usize,
+    // how many of the latest bars to observe, and which bars
+    pub bars: usize,
+    pub bar_granularity: GRANULARITY,
+    pub reward: Reward,
+    // the episode ends after this many steps, or when the scenario's duration_seconds run out
+    pub max_steps: Option<u64>,
+    pub cash: f64,
+    // charged on every fill as a fraction of its value
+    pub fee_rate: f64
+}
+
+impl EnvConfig {
+    pub fn new(stock: Stock) -> Self {
+        EnvConfig {
+            stock: stock,
+            step_seconds: 1.0,
+            depth_levels: 5,
+            bars: 10,
+            bar_granularity: GRANULARITY::SECOND,
+            reward: Reward::Pnl,
+            max_steps: None,
+            cash: 0.0,
+            fee_rate: 0.0
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !(self.step_seconds > 0.0 && self.step_seconds.is_finite()) {
+            return Err("step_seconds must be positive".to_string());
+        }
+        if !(self.fee_rate >= 0.0 && self.fee_rate.is_finite()) {
+            return Err("fee_rate must not be negative".to_string());
+        }
+        if matches!(self.bar_granularity, GRANULARITY::INSTANT) {
+            return Err("bar_granularity can't be INSTANT".to_string());
+        }
+        if matches!(self.reward, Reward::ImplementationShortfall { amount: 0, .. }) {
+            return Err("reward: amount must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Reward {
+    // the change in cash (after fees) plus inventory marked at the last price (the best quote before any trade)
+    Pnl,
+    // working a parent order of `amount` on one side: each fill earns how much better than the price on
+    // arrival it went less its fee, and whatever is left when the episode ends is charged at the last price
+    ImplementationShortfall { side: OrderType, amount: u64 }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    // a market order without a price
+    Buy { amount: u64, price: Option<f64> },
+    Sell { amount: u64, price: Option<f64> },
+    Cancel { order_id: u64 },
+    CancelAll
+}
+
+#[derive(Serialize, Clone)]
+pub struct Observation {
+    pub time: i64,
+    pub price: f64,
+    pub bids: Vec<Level>,
+    pub asks: Vec<Level>,
+    // oldest first
+    pub bars: Vec<ObStat>,
+    pub inventory: i64,
+    pub cash: f64,
+    pub open_orders: Vec<u64>
+}
+
+#[derive(Serialize, Clone)]
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f64,
+    pub done: bool,
+    // ids given to the step's orders, in the order they were placed
+    pub orders: Vec<u64>,
+    // what the learner's orders traded during the step
+    pub fills: Vec<Fill>
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Fill {
+    pub order_id: u64,
+    pub side: OrderType,
+    pub price: f64,
+    pub volume: u64,
+    pub timestamp: i64,
+    pub fee: f64
+}
+
+impl Fill {
+    fn new(order_id: u64, side: OrderType, trade: &Transaction, fee_rate: f64) -> Self {
+        Fill {
+            order_id: order_id,
+            side: side,
+            price: trade.price,
+            volume: trade.volume,
+            timestamp: trade.timestamp,
+            fee: trade.price * trade.volume as f64 * fee_rate
+        }
+    }
+}
+
+/// A Gym style loop over one stock of a deterministic market: reset brings up a fresh market from a scenario,
+/// step places and cancels the learner's orders and runs the market on for a fixed stretch of simulated time.
+/// The same seed, scenario and actions always give the same observations and rewards.
+pub struct Environment {
+    config: EnvConfig,
+    episode: Option<Episode>
+}
+
+struct Episode {
+    market: Arc<Market>,
+    cycle: DeterministicCycle,
+    end: Option<i64>,
+    steps: u64,
+    // the learner's orders still on the book, and every order it placed
+    open: BTreeSet<u64>,
+    placed: HashSet<u64>,
+    // trades of the stock already looked through for fills
+    trades_seen: usize,
+    inventory: i64,
+    cash: f64,
+    value: f64,
+    arrival_price: f64,
+    filled: u64,
+    done: bool
+}
+
+impl Environment {
+    pub fn new(config: EnvConfig) -> Self {
+        Environment {
+            config: config,
+            episode: None
+        }
+    }
+
+    /// The market of the current episode
+    pub fn market(&self) -> Option<&Arc<Market>> {
+        self.episode.as_ref().map(|episode| &episode.market)
+    }
+
+    /// Starts a new episode on a fresh market, the scenario is run on the virtual clock under `seed`
+    pub fn reset(&mut self, seed: u64, scenario: &Scenario) -> Result<Observation, String> {
+        self.config.validate()?;
+        if scenario.replay.is_some() {
+            return Err("a replay can't be stepped".to_string());
+        }
+        let mut scenario = scenario.clone();
+        scenario.seed = Some(seed);
+        scenario.clock.mode = ClockMode::Deterministic;
+        scenario.validate()?;
+
+        if let Some(episode) = self.episode.take() {
+            let _ = episode.market.shut_down();
+        }
+        let market = Arc::new(Market::new());
+        let stocks = scenario.prepare(&market).map_err(|e| e.to_string())?;
+        let stock = self.config.stock;
+        if !stocks.contains(&stock) {
+            return Err(format!("the scenario doesn't list {:?}", stock));
+        }
+
+        let end = scenario.clock.duration_seconds.map(|d| market.clock.now() + seconds_to_nanos(d));
+        self.episode = Some(Episode {
+            cycle: DeterministicCycle::new(market.clone(), stocks, scenario.clock.tick_nanos()),
+            end: end,
+            steps: 0,
+            open: BTreeSet::new(),
+            placed: HashSet::new(),
+            trades_seen: market.trade_count(stock),
+            inventory: 0,
+            cash: self.config.cash,
+            value: self.config.cash,
+            arrival_price: reference_price(&market, stock),
+            filled: 0,
+            done: false,
+            market: market
+        });
+        Ok(self.observe())
+    }
+
+    /// Places and cancels orders as the actions say, in order, then runs the market for step_seconds
+    pub fn step(&mut self, actions: &[Action]) -> Result<Step, String> {
+        let config = &self.config;
+        let stock = config.stock;
+        let Some(episode) = self.episode.as_mut() else {
+            return Err("reset the environment before stepping it".to_string())
+        };
+        if episode.done {
+            return Err("the episode is over, reset to start another".to_string());
+        }
+        let market = episode.market.clone();
+
+        let mut orders = Vec::new();
+        for action in actions {
+            match *action {
+                Action::Buy { amount, price } | Action::Sell { amount, price } => {
+                    let order_type = if matches!(action, Action::Buy { .. }) { OrderType::Buy } else { OrderType::Sell };
+                    let id = market.next_order_id();
+                    market.place_order(stock, amount, order_type, price, None, TimeInForce::default(), Visibility::Displayed, Some(id));
+                    episode.open.insert(id);
+                    episode.placed.insert(id);
+                    orders.push(id);
+                },
+                // only the learner's own orders can be cancelled
+                Action::Cancel { order_id } => {
+                    if episode.open.contains(&order_id) {
+                        market.cancel(stock, order_id);
+                    }
+                },
+                Action::CancelAll => {
+                    for &id in &episode.open {
+                        market.cancel(stock, id);
+                    }
+                }
+            }
+        }
+
+        let target = market.clock.now() + seconds_to_nanos(config.step_seconds);
+        episode.cycle.run_until(episode.end.map_or(target, |end| end.min(target)));
+        episode.steps += 1;
+
+        let trades = market.trades_since(stock, episode.trades_seen);
+        episode.trades_seen += trades.len();
+        let mut fills = Vec::new();
+        for trade in trades {
+            // an order crossing one of the learner's own fills on both sides
+            for (id, side) in [(trade.buy_id, OrderType::Buy), (trade.sell_id, OrderType::Sell)] {
+                if let Some(id) = id.filter(|id| episode.placed.contains(id)) {
+                    fills.push(Fill::new(id, side, &trade, config.fee_rate));
+                }
+            }
+        }
+
+        let mut shortfall = 0.0;
+        for fill in &fills {
+            let value = fill.price * fill.volume as f64;
+            match fill.side {
+                OrderType::Buy => {
+                    episode.inventory += fill.volume as i64;
+                    episode.cash -= value + fill.fee;
+                },
+                OrderType::Sell => {
+                    episode.inventory -= fill.volume as i64;
+                    episode.cash += value - fill.fee;
+                }
+            }
+            if let Reward::ImplementationShortfall { side, .. } = config.reward {
+                if fill.side == side {
+                    shortfall += side_sign(side) * (fill.price - episode.arrival_price) * fill.volume as f64 + fill.fee;
+                    episode.filled += fill.volume;
+                }
+            }
+        }
+        let pending = market.pending_order_ids(stock);
+        episode.open.retain(|id| pending.contains(id));
+
+        let price = reference_price(&market, stock);
+        episode.done = config.max_steps.is_some_and(|max| episode.steps >= max)
+            || episode.end.is_some_and(|end| market.clock.now() >= end)
+            || matches!(config.reward, Reward::ImplementationShortfall { amount, .. } if episode.filled >= amount);
+
+        let value = episode.cash + episode.inventory as f64 * price;
+        let reward = match config.reward {
+            Reward::Pnl => value - episode.value,
+            Reward::ImplementationShortfall { side, amount } => {
+                // what never filled is as good as bought or sold at the last price
+                if episode.done {
+                    let remaining = amount.saturating_sub(episode.filled) as f64;
+                    shortfall += side_sign(side) * (price - episode.arrival_price) * remaining;
+                }
+                -shortfall
+            }
+        };
+        episode.value = value;
+
+        Ok(Step {
+            done: episode.done,
+            observation: self.observe(),
+            reward: reward,
+            orders: orders,
+            fills: fills
+        })
+    }
+
+    fn observe(&self) -> Observation {
+        let episode = self.episode.as_ref().unwrap();
+        let market = &episode.market;
+        let stock = self.config.stock;
+        let (bids, asks) = market.get_depth(stock, self.config.depth_levels);
+        Observation {
+            time: market.clock.now(),
+            price: market.get_price(stock),
+            bids: bids,
+            asks: asks,
+            bars: market.get_bars(stock, self.config.bar_granularity, self.config.bars),
+            inventory: episode.inventory,
+            cash: episode.cash,
+            open_orders: episode.open.iter().copied().collect()
+        }
+    }
+}
+
+// the last price, or before the first trade the best quote
+fn reference_price(market: &Market, stock: Stock) -> f64 {
+    let price = market.get_price(stock);
+    if price > 0.0 {
+        return price;
+    }
+    let (bids, asks) = market.get_depth(stock, 1);
+    asks.first().or(bids.first()).map_or(0.0, |level| level.0)
+}
+
+// buying above the arrival price costs, selling above it gains
+fn side_sign(side: OrderType) -> f64 {
+    match side {
+        OrderType::Buy => 1.0,
+        OrderType::Sell => -1.0
+    }
+}
@@ -0,0 +1,198 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+use crate::classes::shared::order::Stock;
+use crate::globals::GRANULARITY;
+use crate::kernel::agents::strategies::value_process;
+use crate::kernel::market::Market;
+use crate::kernel::order_book::record::ObStat;
+
+// a bad file usually has the same problem on every line, there's no use listing them all
+const MAX_ERRORS: usize = 20;
+
+const TIME_COLUMNS: [&str; 4] = ["timestamp", "time", "date", "datetime"];
+
+/// One OHLCV bar as it was read, at the time it opened
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bar {
+    pub time: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: u64
+}
+
+/// Bars from a CSV file, oldest first, all at one granularity
+#[derive(Clone)]
+pub struct ImportedHistory {
+    pub granularity: GRANULARITY,
+    pub bars: Vec<Bar>
+}
+
+impl ImportedHistory {
+    pub fn last_close(&self) -> f64 {
+        self.bars.last().unwrap().close
+    }
+
+    /// The bars as `ObStat`s, the last one in the unit just before `now` and the rest
+    /// counting back from it, so gaps (weekends, nights) stay where they were
+    pub fn to_bars(&self, now: i64) -> Vec<ObStat> {
+        let unit_seconds = unit_seconds(self.granularity);
+        let last_tick = now.div_euclid(self.granularity as i64) - 1;
+        let last_time = self.bars.last().unwrap().time.timestamp();
+
+        self.bars.iter().map(|bar| ObStat {
+            tick: last_tick - (last_time - bar.time.timestamp()) / unit_seconds,
+            granularity: self.granularity,
+            volume: bar.volume,
+            high: bar.high,
+            low: bar.low,
+            open: bar.open,
+            close: bar.close
+        }).collect()
+    }
+}
+
+pub fn read(path: &Path) -> Result<ImportedHistory, String> {
+    let file = File::open(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+    parse(file).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Reads OHLCV bars from CSV with a header row. The time column can be called timestamp,
+/// time, date or datetime and hold RFC 3339, "YYYY-MM-DD HH:MM[:SS]", "YYYY-MM-DD" or unix seconds,
+/// open, high, low and close are needed and volume is optional. Other columns are ignored.
+pub fn parse<R: Read>(reader: R) -> Result<ImportedHistory, String> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(reader);
+    let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+    let column = |names: &[&str]| headers.iter().position(|h| names.contains(&h.to_lowercase().as_str()));
+
+    let time = column(&TIME_COLUMNS);
+    let open = column(&["open"]);
+    let high = column(&["high"]);
+    let low = column(&["low"]);
+    let close = column(&["close"]);
+    let volume = column(&["volume"]);
+    let (Some(time), Some(open), Some(high), Some(low), Some(close)) = (time, open, high, low, close) else {
+        return Err(format!("expected a {} column and open, high, low and close columns", TIME_COLUMNS.join("/")));
+    };
+
+    let mut bars: Vec<Bar> = Vec::new();
+    let mut errors = Vec::new();
+    for row in reader.records() {
+        let row = match row {
+            Ok(row) => row,
+            Err(e) => {
+                errors.push(e.to_string());
+                continue;
+            }
+        };
+        let line = row.position().map_or(0, |p| p.line());
+        let bar = parse_bar(&row, time, [open, high, low, close], volume)
+            .and_then(|bar| match bars.last() {
+                Some(previous) if bar.time <= previous.time => Err("times must be increasing".to_string()),
+                _ => Ok(bar)
+            });
+        match bar {
+            Ok(bar) => bars.push(bar),
+            Err(e) => errors.push(format!("line {}: {}", line, e))
+        }
+    }
+
+    if errors.is_empty() && bars.len() < 2 {
+        errors.push("at least two bars are needed to tell the granularity".to_string());
+    }
+    if !errors.is_empty() {
+        let more = errors.len().saturating_sub(MAX_ERRORS);
+        errors.truncate(MAX_ERRORS);
+        if more > 0 {
+            errors.push(format!("and {} more", more));
+        }
+        return Err(errors.join("\n"));
+    }
+
+    let granularity = detect_granularity(&bars)?;
+    Ok(ImportedHistory { granularity: granularity, bars: bars })
+}
+
+fn parse_bar(row: &csv::StringRecord, time: usize, prices: [usize; 4], volume: Option<usize>) -> Result<Bar, String> {
+    let field = |i: usize| row.get(i).unwrap_or("");
+    let time = parse_time(field(time))?;
+
+    let mut ohlc = [0.0; 4];
+    for (value, (i, name)) in ohlc.iter_mut().zip(prices.iter().zip(["open", "high", "low", "close"])) {
+        *value = field(*i).parse::<f64>()
+            .ok()
+            .filter(|p| *p > 0.0 && p.is_finite())
+            .ok_or(format!("{} '{}' is not a positive price", name, field(*i)))?;
+    }
+    let [open, high, low, close] = ohlc;
+    if high < open.max(close) || low > open.min(close) {
+        return Err(format!("high {} and low {} don't contain open {} and close {}", high, low, open, close));
+    }
+
+    let volume = match volume.map(field) {
+        None | Some("") => 0,
+        Some(v) => v.parse::<f64>()
+            .ok()
+            .filter(|v| *v >= 0.0 && v.is_finite())
+            .ok_or(format!("volume '{}' is not a non negative number", v))?
+            .round() as u64
+    };
+
+    Ok(Bar { time: time, open: open, high: high, low: low, close: close, volume: volume })
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(value, format) {
+            return Ok(time.and_utc());
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    }
+    value.parse::<i64>()
+        .ok()
+        .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+        .ok_or(format!("time '{}' is not a date, date time or unix timestamp", value))
+}
+
+fn unit_seconds(granularity: GRANULARITY) -> i64 {
+    match granularity {
+        GRANULARITY::SECOND => 1,
+        GRANULARITY::MINUTE => 60,
+        GRANULARITY::HOUR => 3600,
+        GRANULARITY::DAY => 86400,
+        GRANULARITY::INSTANT => panic!()
+    }
+}
+
+// the smallest gap between bars is their length, every other gap has to be a whole number of bars
+fn detect_granularity(bars: &[Bar]) -> Result<GRANULARITY, String> {
+    let gaps: Vec<i64> = bars.windows(2).map(|w| (w[1].time - w[0].time).num_seconds()).collect();
+    let smallest = *gaps.iter().min().unwrap();
+
+    let granularity = [GRANULARITY::SECOND, GRANULARITY::MINUTE, GRANULARITY::HOUR, GRANULARITY::DAY]
+        .into_iter()
+        .find(|g| unit_seconds(*g) == smallest)
+        .ok_or(format!("bars are {} seconds apart, expected a second, minute, hour or day", smallest))?;
+
+    if let Some(i) = gaps.iter().position(|gap| gap % smallest != 0) {
+        return Err(format!("the gap after {} is not a whole number of bars", bars[i].time.to_rfc3339()));
+    }
+    Ok(granularity)
+}
+
+/// Fills a freshly listed stock's history with the imported bars and starts its
+/// price and fundamental value at the last close
+pub fn seed(market: &Market, stock: Stock, history: &ImportedHistory) {
+    market.seed_history(stock, history.to_bars(market.clock.now()));
+    value_process::set_fundamental_value(market, stock, history.last_close());
+}
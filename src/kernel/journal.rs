@@ -0,0 +1,239 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::classes::shared::{order::*, transaction::Transaction};
+use crate::kernel::events::MarketEvent;
+use crate::kernel::market_time::market_time::MTime;
+use crate::kernel::order_book::book::SelfTradePrevention;
+use crate::kernel::order_book::circuit_breaker::HaltReason;
+use crate::kernel::order_book::group::OrderGroup;
+
+/// Something that changed the state of a book
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JournalEntry {
+    OrderAccepted { order: Order },
+    Cancel { stock: Stock, order_id: u64 },
+    // what was left of an order when its lifetime ran out or its session closed
+    Expiry { stock: Stock, order_id: Option<u64>, order_type: OrderType, amount: u64, variant: OrderVariant, placed: i64 },
+    Halt { stock: Stock, reason: HaltReason },
+    Resume { stock: Stock },
+    // an auction is about to execute under the self-trade prevention in force, its trades follow
+    Uncross {
+        stock: Stock,
+        #[serde(default)]
+        self_trade_prevention: Option<SelfTradePrevention>
+    },
+    Trade { stock: Stock, transaction: Transaction },
+    Event { stock: Stock, event: MarketEvent },
+    // an oco or bracket group with its legs, which are not journaled on their own
+    GroupAccepted { stock: Stock, group: OrderGroup },
+    GroupCancel { stock: Stock, group_id: u64 },
+    // the best bid and ask would have traded but shared an owner, `mode` was applied to them instead
+    SelfTrade { stock: Stock, mode: SelfTradePrevention }
+}
+
+impl JournalEntry {
+    pub fn stock(&self) -> Stock {
+        match self {
+            JournalEntry::OrderAccepted { order } => order.details.stock,
+            JournalEntry::Cancel { stock, .. } |
+            JournalEntry::Expiry { stock, .. } |
+            JournalEntry::Halt { stock, .. } |
+            JournalEntry::Resume { stock } |
+            JournalEntry::Uncross { stock, .. } |
+            JournalEntry::Trade { stock, .. } |
+            JournalEntry::Event { stock, .. } |
+            JournalEntry::GroupAccepted { stock, .. } |
+            JournalEntry::GroupCancel { stock, .. } |
+            JournalEntry::SelfTrade { stock, .. } => *stock
+        }
+    }
+}
+
+/// One line of the journal
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JournalRecord {
+    pub seq: u64,
+    // market time the entry was written at
+    pub timestamp: i64,
+    #[serde(flatten)]
+    pub entry: JournalEntry
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FsyncPolicy {
+    // left to the OS, entries are buffered and written out as the buffer fills
+    Never,
+    // every entry is on disk before the change it records goes any further
+    EveryEntry,
+    // flushed and synced at most every `millis`
+    Periodic { millis: u64 }
+}
+
+impl Default for FsyncPolicy {
+    fn default() -> Self {
+        FsyncPolicy::Periodic { millis: 1000 }
+    }
+}
+
+/// Where a market writes its journal to, if anywhere. Entries are stamped with the market's clock.
+pub struct Journal {
+    file: Mutex<Option<JournalFile>>,
+    // checked before taking the lock so that an unjournaled market pays nothing
+    enabled: AtomicBool,
+    clock: Arc<MTime>
+}
+
+struct JournalFile {
+    writer: BufWriter<File>,
+    fsync: FsyncPolicy,
+    seq: u64,
+    last_sync: Instant
+}
+
+impl JournalFile {
+    fn write(&mut self, entry: JournalEntry, timestamp: i64) -> io::Result<()> {
+        self.seq += 1;
+        let record = JournalRecord {
+            seq: self.seq,
+            timestamp: timestamp,
+            entry: entry
+        };
+        serde_json::to_writer(&mut self.writer, &record)?;
+        self.writer.write_all(b"\n")?;
+
+        let sync = match self.fsync {
+            FsyncPolicy::Never => false,
+            FsyncPolicy::EveryEntry => true,
+            FsyncPolicy::Periodic { millis } => self.last_sync.elapsed() >= Duration::from_millis(millis)
+        };
+        if sync {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()?;
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+}
+
+// a journal that is never opened, for books that haven't been attached to a market yet
+impl Default for Journal {
+    fn default() -> Self {
+        Journal::new(Arc::new(MTime::default()))
+    }
+}
+
+impl Journal {
+    pub fn new(clock: Arc<MTime>) -> Self {
+        Journal {
+            file: Mutex::new(None),
+            enabled: AtomicBool::new(false),
+            clock: clock
+        }
+    }
+
+    /// Starts journaling to a JSON lines file, appending to it if it exists.
+    /// Sequence numbers carry on from the last entry already in the file, a last line torn
+    /// by a crash is cut off before anything is appended
+    pub fn open(&self, path: &Path, fsync: FsyncPolicy) -> io::Result<()> {
+        let (seq, good) = if path.exists() {
+            let (records, good) = read_records(path)?;
+            (records.last().map_or(0, |r| r.seq), Some(good))
+        } else {
+            (0, None)
+        };
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if let Some(good) = good {
+            if file.metadata()?.len() > good.len {
+                file.set_len(good.len)?;
+            }
+            if !good.terminated {
+                file.write_all(b"\n")?;
+            }
+        }
+
+        let mut journal = self.file.lock().unwrap();
+        if let Some(previous) = journal.as_mut() {
+            previous.sync()?;
+        }
+        *journal = Some(JournalFile {
+            writer: BufWriter::new(file),
+            fsync: fsync,
+            seq: seq,
+            last_sync: Instant::now()
+        });
+        self.enabled.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Flushes everything to disk and stops journaling
+    pub fn close(&self) -> io::Result<()> {
+        self.enabled.store(false, Ordering::SeqCst);
+        match self.file.lock().unwrap().take() {
+            Some(mut journal) => journal.sync(),
+            None => Ok(())
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Appends an entry, only built when a journal is open
+    pub fn record(&self, entry: impl FnOnce() -> JournalEntry) {
+        if !self.is_open() {
+            return;
+        }
+        if let Some(journal) = self.file.lock().unwrap().as_mut() {
+            // a journal that can no longer be written to should not take the market down with it
+            if let Err(e) = journal.write(entry(), self.clock.now()) {
+                eprintln!("journal write failed: {}", e);
+            }
+        }
+    }
+}
+
+/// The records of a journal. A last line that doesn't parse was torn by a crash mid-write and is
+/// left out, a bad line anywhere else is an error
+pub fn read(path: &Path) -> io::Result<Vec<JournalRecord>> {
+    read_records(path).map(|(records, _)| records)
+}
+
+// how much of a journal file holds whole records, and whether that part ends with a newline
+struct GoodPart {
+    len: u64,
+    terminated: bool
+}
+
+fn read_records(path: &Path) -> io::Result<(Vec<JournalRecord>, GoodPart)> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    let mut records = Vec::new();
+    let mut good = GoodPart { len: 0, terminated: true };
+    let lines: Vec<&[u8]> = bytes.split_inclusive(|b| *b == b'\n').collect();
+    for (i, chunk) in lines.iter().enumerate() {
+        let line = chunk.strip_suffix(b"\n").unwrap_or(chunk);
+        if !line.iter().all(u8::is_ascii_whitespace) {
+            match serde_json::from_slice(line) {
+                Ok(record) => records.push(record),
+                Err(_) if i + 1 == lines.len() => break,
+                Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", i + 1, e)))
+            }
+        }
+        good = GoodPart { len: good.len + chunk.len() as u64, terminated: chunk.ends_with(b"\n") };
+    }
+    Ok((records, good))
+}
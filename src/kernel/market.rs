@@ -1,35 +1,125 @@
-use std::sync::RwLock;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, RwLock};
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
-use lazy_static::lazy_static;
 use hashbrown::HashMap as HashbrownMap; // Optional, replace HashMap with HashbrownMap if using hashbrown
 use circular_buffer::CircularBuffer;
-
-use super::order_book::{book::*, record::*, stats::*};
-use super::market_time::market_time::*;
+use serde::{Deserialize, Serialize};
+
+use super::order_book::{book::*, circuit_breaker::*, group::*, record::*, stats::*};
+use super::market_time::{market_time::*, session::*};
+use super::agents::population::arrivals::SimulationSeed;
+use super::agents::registry::Registry;
+use super::agents::strategies::value_process::Fundamentals;
+use super::agents::trend::chaotic_trend_generator::CommonFactors;
+use super::events::*;
+use super::journal::{Journal, JournalEntry};
+use super::replay::ReplayReport;
+use super::tape::Tape;
 
 use crate::classes::api::response_classes::StockHistoryDTO;
 use crate::classes::shared::order::{self, *};
 use crate::classes::shared::transaction::Transaction;
 use crate::globals::*;
 
+/// One exchange and everything trading on it: the books, the clock, the journal, the agents
+/// and their random state. Markets share nothing, any number of them can run in one process.
 pub struct Market {
-    stock_book: RwLock<HashbrownMap<Stock, RwLock<StockRecord>>>
+    stock_book: RwLock<HashbrownMap<Stock, RwLock<StockRecord>>>,
+    next_order_id: AtomicU64,
+    pub clock: Arc<MTime>,
+    pub journal: Arc<Journal>,
+    pub tape: Tape,
+    pub(crate) agents: Registry,
+    pub(crate) fundamentals: Fundamentals,
+    pub(crate) events: EventSchedule,
+    pub(crate) common_factors: CommonFactors,
+    pub(crate) seeds: SimulationSeed,
+    pub(crate) replay_status: Mutex<Option<ReplayReport>>,
+    // set once the market is shut down, its trading threads stop at their next tick
+    stopped: AtomicBool
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct StockRecord {
     pub order_book: OrderBook,
     pub history: HistoryBuffer,
     pub stats: Stats,
-    pub recent_transactions: CircularBuffer<100, Transaction>
+    #[serde(with = "transaction_buffer")]
+    pub recent_transactions: CircularBuffer<100, Transaction>,
+    // None trades continuously around the clock
+    pub schedule: Option<SessionSchedule>,
+    pub phase: SessionPhase,
+    pub events: Vec<EventRecord>
 }
 
 impl StockRecord {
-    fn new(stock: Stock) -> Self {
+    fn new(stock: Stock, clock: Arc<MTime>, journal: Arc<Journal>) -> Self {
         StockRecord {
-            order_book: OrderBook::new(stock),
+            order_book: OrderBook::new(stock, clock, journal),
             history: HistoryBuffer::new(),
             stats: Stats::new(),
-            recent_transactions: CircularBuffer::<100, Transaction>::new()
+            recent_transactions: CircularBuffer::<100, Transaction>::new(),
+            schedule: None,
+            phase: SessionPhase::Continuous,
+            events: Vec::new()
+        }
+    }
+
+    fn duplicate(&self) -> Self {
+        StockRecord {
+            order_book: self.order_book.duplicate(),
+            history: self.history.clone(),
+            stats: self.stats.clone(),
+            recent_transactions: self.recent_transactions.clone(),
+            schedule: self.schedule.clone(),
+            phase: self.phase,
+            events: self.events.clone()
+        }
+    }
+
+    fn update_session(&mut self, clock: &MTime, journal: &Journal) {
+        let now = clock.now();
+        if self.order_book.breaker.halt_expired(now) {
+            self.resume(journal);
+        }
+
+        let phase = match &self.schedule {
+            Some(schedule) => schedule.phase_at(clock.simulated_datetime(now)),
+            None => SessionPhase::Continuous
+        };
+        if phase == self.phase {
+            return;
+        }
+
+        // orders accumulated during an auction are uncrossed when it ends
+        if self.phase.is_auction() {
+            self.order_book.uncross();
+        }
+        if phase == SessionPhase::Closed {
+            self.order_book.expire_day_orders();
+        }
+        self.phase = phase;
+    }
+
+    fn resume(&mut self, journal: &Journal) {
+        if self.order_book.breaker.is_halted() {
+            let stock = self.order_book.stock();
+            journal.record(|| JournalEntry::Resume { stock: stock });
+        }
+        // outside continuous trading the next scheduled auction does the reopening
+        if self.phase == SessionPhase::Continuous {
+            self.order_book.resume();
+        } else {
+            self.order_book.breaker.halt = None;
+        }
+    }
+
+    fn round_to_tick(&self, price: f64) -> f64 {
+        match self.order_book.tick_size {
+            Some(tick) => (price / tick).round() * tick,
+            None => price
         }
     }
 
@@ -47,171 +137,589 @@ impl StockRecord {
     }
 }
 
-lazy_static! {
-    pub static ref MARKET: Market = Market { 
-        stock_book: RwLock::new(HashbrownMap::new())
-    };
+// the ring buffer has no serde support of its own, it goes to disk as a list oldest first
+mod transaction_buffer {
+    use circular_buffer::CircularBuffer;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::classes::shared::transaction::Transaction;
+
+    pub fn serialize<S: Serializer>(buffer: &CircularBuffer<100, Transaction>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(buffer.iter())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<CircularBuffer<100, Transaction>, D::Error> {
+        let mut buffer = CircularBuffer::<100, Transaction>::new();
+        buffer.extend(Vec::<Transaction>::deserialize(deserializer)?);
+        Ok(buffer)
+    }
+}
+
+impl Default for Market {
+    fn default() -> Self {
+        Market::new()
+    }
 }
 
-pub fn ipo(stock: Stock, amount: u64, price: f64, id: Option<u64>) {
-    {
-        let mut market = MARKET.stock_book.write().unwrap();
+impl Market {
+    pub fn new() -> Self {
+        let clock = Arc::new(MTime::default());
+        Market {
+            stock_book: RwLock::new(HashbrownMap::new()),
+            next_order_id: AtomicU64::new(1),
+            journal: Arc::new(Journal::new(clock.clone())),
+            clock: clock,
+            tape: Tape::default(),
+            agents: Registry::default(),
+            fundamentals: Fundamentals::default(),
+            events: EventSchedule::default(),
+            common_factors: CommonFactors::default(),
+            seeds: SimulationSeed::default(),
+            replay_status: Mutex::new(None),
+            stopped: AtomicBool::new(false)
+        }
+    }
+
+    /// Stops every thread trading on the market and closes its journal, the books stay readable
+    pub fn shut_down(&self) -> io::Result<()> {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.journal.close()
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    pub fn next_order_id(&self) -> u64 {
+        self.next_order_id.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// The id the next order will get, without taking it
+    pub fn peek_next_order_id(&self) -> u64 {
+        self.next_order_id.load(Ordering::SeqCst)
+    }
+
+    pub fn set_next_order_id(&self, id: u64) {
+        self.next_order_id.store(id, Ordering::SeqCst);
+    }
+
+    /// A copy of every listed stock's record
+    pub fn snapshot_records(&self) -> Vec<StockRecord> {
+        let lock =  self.stock_book.read().unwrap();
+        lock.values().map(|record| record.read().unwrap().duplicate()).collect()
+    }
+
+    /// Replaces every listed stock with the given records
+    pub fn restore_records(&self, records: Vec<StockRecord>) {
+        let mut market = self.stock_book.write().unwrap();
+        market.clear();
+        for mut record in records {
+            record.order_book.attach(self.clock.clone(), self.journal.clone());
+            // the tape isn't part of a snapshot, it starts again from here
+            self.tape.clear(record.order_book.stock());
+            market.insert(record.order_book.stock(), RwLock::new(record));
+        }
+    }
+
+    pub fn ipo(&self, stock: Stock, amount: u64, price: f64, id: Option<u64>) {
+        self.list(stock);
+        self.place_order(stock, amount, OrderType::Sell, Some(price), None, TimeInForce::GoodTillCancel, Visibility::Displayed, id)
+    }
+
+    /// Lists a stock with an empty book, replacing whatever was there
+    pub fn list(&self, stock: Stock) {
+        self.tape.clear(stock);
+        let mut market = self.stock_book.write().unwrap();
         market.insert(
             stock,
-            RwLock::new(StockRecord::new(stock))
+            RwLock::new(StockRecord::new(stock, self.clock.clone(), self.journal.clone()))
         );
     }
-   
-    place_order(stock, amount, OrderType::Sell, Some(price), None, id)
-}
 
-pub fn buy(stock: Stock, amount: u64, price: Option<f64>, lifetime: Option<i64>, id: Option<u64>){
-    place_order(stock, amount, OrderType::Buy, price, lifetime, id)    
-}
+    pub fn listed(&self) -> Vec<Stock> {
+        self.stock_book.read().unwrap().keys().copied().collect()
+    }
 
+    pub fn is_listed(&self, stock: Stock) -> bool {
+        self.stock_book.read().unwrap().contains_key(&stock)
+    }
 
-pub fn sell(stock: Stock, amount: u64, price: Option<f64>, lifetime: Option<i64>, id: Option<u64>){
-    place_order(stock, amount, OrderType::Sell, price, lifetime, id)
-}
+    pub fn buy(&self, stock: Stock, amount: u64, price: Option<f64>, lifetime: Option<i64>, id: Option<u64>){
+        self.place_order(stock, amount, OrderType::Buy, price, lifetime, TimeInForce::GoodTillCancel, Visibility::Displayed, id)    
+    }
 
-pub fn clean_books(stock: Stock) {
-    let lock =  MARKET.stock_book.read().unwrap();
-    let book = &mut lock.get(&stock).unwrap().write().unwrap().order_book;
-    book.clean_book();
-}
 
-pub fn find_trades(stock: Stock) {
-    let lock =  MARKET.stock_book.read().unwrap();
-    let book = &mut lock.get(&stock).unwrap().write().unwrap().order_book;
-    book.find_trade();
-}
+    pub fn sell(&self, stock: Stock, amount: u64, price: Option<f64>, lifetime: Option<i64>, id: Option<u64>){
+        self.place_order(stock, amount, OrderType::Sell, price, lifetime, TimeInForce::GoodTillCancel, Visibility::Displayed, id)
+    }
 
-pub fn report_transactions(stock: Stock) -> Vec<Transaction>{
-    let lock =  MARKET.stock_book.read().unwrap();
-    let record = &mut lock.get(&stock).unwrap().write().unwrap();
-    record.report_transactions();
+    pub fn cancel(&self, stock: Stock, id: u64) -> bool {
+        let lock =  self.stock_book.read().unwrap();
+        let book = &mut lock.get(&stock).unwrap().write().unwrap().order_book;
+        book.cancel(id)
+    }
 
-    let transactions = &mut record.order_book.transaction_record;
-    if transactions.len() == 0 {return Vec::new()}
+    pub fn set_schedule(&self, stock: Stock, schedule: Option<SessionSchedule>) {
+        let lock =  self.stock_book.read().unwrap();
+        let record = &mut lock.get(&stock).unwrap().write().unwrap();
+        record.schedule = schedule;
+        record.update_session(&self.clock, &self.journal);
+    }
 
-    let last_second_timestamp: u64 = MTime::which_second(transactions.last().unwrap().timestamp) * GRANULARITY::SECOND as u64;
-    
-    let index = transactions.iter()
-        .position(|x| x.timestamp > last_second_timestamp as i64)
-        .unwrap_or(transactions.len());
+    pub fn update_session(&self, stock: Stock) {
+        let lock =  self.stock_book.read().unwrap();
+        let record = &mut lock.get(&stock).unwrap().write().unwrap();
+        record.update_session(&self.clock, &self.journal);
+    }
 
-    let whole_seconds = transactions.drain(0..index).collect::<Vec<Transaction>>();
+    pub fn set_tick_size(&self, stock: Stock, tick_size: Option<f64>) {
+        let lock =  self.stock_book.read().unwrap();
+        let record = &mut lock.get(&stock).unwrap().write().unwrap();
+        record.order_book.tick_size = tick_size;
+    }
 
-    let stock_record = &mut record.history;
-    stock_record.process_transactions(&whole_seconds);
-    stock_record.compress();
+    pub fn set_price_band(&self, stock: Stock, band: Option<PriceBand>) {
+        let lock =  self.stock_book.read().unwrap();
+        let book = &mut lock.get(&stock).unwrap().write().unwrap().order_book;
+        book.breaker.price_band = band;
+        if book.price > 0.0 {
+            book.breaker.reset(book.price, self.clock.now());
+        }
+    }
 
-    whole_seconds
-}
+    pub fn set_self_trade_prevention(&self, stock: Stock, mode: Option<SelfTradePrevention>) {
+        let lock =  self.stock_book.read().unwrap();
+        let book = &mut lock.get(&stock).unwrap().write().unwrap().order_book;
+        book.self_trade_prevention = mode;
+    }
 
-pub fn get_order_status(stock: Stock, id: u64, order_type: OrderType) -> OrderStatus { 
-    let executed = search_transaction_buffer(stock, id, order_type);
-    let pending = search_orderbook(stock, id, order_type);  
-   
-    match (executed, pending) {
-        (Some(price), false) => OrderStatus::Executed { price: price },
-        (Some(_), true) => OrderStatus::PartiallyFilled,
-        (None, true) => OrderStatus::Pending,
-        _ => panic!()
+    pub fn halt(&self, stock: Stock) {
+        self.halt_for(stock, HaltReason::Manual);
     }
-}
 
-fn search_transaction_buffer(stock: Stock, id: u64, order_type: OrderType) -> Option<f64> {
-    let lock =  MARKET.stock_book.read().unwrap();
-    let record = &lock.get(&stock).unwrap().read().unwrap();
-    
-    let search_criteria: &dyn Fn(Transaction) -> bool;
+    pub fn halt_for(&self, stock: Stock, reason: HaltReason) {
+        let lock =  self.stock_book.read().unwrap();
+        let book = &mut lock.get(&stock).unwrap().write().unwrap().order_book;
+        book.halt(reason);
+    }
 
-    let sell_cond = |t: Transaction| t.sell_id == Some(id);
-    let buy_cond = |t: Transaction| t.buy_id == Some(id);
-    match order_type {
-        OrderType::Sell => search_criteria = &sell_cond,
-        OrderType::Buy => search_criteria = &buy_cond
+    // clears a halt without the reopening auction
+    pub fn lift_halt(&self, stock: Stock) {
+        let lock =  self.stock_book.read().unwrap();
+        let book = &mut lock.get(&stock).unwrap().write().unwrap().order_book;
+        book.breaker.halt = None;
     }
 
-    let (sum, count) = record.recent_transactions.iter()
-        .filter(|&t| search_criteria(*t))
-        .fold((0.0, 0), |(sum, count), t| (sum + t.price, count + 1));
+    pub fn resume(&self, stock: Stock) {
+        let lock =  self.stock_book.read().unwrap();
+        let record = &mut lock.get(&stock).unwrap().write().unwrap();
+        record.resume(&self.journal);
+    }
 
-    if count > 0 {
-        let avg_price = sum / count as f64;
-        Some(avg_price)
-    } else {
-        None
+    pub fn get_halt(&self, stock: Stock) -> Option<HaltReason> {
+        let lock =  self.stock_book.read().unwrap();
+        let book = &lock.get(&stock).unwrap().read().unwrap().order_book;
+        book.breaker.halt_reason()
     }
-}
 
-fn search_orderbook(stock: Stock, id: u64, order_type: OrderType) -> bool {
-    let lock =  MARKET.stock_book.read().unwrap();
-    let record = &lock.get(&stock).unwrap().read().unwrap();
+    pub fn get_depth(&self, stock: Stock, levels: usize) -> (Vec<Level>, Vec<Level>) {
+        let lock =  self.stock_book.read().unwrap();
+        let book = &lock.get(&stock).unwrap().read().unwrap().order_book;
+        book.depth(levels)
+    }
 
-    match order_type {
-        OrderType::Buy => record.order_book.is_pending_bid(id),
-        OrderType::Sell => record.order_book.is_pending_ask(id) 
+    pub fn get_quotes(&self, stock: Stock) -> (Option<f64>, Option<f64>) {
+        let lock =  self.stock_book.read().unwrap();
+        let book = &lock.get(&stock).unwrap().read().unwrap().order_book;
+        book.best_quotes()
     }
-}
 
-pub fn update_stats(stock: Stock) {
-    let market_lock =  MARKET.stock_book.read().unwrap();
-    let record = &mut market_lock.get(&stock).unwrap().write().unwrap();
-    record.update_stats()
-}
+    pub fn get_session_phase(&self, stock: Stock) -> SessionPhase {
+        let lock =  self.stock_book.read().unwrap();
+        let record = &lock.get(&stock).unwrap().read().unwrap();
+        record.phase
+    }
 
+    pub fn record_event(&self, stock: Stock, event: MarketEvent) {
+        let lock =  self.stock_book.read().unwrap();
+        let record = &mut lock.get(&stock).unwrap().write().unwrap();
+        // a price shock leaves the last price alone, it moves as agents trade towards the news
+        record.events.push(EventRecord {
+            timestamp: self.clock.now(),
+            event: event
+        });
+        self.journal.record(|| JournalEntry::Event { stock: stock, event: event });
+    }
 
-fn place_order(stock: Stock, amount: u64, order_type: OrderType, price: Option<f64>, lifetime: Option<i64>, id: Option<u64>){
-    if amount <= 0 {
-        return;
-    }
-    // println!("placing order");
-    use OrderVariant::*;
-    let order = Order {
-        id: id,
-        order_type: order_type,
-        variant: match price {
-            Some(p) => Limit { price: (p) },
-            None => Market
-        },
-        details: OrderDetails {
-            time: MTime::now(),
-            stock: stock,
-            amount: amount,
-            lifetime_nanos: lifetime
-        }
-    };
-    let lock =  MARKET.stock_book.read().unwrap();
-    let book = &mut lock.get(&stock).unwrap().write().unwrap().order_book;
-    book.process_order(order);
-}
+    pub fn get_events(&self, stock: Stock) -> Vec<EventRecord> {
+        let lock =  self.stock_book.read().unwrap();
+        let record = &lock.get(&stock).unwrap().read().unwrap();
+        record.events.clone()
+    }
 
-pub fn get_price(stock: Stock) -> f64 {
-    let lock =  MARKET.stock_book.read().unwrap();
-    let book = &lock.get(&stock).unwrap().read().unwrap().order_book;
-    book.price
-}
+    pub fn clean_books(&self, stock: Stock) {
+        let lock =  self.stock_book.read().unwrap();
+        let book = &mut lock.get(&stock).unwrap().write().unwrap().order_book;
+        book.clean_book();
+    }
 
-pub fn get_stock_history(stock: Stock, granularity: GRANULARITY, count: usize) -> Vec<StockHistoryDTO> {
-    let lock =  MARKET.stock_book.read().unwrap();
-    let history = &lock.get(&stock).unwrap().read().unwrap().history._historic_data;
+    pub fn find_trades(&self, stock: Stock) {
+        let lock =  self.stock_book.read().unwrap();
+        let record = &mut lock.get(&stock).unwrap().write().unwrap();
+        // outside of continuous trading orders rest until the next auction
+        if record.phase == SessionPhase::Continuous {
+            record.order_book.find_trade();
+        }
+    }
 
-    let requested_hist = &history[granularity_index(granularity)];
-    let n = requested_hist.len();
-    
-    let subj: &[ObStat];
-    if n <= count {
-        subj = &requested_hist[..];
-    } else {
-        subj = &requested_hist[n-count..];
+    /// Executes at most one trade whatever the session phase, handing it back
+    pub fn match_next(&self, stock: Stock) -> Option<Transaction> {
+        let lock =  self.stock_book.read().unwrap();
+        let book = &mut lock.get(&stock).unwrap().write().unwrap().order_book;
+        if book.match_next() {
+            book.transaction_record.last().copied()
+        } else {
+            None
+        }
     }
 
-    subj.iter().map(|x| Into::<StockHistoryDTO>::into(*x)).collect()
-}
+    /// Applies a self-trade prevention step the journal recorded
+    pub fn self_trade(&self, stock: Stock, mode: SelfTradePrevention) -> bool {
+        let lock =  self.stock_book.read().unwrap();
+        let book = &mut lock.get(&stock).unwrap().write().unwrap().order_book;
+        book.self_trade(mode)
+    }
+
+    /// Runs an auction under `mode` whatever the session phase, handing back its trades and
+    /// how many self-trade steps it took
+    pub fn uncross(&self, stock: Stock, mode: Option<SelfTradePrevention>) -> (Vec<Transaction>, usize) {
+        let lock =  self.stock_book.read().unwrap();
+        let book = &mut lock.get(&stock).unwrap().write().unwrap().order_book;
+        let before = book.transaction_record.len();
+        let self_trades = book.uncross_with(mode).map_or(0, |(_, self_trades)| self_trades);
+        (book.transaction_record[before..].to_vec(), self_trades)
+    }
+
+    /// Puts an order on the book exactly as given, for replaying orders the market already accepted
+    pub fn accept_order(&self, order: Order) {
+        let lock =  self.stock_book.read().unwrap();
+        let book = &mut lock.get(&order.details.stock).unwrap().write().unwrap().order_book;
+        book.process_order(order);
+    }
+
+    pub fn remove_orders(&self, stock: Stock, retain_condition: impl FnMut(&Order) -> bool) {
+        let lock =  self.stock_book.read().unwrap();
+        let book = &mut lock.get(&stock).unwrap().write().unwrap().order_book;
+        book.remove_where(retain_condition);
+    }
+
+    pub fn report_transactions(&self, stock: Stock) -> Vec<Transaction>{
+        let lock =  self.stock_book.read().unwrap();
+        let record = &mut lock.get(&stock).unwrap().write().unwrap();
+        record.report_transactions();
+
+        let transactions = &mut record.order_book.transaction_record;
+        if transactions.len() == 0 {return Vec::new()}
+
+        let last_second_timestamp: u64 = MTime::which_second(transactions.last().unwrap().timestamp) * GRANULARITY::SECOND as u64;
+
+        let index = transactions.iter()
+            .position(|x| x.timestamp > last_second_timestamp as i64)
+            .unwrap_or(transactions.len());
+
+        let whole_seconds = transactions.drain(0..index).collect::<Vec<Transaction>>();
+        self.tape.append(stock, &whole_seconds);
+
+        let stock_record = &mut record.history;
+        stock_record.process_transactions(&whole_seconds);
+        stock_record.compress();
+
+        whole_seconds
+    }
+
+    /// How many trades the stock has had, on the tape and waiting to go on it
+    pub fn trade_count(&self, stock: Stock) -> usize {
+        let lock =  self.stock_book.read().unwrap();
+        let record = &lock.get(&stock).unwrap().read().unwrap();
+        self.tape.len(stock) + record.order_book.transaction_record.len()
+    }
+
+    /// Every trade after the first `from`, counting the tape and then the trades of the
+    /// latest second that aren't on it yet, oldest first
+    pub fn trades_since(&self, stock: Stock, from: usize) -> Vec<Transaction> {
+        let lock =  self.stock_book.read().unwrap();
+        let record = &lock.get(&stock).unwrap().read().unwrap();
+        let on_tape = self.tape.len(stock);
+        let mut trades = self.tape.slice(stock, from.min(on_tape)..on_tape);
+        trades.extend(record.order_book.transaction_record.iter().skip(from.saturating_sub(on_tape)));
+        trades
+    }
+
+    pub fn get_order_status(&self, stock: Stock, id: u64, order_type: OrderType) -> OrderStatus { 
+        let executed = self.search_transaction_buffer(stock, id, order_type);
+        let pending = self.search_orderbook(stock, id, order_type);  
+
+        match (executed, pending) {
+            (Some(price), false) => OrderStatus::Executed { price: price },
+            (Some(_), true) => OrderStatus::PartiallyFilled,
+            (None, true) => OrderStatus::Pending,
+            _ => panic!()
+        }
+    }
+
+    fn search_transaction_buffer(&self, stock: Stock, id: u64, order_type: OrderType) -> Option<f64> {
+        let lock =  self.stock_book.read().unwrap();
+        let record = &lock.get(&stock).unwrap().read().unwrap();
+
+        let search_criteria: &dyn Fn(Transaction) -> bool;
+
+        let sell_cond = |t: Transaction| t.sell_id == Some(id);
+        let buy_cond = |t: Transaction| t.buy_id == Some(id);
+        match order_type {
+            OrderType::Sell => search_criteria = &sell_cond,
+            OrderType::Buy => search_criteria = &buy_cond
+        }
+
+        let (sum, count) = record.recent_transactions.iter()
+            .filter(|&t| search_criteria(*t))
+            .fold((0.0, 0), |(sum, count), t| (sum + t.price, count + 1));
+
+        if count > 0 {
+            let avg_price = sum / count as f64;
+            Some(avg_price)
+        } else {
+            None
+        }
+    }
+
+    fn search_orderbook(&self, stock: Stock, id: u64, order_type: OrderType) -> bool {
+        let lock =  self.stock_book.read().unwrap();
+        let record = &lock.get(&stock).unwrap().read().unwrap();
+
+        match order_type {
+            OrderType::Buy => record.order_book.is_pending_bid(id),
+            OrderType::Sell => record.order_book.is_pending_ask(id) 
+        }
+    }
+
+    pub fn pending_order_ids(&self, stock: Stock) -> HashSet<u64> {
+        let lock =  self.stock_book.read().unwrap();
+        let book = &lock.get(&stock).unwrap().read().unwrap().order_book;
+        book.pending_ids()
+    }
+
+    /// Ids of the orders in trades that haven't been reported yet, their fills are still to be routed
+    pub fn unreported_order_ids(&self, stock: Stock) -> HashSet<u64> {
+        let lock =  self.stock_book.read().unwrap();
+        let book = &lock.get(&stock).unwrap().read().unwrap().order_book;
+        book.transaction_record.iter()
+            .flat_map(|t| [t.buy_id, t.sell_id])
+            .flatten()
+            .collect()
+    }
+
+    // volatility of per-second returns over the last minute
+    pub fn get_volatility(&self, stock: Stock) -> f64 {
+        let lock =  self.stock_book.read().unwrap();
+        let record = &lock.get(&stock).unwrap().read().unwrap();
+        record.stats.minute_volatility()
+    }
 
-// ONLY FOR USAGE IN UNIT TESTS
-pub fn get_market() -> &'static RwLock<hashbrown::HashMap<Stock, RwLock<StockRecord>>> {
-    return &MARKET.stock_book;
-}
\ No newline at end of file
+    pub fn update_stats(&self, stock: Stock) {
+        let market_lock =  self.stock_book.read().unwrap();
+        let record = &mut market_lock.get(&stock).unwrap().write().unwrap();
+        record.update_stats()
+    }
+
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn place_order(&self, stock: Stock, amount: u64, order_type: OrderType, price: Option<f64>, lifetime: Option<i64>, time_in_force: TimeInForce, visibility: Visibility, id: Option<u64>){
+        let variant = match price {
+            Some(p) => OrderVariant::Limit { price: p },
+            None => OrderVariant::Market
+        };
+        self.place_variant(stock, amount, order_type, variant, lifetime, time_in_force, visibility, None, id);
+    }
+
+    /// Places an order of any variant, returning whether the book took it. Orders that make no sense
+    /// are dropped, and a post-only order that would trade without a slide to reprice by is rejected
+    #[allow(clippy::too_many_arguments)]
+    pub fn place_variant(&self, stock: Stock, amount: u64, order_type: OrderType, variant: OrderVariant, lifetime: Option<i64>, time_in_force: TimeInForce, visibility: Visibility, owner: Option<u64>, id: Option<u64>) -> bool {
+        let lock =  self.stock_book.read().unwrap();
+        let record = &mut lock.get(&stock).unwrap().write().unwrap();
+        match self.new_order(record, amount, order_type, variant, lifetime, time_in_force, visibility, owner, id) {
+            Some(order) => record.order_book.process_order(order),
+            None => false
+        }
+    }
+
+    /// Places orders that cancel each other: the first to trade takes the rest off the book. Each leg is
+    /// (side, amount, variant), the group's id comes back
+    pub fn place_oco(&self, stock: Stock, legs: &[(OrderType, u64, OrderVariant)]) -> Result<u64, String> {
+        if legs.len() < 2 {
+            return Err("an oco group needs at least two legs".to_string());
+        }
+        let lock =  self.stock_book.read().unwrap();
+        let record = &mut lock.get(&stock).unwrap().write().unwrap();
+        let mut orders = Vec::new();
+        for &(order_type, amount, variant) in legs {
+            let id = Some(self.next_order_id());
+            let order = self.new_order(record, amount, order_type, variant, None, TimeInForce::GoodTillCancel, Visibility::Displayed, None, id)
+                .ok_or_else(|| format!("invalid leg {:?} {} {:?}", order_type, amount, variant))?;
+            orders.push(order);
+        }
+        let group = OrderGroup::oco(self.next_order_id(), orders);
+        let id = group.id;
+        match record.order_book.place_group(group) {
+            true => Ok(id),
+            false => Err("the book rejected a leg".to_string())
+        }
+    }
+
+    /// An entry with a take profit limit and a stop loss on the other side, both worked for whatever
+    /// the entry fills. The first exit to trade cancels the other and the rest of the entry
+    pub fn place_bracket(&self, stock: Stock, side: OrderType, amount: u64, entry: OrderVariant, take_profit: f64, stop_loss: f64) -> Result<u64, String> {
+        let entry_price = entry.limit();
+        let (exit_side, ordered) = match side {
+            OrderType::Buy => (OrderType::Sell, stop_loss < take_profit && entry_price.is_none_or(|p| stop_loss < p && p < take_profit)),
+            OrderType::Sell => (OrderType::Buy, take_profit < stop_loss && entry_price.is_none_or(|p| take_profit < p && p < stop_loss))
+        };
+        if !ordered {
+            return Err("the entry must lie between the take profit and the stop loss".to_string());
+        }
+        let lock =  self.stock_book.read().unwrap();
+        let record = &mut lock.get(&stock).unwrap().write().unwrap();
+        let order = |order_type, variant| {
+            self.new_order(record, amount, order_type, variant, None, TimeInForce::GoodTillCancel, Visibility::Displayed, None, Some(self.next_order_id()))
+                .ok_or_else(|| format!("invalid order {:?} {} {:?}", order_type, amount, variant))
+        };
+        let entry = order(side, entry)?;
+        let take_profit = order(exit_side, OrderVariant::Limit { price: take_profit })?;
+        let stop_loss = order(exit_side, OrderVariant::Stop { stop: stop_loss })?;
+        let group = OrderGroup::bracket(self.next_order_id(), entry, take_profit, stop_loss);
+        let id = group.id;
+        match record.order_book.place_group(group) {
+            true => Ok(id),
+            false => Err("the book rejected the entry".to_string())
+        }
+    }
+
+    /// Puts a group on the book exactly as given, for replaying groups the market already accepted
+    pub fn accept_group(&self, stock: Stock, group: OrderGroup) {
+        let lock =  self.stock_book.read().unwrap();
+        let book = &mut lock.get(&stock).unwrap().write().unwrap().order_book;
+        book.place_group(group);
+    }
+
+    /// Cancels whatever is still working of a group, on whichever stock it is
+    pub fn cancel_group(&self, id: u64) -> bool {
+        let lock =  self.stock_book.read().unwrap();
+        lock.values().any(|record| record.write().unwrap().order_book.cancel_group(id))
+    }
+
+    pub fn group_status(&self, id: u64) -> Option<GroupStatus> {
+        let lock =  self.stock_book.read().unwrap();
+        lock.values().find_map(|record| record.read().unwrap().order_book.group_status(id))
+    }
+
+    // checks an order over and puts its prices on the tick, None if it makes no sense
+    #[allow(clippy::too_many_arguments)]
+    fn new_order(&self, record: &StockRecord, amount: u64, order_type: OrderType, variant: OrderVariant, lifetime: Option<i64>, time_in_force: TimeInForce, visibility: Visibility, owner: Option<u64>, id: Option<u64>) -> Option<Order> {
+        if amount <= 0 {
+            return None;
+        }
+        let positive = |p: f64| p > 0.0 && p.is_finite();
+        let valid = match variant {
+            // a limit at or below zero would let a market order drag the price negative
+            OrderVariant::Limit { price } => positive(price),
+            OrderVariant::PostOnly { price, slide } => positive(price) && slide.is_none_or(positive),
+            OrderVariant::Pegged { offset, .. } => offset.is_finite(),
+            OrderVariant::TrailingStop { trail: Trail::Offset(offset), .. } => positive(offset),
+            OrderVariant::TrailingStop { trail: Trail::Percent(percent), .. } => percent > 0.0 && percent < 1.0,
+            OrderVariant::Stop { stop } => positive(stop),
+            OrderVariant::Market => true
+        };
+        if !valid {
+            return None;
+        }
+        // only a resting order can keep anything back, and an iceberg as big as its peak is shown in full
+        let visibility = match (visibility, variant) {
+            (_, OrderVariant::Market | OrderVariant::TrailingStop { .. } | OrderVariant::Stop { .. }) => Visibility::Displayed,
+            (Visibility::Iceberg { peak: 0, .. }, _) => Visibility::Hidden,
+            (Visibility::Iceberg { peak, .. }, _) if peak >= amount => Visibility::Displayed,
+            (Visibility::Iceberg { peak, .. }, _) => Visibility::iceberg(peak),
+            (visibility, _) => visibility
+        };
+        // println!("placing order");
+        use OrderVariant::*;
+        Some(Order {
+            id: id,
+            order_type: order_type,
+            // prices go on the tick, pegs and stops are worked out by the book as it moves
+            variant: match variant {
+                Limit { price } => Limit { price: record.round_to_tick(price) },
+                PostOnly { price, slide } => PostOnly { price: record.round_to_tick(price), slide: slide },
+                Pegged { reference, offset, .. } => Pegged { reference: reference, offset: record.round_to_tick(offset), price: None },
+                TrailingStop { trail, .. } => TrailingStop { trail: trail, stop: None },
+                Stop { stop } => Stop { stop: record.round_to_tick(stop) },
+                Market => Market
+            },
+            details: OrderDetails {
+                time: self.clock.now(),
+                stock: record.order_book.stock(),
+                amount: amount,
+                lifetime_nanos: lifetime,
+                time_in_force: time_in_force,
+                visibility: visibility,
+                owner: owner
+            }
+        })
+    }
+
+    pub fn get_price(&self, stock: Stock) -> f64 {
+        let lock =  self.stock_book.read().unwrap();
+        let book = &lock.get(&stock).unwrap().read().unwrap().order_book;
+        book.price
+    }
+
+    pub fn get_stock_history(&self, stock: Stock, granularity: GRANULARITY, count: usize) -> Vec<StockHistoryDTO> {
+        let lock =  self.stock_book.read().unwrap();
+        let history = &lock.get(&stock).unwrap().read().unwrap().history._historic_data;
+
+        let requested_hist = &history[granularity_index(granularity)];
+        let n = requested_hist.len();
+
+        let subj: &[ObStat];
+        if n <= count {
+            subj = &requested_hist[..];
+        } else {
+            subj = &requested_hist[n-count..];
+        }
+
+        subj.iter().map(|x| Into::<StockHistoryDTO>::into(*x)).collect()
+    }
+
+    pub fn get_bars(&self, stock: Stock, granularity: GRANULARITY, count: usize) -> Vec<ObStat> {
+        let lock =  self.stock_book.read().unwrap();
+        let history = &lock.get(&stock).unwrap().read().unwrap().history;
+        history.recent(granularity, count)
+    }
+
+    /// Adds bars from before the stock listed to its history, the last close becomes its price
+    pub fn seed_history(&self, stock: Stock, bars: Vec<ObStat>) {
+        let lock =  self.stock_book.read().unwrap();
+        let record = &mut lock.get(&stock).unwrap().write().unwrap();
+        let Some(last) = bars.last() else {
+            return
+        };
+        record.order_book.price = last.close;
+        record.history._live_data[granularity_index(last.granularity)].extend(bars);
+        record.history.compress();
+        record.update_stats();
+    }
+
+    // ONLY FOR USAGE IN UNIT TESTS
+    pub fn stock_book(&self) -> &RwLock<hashbrown::HashMap<Stock, RwLock<StockRecord>>> {
+        &self.stock_book
+    }
+}
@@ -1,22 +1,135 @@
-use chrono::Utc;
+use std::sync::RwLock;
 
-use crate::globals::{GRANULARITY, MARKET_EPOCH};
+use chrono::{DateTime, TimeZone, Utc};
 
-pub struct MTime {}
+use crate::globals::{ACCELERATION_PARAMETER, GRANULARITY};
+
+/// A market's clock. Market time is measured in nanoseconds from when the clock was made
+pub struct MTime {
+    clock: RwLock<Clock>
+}
+
+// market time runs at `speed` times the real clock from the anchor onwards,
+// so the acceleration can change without time jumping
+struct Clock {
+    // the real time market time 0 falls on
+    epoch: i64,
+    anchor_real: i64,
+    anchor_market: i64,
+    speed: f64,
+    // set, the clock ignores the wall clock and only moves when advanced
+    virtual_time: Option<i64>,
+    // the simulated date market time 0 falls on, the epoch by default
+    simulated_start: Option<i64>
+}
+
+impl Default for MTime {
+    fn default() -> Self {
+        MTime {
+            clock: RwLock::new(Clock {
+                epoch: Utc::now().timestamp_nanos_opt().unwrap(),
+                anchor_real: 0,
+                anchor_market: 0,
+                speed: 1.0,
+                virtual_time: None,
+                simulated_start: None
+            })
+        }
+    }
+}
 
 impl MTime {
-    pub fn now() -> i64 {
-        Utc::now().timestamp_nanos_opt().unwrap() - *MARKET_EPOCH
+    pub fn now(&self) -> i64 {
+        let clock = self.clock.read().unwrap();
+        if let Some(time) = clock.virtual_time {
+            return time;
+        }
+        let real = Self::real_now(&clock);
+        clock.anchor_market + ((real - clock.anchor_real) as f64 * clock.speed) as i64
+    }
+
+    /// Detaches market time from the wall clock, from here on it stands still at `start` until advanced
+    pub fn use_virtual_clock(&self, start: i64) {
+        self.clock.write().unwrap().virtual_time = Some(start);
+    }
+
+    pub fn is_virtual(&self) -> bool {
+        self.clock.read().unwrap().virtual_time.is_some()
+    }
+
+    pub fn advance(&self, nanos: i64) {
+        let mut clock = self.clock.write().unwrap();
+        if let Some(time) = clock.virtual_time.as_mut() {
+            *time += nanos;
+        }
+    }
+
+    pub fn set_simulated_start(&self, start: DateTime<Utc>) {
+        self.clock.write().unwrap().simulated_start = start.timestamp_nanos_opt();
+    }
+
+    pub fn simulated_start(&self) -> Option<DateTime<Utc>> {
+        self.clock.read().unwrap().simulated_start.map(|start| Utc.timestamp_nanos(start))
+    }
+
+    /// Moves market time to `time`, carrying on from there at the current speed
+    pub fn set_now(&self, time: i64) {
+        let mut clock = self.clock.write().unwrap();
+        match clock.virtual_time.as_mut() {
+            Some(virtual_time) => *virtual_time = time,
+            None => {
+                clock.anchor_real = Self::real_now(&clock);
+                clock.anchor_market = time;
+            }
+        }
+    }
+
+    fn real_now(clock: &Clock) -> i64 {
+        Utc::now().timestamp_nanos_opt().unwrap() - clock.epoch
+    }
+
+    /// Simulated seconds per real second, ACCELERATION_PARAMETER unless changed
+    pub fn acceleration(&self) -> f64 {
+        self.clock.read().unwrap().speed * ACCELERATION_PARAMETER
+    }
+
+    pub fn set_acceleration(&self, acceleration: f64) {
+        let now = self.now();
+        let mut clock = self.clock.write().unwrap();
+        clock.anchor_real = Self::real_now(&clock);
+        clock.anchor_market = now;
+        clock.speed = acceleration / ACCELERATION_PARAMETER;
     }
 
     pub fn which_second(timestamp: i64) -> u64 {
         (timestamp / GRANULARITY::SECOND as i64) as u64
     }
 
-    pub fn current_second() -> u64 {
-        let now = Self::now();
+    pub fn current_second(&self) -> u64 {
+        let now = self.now();
         (now / GRANULARITY::SECOND as i64) as u64
     }
 
-}
+    // wall clock time in the simulated world, the market opens at its epoch (or the
+    // simulated start) and every market nanosecond after that is worth ACCELERATION_PARAMETER simulated ones
+    pub fn simulated_datetime(&self, timestamp: i64) -> DateTime<Utc> {
+        let simulated_nanos = (timestamp as f64 * ACCELERATION_PARAMETER) as i64;
+        let clock = self.clock.read().unwrap();
+        let start = clock.simulated_start.unwrap_or(clock.epoch);
+        Utc.timestamp_nanos(start + simulated_nanos)
+    }
 
+    /// The market time a simulated date and time falls on, the inverse of simulated_datetime
+    pub fn from_simulated_datetime(&self, time: DateTime<Utc>) -> i64 {
+        let clock = self.clock.read().unwrap();
+        let start = clock.simulated_start.unwrap_or(clock.epoch);
+        // nanoseconds only reach from 1677 to 2262, anything further goes to the end it is beyond
+        let simulated_nanos = match time.timestamp_nanos_opt() {
+            Some(nanos) => nanos.saturating_sub(start),
+            None if time.timestamp() < 0 => i64::MIN,
+            None => i64::MAX
+        };
+        (simulated_nanos as f64 / ACCELERATION_PARAMETER) as i64
+    }
+
+}
@@ -1 +1,2 @@
-pub mod market_time;
\ No newline at end of file
+pub mod market_time;
+pub mod session;
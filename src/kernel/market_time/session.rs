@@ -0,0 +1,78 @@
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum SessionPhase {
+    PreOpen,
+    OpeningAuction,
+    Continuous,
+    ClosingAuction,
+    Closed
+}
+
+impl SessionPhase {
+    pub fn is_auction(&self) -> bool {
+        matches!(self, SessionPhase::OpeningAuction | SessionPhase::ClosingAuction)
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct TradingCalendar {
+    pub trade_weekends: bool,
+    pub holidays: Vec<NaiveDate>
+}
+
+impl TradingCalendar {
+    pub fn is_trading_day(&self, date: NaiveDate) -> bool {
+        let weekend = matches!(date.weekday(), Weekday::Sat | Weekday::Sun);
+        (self.trade_weekends || !weekend) && !self.holidays.contains(&date)
+    }
+}
+
+/// Times of day (in simulated UTC) at which each phase of the trading day begins.
+/// Anything before `pre_open` or after `close` is Closed, as is any non trading day.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SessionSchedule {
+    pub pre_open: NaiveTime,
+    pub opening_auction: NaiveTime,
+    pub continuous: NaiveTime,
+    pub closing_auction: NaiveTime,
+    pub close: NaiveTime,
+    pub calendar: TradingCalendar
+}
+
+impl Default for SessionSchedule {
+    fn default() -> Self {
+        let hm = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        SessionSchedule {
+            pre_open: hm(8, 0),
+            opening_auction: hm(9, 25),
+            continuous: hm(9, 30),
+            closing_auction: hm(16, 0),
+            close: hm(16, 5),
+            calendar: TradingCalendar::default()
+        }
+    }
+}
+
+impl SessionSchedule {
+    pub fn phase_at(&self, time: DateTime<Utc>) -> SessionPhase {
+        use SessionPhase::*;
+        if !self.calendar.is_trading_day(time.date_naive()) {
+            return Closed;
+        }
+
+        let t = time.time();
+        if t < self.pre_open || t >= self.close {
+            Closed
+        } else if t < self.opening_auction {
+            PreOpen
+        } else if t < self.continuous {
+            OpeningAuction
+        } else if t < self.closing_auction {
+            Continuous
+        } else {
+            ClosingAuction
+        }
+    }
+}
@@ -1,6 +1,17 @@
 pub mod order_book;
 pub mod agents;
 
-mod market_time;
+pub mod market_time;
 
-pub mod market;
\ No newline at end of file
+pub mod market;
+
+pub mod events;
+pub mod journal;
+pub mod snapshot;
+pub mod replay;
+pub mod history_import;
+pub mod tape;
+pub mod export;
+pub mod gym;
+
+pub mod backtest;
\ No newline at end of file
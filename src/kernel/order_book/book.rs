@@ -1,105 +1,729 @@
 use std::cmp;
-use std::collections::BinaryHeap;
-use std::sync::RwLock;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
 
 use super::record::*;
+use super::circuit_breaker::*;
+use super::group::*;
 
+use crate::kernel::journal::{Journal, JournalEntry};
 use crate::kernel::market_time::market_time::MTime;
 use crate::classes::shared::{order::*, transaction::*};
 
+/// Finished groups a book still answers for, older ones are forgotten as new groups come in
+pub const FINISHED_GROUPS_KEPT: usize = 1_000;
+
+// (price, volume) of a price level
+pub type Level = (f64, u64);
+
+/// What happens when the best bid and ask would cross but share an owner
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SelfTradePrevention {
+    // the order that arrived last is cancelled, the one resting stays
+    CancelNewest,
+    CancelOldest,
+    CancelBoth,
+    // both are reduced by the smaller of the two without trading, which takes it off the book
+    Decrement
+}
+
+/// What pegs and stops last caught up with, so matching only moves them when something they follow has
+#[derive(Debug, Clone)]
+struct Conditional {
+    // at least as many pegs as rest on the book, counted again whenever they are moved
+    pegged: usize,
+    // orders went on or came off the book since, so the quotes may have moved
+    book_changed: bool,
+    // the last price the stops were trailed behind
+    price: Option<f64>
+}
+
+impl Default for Conditional {
+    // a book read back from a snapshot counts its pegs on the first pass
+    fn default() -> Self {
+        Conditional { pegged: usize::MAX, book_changed: true, price: None }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct OrderBook {
     pub transaction_record: Vec<Transaction>,
     pub stats: ObStat,
     pub price: f64,
+    pub breaker: CircuitBreaker,
+    // None lets orders of the same owner trade with each other
+    #[serde(default)]
+    pub self_trade_prevention: Option<SelfTradePrevention>,
+    // prices are kept to a multiple of this, None leaves them as they come
+    #[serde(default)]
+    pub tick_size: Option<f64>,
     stock: Stock,
     _bid: RwLock<BinaryHeap<Order>>,
     _ask: RwLock<BinaryHeap<Order>>,
+    // orders off the book until the price lets them on: stops not yet hit, and pegs with nothing to peg to
+    #[serde(default)]
+    _parked: RwLock<Vec<Order>>,
+    // oco and bracket groups by id, and the group each of their orders belongs to
+    #[serde(default)]
+    groups: BTreeMap<u64, OrderGroup>,
+    #[serde(default)]
+    grouped: HashMap<u64, u64>,
+    // groups are pruned once there are this many, so the book is only scanned every so often
+    #[serde(skip)]
+    prune_groups_at: usize,
+    #[serde(skip)]
+    conditional: Conditional,
+    // the market's clock and journal, a book read back from a snapshot is attached to them again
+    #[serde(skip)]
+    clock: Arc<MTime>,
+    #[serde(skip)]
+    journal: Arc<Journal>
 }
 
 impl OrderBook {
-    pub fn new(stock: Stock) -> Self {
+    pub fn new(stock: Stock, clock: Arc<MTime>, journal: Arc<Journal>) -> Self {
         let order_book = OrderBook {
             transaction_record: Vec::<Transaction>::new(),
             stats: ObStat::default(),
             price: 0.0,
+            breaker: CircuitBreaker::default(),
+            self_trade_prevention: None,
+            tick_size: None,
             stock: stock, 
             _bid: RwLock::new(BinaryHeap::<Order>::new()), 
             _ask: RwLock::new(BinaryHeap::<Order>::new()),
+            _parked: RwLock::new(Vec::new()),
+            groups: BTreeMap::new(),
+            grouped: HashMap::new(),
+            prune_groups_at: FINISHED_GROUPS_KEPT,
+            conditional: Conditional { pegged: 0, book_changed: false, price: None },
+            clock: clock,
+            journal: journal
         };
 
         order_book
     }
+
+    pub fn attach(&mut self, clock: Arc<MTime>, journal: Arc<Journal>) {
+        self.clock = clock;
+        self.journal = journal;
+    }
     
-    pub fn process_order(&mut self, order: Order){
+    /// Puts an order on the book, or aside until it can go on. Returns false for a post-only order that
+    /// would have traded and had no slide to reprice by
+    pub fn process_order(&mut self, order: Order) -> bool {
         // println!("Processing order");
-        match order.order_type {
-            OrderType::Buy => { self._bid.write().unwrap().push(order) },
-            OrderType::Sell => { self._ask.write().unwrap().push(order) }
+        let Some(order) = self.admit(order) else {
+            return false
+        };
+        self.journal.record(|| JournalEntry::OrderAccepted { order: order.clone() });
+        self.conditional.book_changed = true;
+        if matches!(order.variant, OrderVariant::Pegged { .. }) {
+            self.conditional.pegged = self.conditional.pegged.saturating_add(1);
         }
+        Self::rest(&mut self._bid.write().unwrap(), &mut self._ask.write().unwrap(), &mut self._parked.write().unwrap(), order);
         // println!("order has been placed");
+        true
     }
 
-    pub fn find_trade(&mut self) {
-        use OrderVariant::*;
-        loop {
-            // println!("Finding trade");
+    /// Places a group of linked orders, all of them or none. The legs of an oco group all go on the book,
+    /// a bracket's exits wait for its entry to fill
+    pub fn place_group(&mut self, mut group: OrderGroup) -> bool {
+        if group.legs.iter().any(|leg| leg.order.id.is_none()) || self.groups.contains_key(&group.id) {
+            return false;
+        }
+        for leg in group.legs.iter_mut().filter(|leg| leg.role == LegRole::Leg || leg.role == LegRole::Entry) {
+            let Some(order) = self.admit(leg.order.clone()) else {
+                return false
+            };
+            leg.order = order;
+            leg.active = true;
+        }
+
+        self.journal.record(|| JournalEntry::GroupAccepted { stock: self.stock, group: group.clone() });
+        self.conditional.book_changed = true;
+        {
             let mut bid = self._bid.write().unwrap();
             let mut ask = self._ask.write().unwrap();
+            let mut parked = self._parked.write().unwrap();
+            for leg in group.legs.iter().filter(|leg| leg.active) {
+                Self::rest(&mut bid, &mut ask, &mut parked, leg.order.clone());
+            }
+        }
+        for id in group.ids() {
+            self.grouped.insert(id, group.id);
+        }
+        self.groups.insert(group.id, group);
+        if self.groups.len() > self.prune_groups_at {
+            self.prune_groups();
+        }
+        true
+    }
 
-            if bid.is_empty() {
-                return;
+    // forgets the oldest finished groups with nothing left on the book beyond the last
+    // FINISHED_GROUPS_KEPT, group ids come from the order ids so the oldest have the lowest
+    fn prune_groups(&mut self) {
+        let pending = self.pending_ids();
+        let finished: Vec<u64> = self.groups.values()
+            .filter(|group| group.done && !group.ids().any(|id| pending.contains(&id)))
+            .map(|group| group.id)
+            .collect();
+        for id in finished.iter().take(finished.len().saturating_sub(FINISHED_GROUPS_KEPT)) {
+            let group = self.groups.remove(id).unwrap();
+            for order_id in group.ids() {
+                self.grouped.remove(&order_id);
             }
-            if ask.is_empty() {
-                return;
+        }
+        self.prune_groups_at = self.groups.len() + FINISHED_GROUPS_KEPT;
+    }
+
+    /// Takes every order of a group that is still working off the book
+    pub fn cancel_group(&mut self, id: u64) -> bool {
+        let Some(group) = self.groups.get_mut(&id).filter(|group| !group.done) else {
+            return false
+        };
+        group.done = true;
+        self.conditional.book_changed = true;
+        let mut bid = self._bid.write().unwrap();
+        let mut ask = self._ask.write().unwrap();
+        let mut parked = self._parked.write().unwrap();
+        for order_id in group.ids() {
+            Self::take(&mut bid, &mut ask, &mut parked, order_id);
+        }
+        self.journal.record(|| JournalEntry::GroupCancel { stock: self.stock, group_id: id });
+        true
+    }
+
+    pub fn group_status(&self, id: u64) -> Option<GroupStatus> {
+        let pending = self.pending_ids();
+        self.groups.get(&id).map(|group| group.status(|order_id| pending.contains(&order_id)))
+    }
+
+    // readies an order for the book: a post-only order is checked against the other side, and pegs and
+    // stops get their first prices. None if it is rejected
+    fn admit(&self, mut order: Order) -> Option<Order> {
+        let bid = self._bid.read().unwrap();
+        let ask = self._ask.read().unwrap();
+        match &mut order.variant {
+            OrderVariant::PostOnly { price, slide } => {
+                // a resting market order on the other side would be the one taking, only prices count
+                let (other, crosses) = match order.order_type {
+                    OrderType::Buy => (Self::best_price(&ask, OrderType::Sell, |_| true), 1.0),
+                    OrderType::Sell => (Self::best_price(&bid, OrderType::Buy, |_| true), -1.0)
+                };
+                if let Some(other) = other.filter(|other| (*price - other) * crosses >= 0.0) {
+                    *price = other - (*slide)? * crosses;
+                    if *price <= 0.0 {
+                        return None;
+                    }
+                }
+            },
+            OrderVariant::Pegged { .. } => {
+                let (best_bid, best_ask) = Self::quotes(&bid, &ask);
+                order.repeg(best_bid, best_ask, self.tick_size, self.clock.now());
+            },
+            OrderVariant::TrailingStop { .. } | OrderVariant::Stop { .. } => {
+                order.trail(self.price);
+            },
+            OrderVariant::Market | OrderVariant::Limit { .. } => {}
+        }
+        Some(order)
+    }
+
+    // stops and pegs without a price wait off the book
+    fn rest(bid: &mut BinaryHeap<Order>, ask: &mut BinaryHeap<Order>, parked: &mut Vec<Order>, order: Order) {
+        match (order.order_type, order.variant) {
+            (_, OrderVariant::TrailingStop { .. } | OrderVariant::Stop { .. } | OrderVariant::Pegged { price: None, .. }) => {
+                parked.push(order)
+            },
+            (OrderType::Buy, _) => bid.push(order),
+            (OrderType::Sell, _) => ask.push(order)
+        }
+    }
+
+    // takes an order off the book, or from among the parked ones
+    fn take(bid: &mut BinaryHeap<Order>, ask: &mut BinaryHeap<Order>, parked: &mut Vec<Order>, id: u64) -> Option<Order> {
+        for side in [bid, ask] {
+            if side.iter().any(|o| o.id == Some(id)) {
+                let mut taken = None;
+                side.retain(|o| {
+                    if o.id == Some(id) {
+                        taken = Some(o.clone());
+                    }
+                    o.id != Some(id)
+                });
+                return taken;
             }
+        }
+        parked.iter().position(|o| o.id == Some(id)).map(|i| parked.remove(i))
+    }
 
-            let mut buy = bid.pop().unwrap();
-            let mut sell = ask.pop().unwrap();
-            match (&buy.variant, &sell.variant) {
-                (Limit { price: bid_price}, Limit { price: ask_price }) 
-                    => {
-                        if bid_price < ask_price {
-                            bid.push(buy);
-                            ask.push(sell);
-                            return;
-                        }
-                        self.price = *ask_price;
-                        // println!("Sold at {}", self.price);
+    /// A trade of a grouped order: a bracket entry's fills put its exits on the book (or add to them),
+    /// any other leg trading cancels the rest of its group there and then
+    fn group_fills(groups: &mut BTreeMap<u64, OrderGroup>, grouped: &HashMap<u64, u64>, bid: &mut BinaryHeap<Order>, ask: &mut BinaryHeap<Order>, parked: &mut Vec<Order>, trade: &Transaction, now: i64) {
+        for id in [trade.buy_id, trade.sell_id].into_iter().flatten() {
+            let Some(group) = grouped.get(&id).and_then(|group_id| groups.get_mut(group_id)) else {
+                continue
+            };
+            let leg = group.leg_mut(id).unwrap();
+            leg.filled += trade.volume;
+            let (role, filled) = (leg.role, leg.filled);
+            if group.done {
+                continue;
+            }
+            if role != LegRole::Entry {
+                group.done = true;
+                for other in group.legs.iter().filter(|other| other.active && other.order.id != Some(id)) {
+                    Self::take(bid, ask, parked, other.order.id.unwrap());
+                }
+                continue;
+            }
+
+            for exit in group.legs.iter_mut().filter(|exit| exit.role != LegRole::Entry) {
+                // resized off the book and put back with its time priority, unless it has gone since
+                let mut order = if exit.active {
+                    match Self::take(bid, ask, parked, exit.order.id.unwrap()) {
+                        Some(order) => order,
+                        None => continue
+                    }
+                } else {
+                    exit.active = true;
+                    exit.order.details.time = now;
+                    exit.order.clone()
+                };
+                exit.order.details.amount = filled;
+                order.details.amount = filled - exit.filled;
+                Self::rest(bid, ask, parked, order);
+            }
+        }
+    }
+
+    pub fn find_trade(&mut self) {
+        while self.match_next() {}
+    }
+
+    /// Executes the best bid against the best ask if they cross, returning whether anything traded
+    pub fn match_next(&mut self) -> bool {
+        if self.breaker.is_halted() {
+            return false;
+        }
+        // println!("Finding trade");
+        let mut bid = self._bid.write().unwrap();
+        let mut ask = self._ask.write().unwrap();
+        let mut parked = self._parked.write().unwrap();
+        // pegs and stops catch up with the book before anything matches
+        Self::update_conditional(&mut bid, &mut ask, &mut parked, &mut self.conditional, self.price, self.tick_size, self.clock.now());
+        // an owner's orders crossing each other are dealt with first, each step is journaled for replay
+        if let Some(mode) = self.self_trade_prevention {
+            while Self::prevent_self_trade(&mut bid, &mut ask, mode, self.clock.now()) {
+                self.conditional.book_changed = true;
+                self.journal.record(|| JournalEntry::SelfTrade { stock: self.stock, mode: mode });
+            }
+        }
 
+        // look before taking anything off, a book that doesn't trade is left exactly as it was
+        let (Some(buy), Some(sell)) = (bid.peek(), ask.peek()) else {
+            return false
+        };
+        let trade_price = match (buy.variant.limit(), sell.variant.limit()) {
+            (Some(bid_price), Some(ask_price)) 
+                => {
+                    if bid_price < ask_price {
+                        return false;
                     }
-                (Market, Limit { price }) |
-                (Limit { price }, Market ) => {
-                    self.price = *price;
+                    ask_price
                 }
-                _ => { 
-                    // let market_price = self.price;
+            (None, Some(price)) |
+            (Some(price), None) => price,
+            _ => self.price
+        };
+
+        // the trade would take the price outside its band, halt instead of executing
+        if let Some(reason) = self.breaker.breach(trade_price) {
+            self.breaker.trip(reason, self.clock.now());
+            self.journal.record(|| JournalEntry::Halt { stock: self.stock, reason: reason });
+            return false;
+        }
+        let mut buy = bid.pop().unwrap();
+        let mut sell = ask.pop().unwrap();
+        self.price = trade_price;
+        self.breaker.record(trade_price, self.clock.now());
+        // an iceberg only trades what it shows, the rest comes round again as the book keeps crossing
+        let trade_size = cmp::min(buy.tradable(), sell.tradable());
+        let buy_id = buy.id;
+        let sell_id = sell.id;
+
+        let now = self.clock.now();
+        buy.fill(trade_size, now);
+        sell.fill(trade_size, now);
+        // the quotes only move when an order leaves the book or a group's orders come and go
+        let grouped = [buy_id, sell_id].iter().flatten().any(|id| self.grouped.contains_key(id));
+        if buy.details.amount == 0 || sell.details.amount == 0 || grouped {
+            self.conditional.book_changed = true;
+        }
+        if buy.details.amount > 0 {
+            bid.push(buy)
+        }
+        if sell.details.amount > 0 {
+            ask.push(sell);
+        }
+
+        let trade = Transaction {
+            transaction_id: None,
+            buy_id: buy_id,
+            sell_id: sell_id,
+            price: self.price,
+            volume: trade_size,
+            timestamp: self.clock.now(),
+        };
+        Self::group_fills(&mut self.groups, &self.grouped, &mut bid, &mut ask, &mut parked, &trade, now);
+        self.transaction_record.push(trade);
+        self.journal_trade();
+        true
+    }
+
+    /// Applies `mode` once to the best bid and ask if they would cross and share an owner, for replaying
+    /// a step the journal recorded. Returns whether they did
+    pub fn self_trade(&mut self, mode: SelfTradePrevention) -> bool {
+        let mut bid = self._bid.write().unwrap();
+        let mut ask = self._ask.write().unwrap();
+        let mut parked = self._parked.write().unwrap();
+        Self::update_conditional(&mut bid, &mut ask, &mut parked, &mut self.conditional, self.price, self.tick_size, self.clock.now());
+        let prevented = Self::prevent_self_trade(&mut bid, &mut ask, mode, self.clock.now());
+        self.conditional.book_changed |= prevented;
+        prevented
+    }
+
+    fn prevent_self_trade(bid: &mut BinaryHeap<Order>, ask: &mut BinaryHeap<Order>, mode: SelfTradePrevention, now: i64) -> bool {
+        let (Some(buy), Some(sell)) = (bid.peek(), ask.peek()) else {
+            return false
+        };
+        let crosses = !matches!((buy.variant.limit(), sell.variant.limit()), (Some(bid_price), Some(ask_price)) if bid_price < ask_price);
+        if !crosses || buy.details.owner.is_none() || buy.details.owner != sell.details.owner {
+            return false;
+        }
+
+        let mut buy = bid.pop().unwrap();
+        let mut sell = ask.pop().unwrap();
+        // ids break a tie, a later order got a later one
+        let buy_newer = (buy.details.time, buy.id) > (sell.details.time, sell.id);
+        let (keep_buy, keep_sell) = match mode {
+            SelfTradePrevention::CancelNewest => (!buy_newer, buy_newer),
+            SelfTradePrevention::CancelOldest => (buy_newer, !buy_newer),
+            SelfTradePrevention::CancelBoth => (false, false),
+            SelfTradePrevention::Decrement => {
+                // an iceberg goes down by what it shows, the book comes round again for the rest
+                let size = cmp::min(buy.tradable(), sell.tradable());
+                buy.fill(size, now);
+                sell.fill(size, now);
+                (buy.details.amount > 0, sell.details.amount > 0)
+            }
+        };
+        if keep_buy {
+            bid.push(buy);
+        }
+        if keep_sell {
+            ask.push(sell);
+        }
+        true
+    }
+
+    /// Single price auction, trades everything that can cross at the price which maximises
+    /// executed volume. Ties go to the smallest imbalance, then to the price closest to the last trade.
+    /// Returns the equilibrium price, or None if nothing crosses.
+    pub fn uncross(&mut self) -> Option<f64> {
+        self.uncross_with(self.self_trade_prevention).map(|(price, _)| price)
+    }
+
+    /// The auction under a given self-trade prevention, for replaying one the journal recorded.
+    /// Returns the equilibrium price and how many self-trade steps were taken on the way.
+    pub fn uncross_with(&mut self, mode: Option<SelfTradePrevention>) -> Option<(f64, usize)> {
+        if self.breaker.is_halted() {
+            return None;
+        }
+        let price = self.equilibrium_price()?;
+        self.journal.record(|| JournalEntry::Uncross { stock: self.stock, self_trade_prevention: mode });
+
+        let mut bid = self._bid.write().unwrap();
+        let mut ask = self._ask.write().unwrap();
+        let mut parked = self._parked.write().unwrap();
+        let mut self_trades = 0;
+        loop {
+            match (bid.peek(), ask.peek()) {
+                (Some(buy), Some(sell)) if Self::will_trade_at(buy, price) && Self::will_trade_at(sell, price) => {},
+                _ => break
+            }
+            // as in continuous trading, an owner's orders don't trade with each other
+            if let Some(mode) = mode {
+                if Self::prevent_self_trade(&mut bid, &mut ask, mode, self.clock.now()) {
+                    self.journal.record(|| JournalEntry::SelfTrade { stock: self.stock, mode: mode });
+                    self_trades += 1;
+                    continue;
                 }
             }
-            let trade_size = cmp::min(buy.details.amount, sell.details.amount);
+
+            let mut buy = bid.pop().unwrap();
+            let mut sell = ask.pop().unwrap();
+            let trade_size = cmp::min(buy.tradable(), sell.tradable());
             let buy_id = buy.id;
             let sell_id = sell.id;
 
-            if buy.details.amount > trade_size {
-                buy.details.amount -= trade_size;
+            let now = self.clock.now();
+            buy.fill(trade_size, now);
+            sell.fill(trade_size, now);
+            if buy.details.amount > 0 {
                 bid.push(buy)
-            } else if sell.details.amount > trade_size {
-                sell.details.amount -= trade_size;
+            }
+            if sell.details.amount > 0 {
                 ask.push(sell);
             }
 
-            self.transaction_record.push(Transaction {
+            let trade = Transaction {
                 transaction_id: None,
                 buy_id: buy_id,
                 sell_id: sell_id,
-                price: self.price,
+                price: price,
                 volume: trade_size,
-                timestamp: MTime::now(),
+                timestamp: self.clock.now(),
+            };
+            Self::group_fills(&mut self.groups, &self.grouped, &mut bid, &mut ask, &mut parked, &trade, now);
+            self.transaction_record.push(trade);
+            self.journal_trade();
+        }
+
+        self.price = price;
+        self.breaker.reset(price, self.clock.now());
+        self.conditional.book_changed = true;
+        Some((price, self_trades))
+    }
+
+    // a copy of the book as it stands, for snapshots
+    pub fn duplicate(&self) -> Self {
+        OrderBook {
+            transaction_record: self.transaction_record.clone(),
+            stats: self.stats,
+            price: self.price,
+            breaker: self.breaker.clone(),
+            self_trade_prevention: self.self_trade_prevention,
+            tick_size: self.tick_size,
+            stock: self.stock,
+            _bid: RwLock::new(self._bid.read().unwrap().clone()),
+            _ask: RwLock::new(self._ask.read().unwrap().clone()),
+            _parked: RwLock::new(self._parked.read().unwrap().clone()),
+            groups: self.groups.clone(),
+            grouped: self.grouped.clone(),
+            prune_groups_at: self.prune_groups_at,
+            conditional: self.conditional.clone(),
+            clock: self.clock.clone(),
+            journal: self.journal.clone()
+        }
+    }
+
+    pub fn stock(&self) -> Stock {
+        self.stock
+    }
+
+    pub fn halt(&mut self, reason: HaltReason) {
+        self.breaker.trip(reason, self.clock.now());
+        self.journal.record(|| JournalEntry::Halt { stock: self.stock, reason: reason });
+    }
+
+    fn journal_trade(&self) {
+        let transaction = *self.transaction_record.last().unwrap();
+        self.journal.record(|| JournalEntry::Trade { stock: self.stock, transaction: transaction });
+    }
+
+    fn journal_expiry(&self, expired: Vec<Order>) {
+        for order in expired {
+            self.journal.record(|| JournalEntry::Expiry {
+                stock: self.stock,
+                order_id: order.id,
+                order_type: order.order_type,
+                amount: order.details.amount,
+                variant: order.variant,
+                placed: order.details.time
             });
         }
     }
 
+    /// Lifts a halt, orders that built up while halted go through a reopening auction
+    pub fn resume(&mut self) -> Option<f64> {
+        self.breaker.halt = None;
+        self.uncross()
+    }
+
+    /// Aggregated (price, volume) levels of resting limit orders, best first. Only what the orders
+    /// display is counted, hidden orders and iceberg reserves stay off it
+    pub fn depth(&self, levels: usize) -> (Vec<Level>, Vec<Level>) {
+        (
+            Self::aggregate_levels(&self._bid.read().unwrap(), levels),
+            Self::aggregate_levels(&self._ask.read().unwrap(), levels)
+        )
+    }
+
+    /// The best displayed bid and ask, what the top of `depth` would show
+    pub fn best_quotes(&self) -> (Option<f64>, Option<f64>) {
+        let displayed = |o: &Order| o.displayed() > 0;
+        (
+            Self::best_price(&self._bid.read().unwrap(), OrderType::Buy, displayed),
+            Self::best_price(&self._ask.read().unwrap(), OrderType::Sell, displayed)
+        )
+    }
+
+    fn aggregate_levels(orders: &BinaryHeap<Order>, levels: usize) -> Vec<Level> {
+        let mut limits: Vec<&Order> = orders.iter()
+            .filter(|o| o.variant.limit().is_some() && o.displayed() > 0)
+            .collect();
+        limits.sort_by(|a, b| b.cmp(a));
+
+        let mut depth: Vec<Level> = Vec::new();
+        for order in limits {
+            let Some(price) = order.variant.limit() else { continue };
+            match depth.last_mut() {
+                Some((level, volume)) if *level == price => *volume += order.displayed(),
+                _ => {
+                    if depth.len() == levels {
+                        break;
+                    }
+                    depth.push((price, order.displayed()))
+                }
+            }
+        }
+        depth
+    }
+
+    fn equilibrium_price(&self) -> Option<f64> {
+        let bid = self._bid.read().unwrap();
+        let ask = self._ask.read().unwrap();
+
+        let mut candidates: Vec<f64> = bid.iter().chain(ask.iter())
+            .filter_map(|o| o.variant.limit())
+            .collect();
+        // only market orders on the book, uncross them at the last traded price
+        if candidates.is_empty() {
+            candidates.push(self.price);
+        }
+
+        let volume_at = |orders: &BinaryHeap<Order>, price: f64| -> u64 {
+            orders.iter()
+                .filter(|o| Self::will_trade_at(o, price))
+                .map(|o| o.details.amount)
+                .sum()
+        };
+
+        candidates.into_iter()
+            .map(|p| {
+                let demand = volume_at(&bid, p);
+                let supply = volume_at(&ask, p);
+                (p, cmp::min(demand, supply), demand.abs_diff(supply))
+            })
+            .filter(|(_, volume, _)| *volume > 0)
+            .min_by(|(p1, v1, i1), (p2, v2, i2)| {
+                v2.cmp(v1)
+                    .then(i1.cmp(i2))
+                    .then((p1 - self.price).abs().total_cmp(&(p2 - self.price).abs()))
+            })
+            .map(|(p, _, _)| p)
+    }
+
+    fn will_trade_at(order: &Order, price: f64) -> bool {
+        match (order.order_type, order.variant.limit()) {
+            (_, None) => true,
+            (OrderType::Buy, Some(limit)) => limit >= price,
+            (OrderType::Sell, Some(limit)) => limit <= price
+        }
+    }
+
+    // the best price on a side among the orders that pass the filter
+    fn best_price(orders: &BinaryHeap<Order>, order_type: OrderType, include: impl Fn(&Order) -> bool) -> Option<f64> {
+        let prices = orders.iter()
+            .filter(|o| include(o))
+            .filter_map(|o| o.variant.limit());
+        match order_type {
+            OrderType::Buy => prices.reduce(f64::max),
+            OrderType::Sell => prices.reduce(f64::min)
+        }
+    }
+
+    // the best displayed bid and ask pegs follow, leaving pegs out so they never follow each other
+    fn quotes(bid: &BinaryHeap<Order>, ask: &BinaryHeap<Order>) -> (Option<f64>, Option<f64>) {
+        let quoted = |o: &Order| !matches!(o.variant, OrderVariant::Pegged { .. }) && o.displayed() > 0;
+        (Self::best_price(bid, OrderType::Buy, quoted), Self::best_price(ask, OrderType::Sell, quoted))
+    }
+
+    /// Moves pegged orders to where their references are now and trails the stops behind the last price.
+    /// A stop that is hit goes on the book as a market order, a peg that finds a price goes on at it.
+    /// Nothing is looked at unless the book or the price has changed since the last time
+    fn update_conditional(bid: &mut BinaryHeap<Order>, ask: &mut BinaryHeap<Order>, parked: &mut Vec<Order>, conditional: &mut Conditional, price: f64, tick_size: Option<f64>, now: i64) {
+        let quotes_moved = conditional.book_changed;
+        let price_moved = conditional.price != Some(price);
+        conditional.book_changed = false;
+        conditional.price = Some(price);
+        let repeg = quotes_moved && conditional.pegged > 0;
+        if !repeg && (parked.is_empty() || !(quotes_moved || price_moved)) {
+            return;
+        }
+
+        let is_pegged = |o: &Order| matches!(o.variant, OrderVariant::Pegged { .. });
+        let (best_bid, best_ask) = if repeg || parked.iter().any(is_pegged) {
+            Self::quotes(bid, ask)
+        } else {
+            (None, None)
+        };
+        if repeg {
+            conditional.pegged = 0;
+            for side in [&mut *bid, &mut *ask] {
+                let pegged = side.iter().filter(|o| is_pegged(o)).count();
+                if pegged > 0 {
+                    let mut orders = std::mem::take(side).into_vec();
+                    for order in orders.iter_mut() {
+                        order.repeg(best_bid, best_ask, tick_size, now);
+                    }
+                    *side = BinaryHeap::from(orders);
+                }
+                conditional.pegged += pegged;
+            }
+        }
+
+        for mut order in std::mem::take(parked) {
+            let ready = match order.variant {
+                OrderVariant::TrailingStop { .. } | OrderVariant::Stop { .. } => {
+                    let hit = order.trail(price);
+                    if hit {
+                        order.variant = OrderVariant::Market;
+                        order.details.time = now;
+                    }
+                    hit
+                },
+                _ => {
+                    order.repeg(best_bid, best_ask, tick_size, now);
+                    let ready = order.variant.limit().is_some();
+                    if ready {
+                        conditional.pegged = conditional.pegged.saturating_add(1);
+                    }
+                    ready
+                }
+            };
+            match (ready, order.order_type) {
+                (false, _) => parked.push(order),
+                (true, OrderType::Buy) => bid.push(order),
+                (true, OrderType::Sell) => ask.push(order)
+            }
+        }
+    }
+
+    pub fn expire_day_orders(&mut self) {
+        let retain_condition = |o: &Order| o.details.time_in_force != TimeInForce::Day;
+
+        let expired = self.remove_where(retain_condition);
+        self.journal_expiry(expired);
+    }
+
     pub fn clean_book(&mut self){
-        let now = chrono::Utc::now().timestamp_nanos_opt().unwrap();
+        let now = self.clock.now();
         let retain_condition = |o: &Order| match o.details.lifetime_nanos {
             Some(lifetime) => {
                 lifetime + o.details.time > now
@@ -107,18 +731,74 @@ impl OrderBook {
             None => true
         };
 
-        self._bid.write().unwrap().retain(retain_condition);
-        self._ask.write().unwrap().retain(retain_condition);
+        let expired = self.remove_where(retain_condition);
+        self.journal_expiry(expired);
+    }
+
+    /// Drops the orders on both sides that fail the condition, bids first, handing them back when journaling.
+    /// Nothing is journaled here, that is up to the caller.
+    pub fn remove_where(&mut self, mut retain_condition: impl FnMut(&Order) -> bool) -> Vec<Order> {
+        self.conditional.book_changed = true;
+        let mut removed = Vec::new();
+        let journaling = self.journal.is_open();
+        for side in [&self._bid, &self._ask] {
+            side.write().unwrap().retain(|o| {
+                let keep = retain_condition(o);
+                if !keep && journaling {
+                    removed.push(o.clone());
+                }
+                keep
+            });
+        }
+        self._parked.write().unwrap().retain(|o| {
+            let keep = retain_condition(o);
+            if !keep && journaling {
+                removed.push(o.clone());
+            }
+            keep
+        });
+        removed
     }
 
     pub fn is_pending_ask(&self, id: u64) -> bool {
        let asks = self._ask.read().unwrap();
-       asks.iter().any(|o| o.id == Some(id))
+       asks.iter().any(|o| o.id == Some(id)) || self.is_parked(id, OrderType::Sell)
     }
 
     pub fn is_pending_bid(&self, id: u64) -> bool {
        let asks = self._bid.read().unwrap();
-       asks.iter().any(|o| o.id == Some(id))
+       asks.iter().any(|o| o.id == Some(id)) || self.is_parked(id, OrderType::Buy)
+    }
+
+    fn is_parked(&self, id: u64, order_type: OrderType) -> bool {
+        self._parked.read().unwrap().iter().any(|o| o.id == Some(id) && o.order_type == order_type)
+    }
+
+    pub fn cancel(&mut self, id: u64) -> bool {
+        let mut found = false;
+        for side in [&self._bid, &self._ask] {
+            let mut orders = side.write().unwrap();
+            let before = orders.len();
+            orders.retain(|o| o.id != Some(id));
+            found |= orders.len() != before;
+        }
+        let mut parked = self._parked.write().unwrap();
+        let before = parked.len();
+        parked.retain(|o| o.id != Some(id));
+        found |= parked.len() != before;
+        drop(parked);
+        if found {
+            self.conditional.book_changed = true;
+            self.journal.record(|| JournalEntry::Cancel { stock: self.stock, order_id: id });
+        }
+        found
+    }
+
+    pub fn pending_ids(&self) -> HashSet<u64> {
+        let bids = self._bid.read().unwrap();
+        let asks = self._ask.read().unwrap();
+        let parked = self._parked.read().unwrap();
+        bids.iter().chain(asks.iter()).chain(parked.iter()).filter_map(|o| o.id).collect()
     }
 
     #[cfg(test)]
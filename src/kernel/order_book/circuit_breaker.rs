@@ -0,0 +1,106 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum HaltReason {
+    LimitUp,
+    LimitDown,
+    Manual
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Halt {
+    pub reason: HaltReason,
+    pub since: i64,
+    // manual halts last until someone resumes trading
+    pub until: Option<i64>
+}
+
+/// Limit-up/limit-down bands around a reference price, a trade outside
+/// reference * (1 +- band) halts the book for halt_nanos of market time.
+/// The reference follows the trades, the last one or the average of those in the last window_nanos
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct PriceBand {
+    pub band: f64,
+    pub halt_nanos: i64,
+    #[serde(default)]
+    pub window_nanos: Option<i64>
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct CircuitBreaker {
+    pub price_band: Option<PriceBand>,
+    pub reference_price: Option<f64>,
+    pub halt: Option<Halt>,
+    // (time, price) of the trades the reference is averaged over
+    #[serde(default)]
+    window: VecDeque<(i64, f64)>
+}
+
+impl PriceBand {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.band > 0.0 && self.band < 1.0) || self.halt_nanos <= 0 {
+            return Err("band must be within (0, 1) and halt_seconds positive".to_string());
+        }
+        if self.window_nanos.is_some_and(|window| window <= 0) {
+            return Err("window_seconds must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl CircuitBreaker {
+    pub fn is_halted(&self) -> bool {
+        self.halt.is_some()
+    }
+
+    pub fn breach(&self, price: f64) -> Option<HaltReason> {
+        let band = self.price_band?;
+        let reference = self.reference_price?;
+
+        if price > reference * (1.0 + band.band) {
+            Some(HaltReason::LimitUp)
+        } else if price < reference * (1.0 - band.band) {
+            Some(HaltReason::LimitDown)
+        } else {
+            None
+        }
+    }
+
+    /// Moves the reference along with a trade inside the band
+    pub fn record(&mut self, price: f64, now: i64) {
+        let Some(window) = self.price_band.and_then(|band| band.window_nanos) else {
+            self.reference_price = Some(price);
+            return
+        };
+        self.window.push_back((now, price));
+        while self.window.front().is_some_and(|&(time, _)| time <= now.saturating_sub(window)) {
+            self.window.pop_front();
+        }
+        let total: f64 = self.window.iter().map(|&(_, price)| price).sum();
+        self.reference_price = Some(total / self.window.len() as f64);
+    }
+
+    /// Starts the reference again from a single price, an auction's or the last one when a band is set
+    pub fn reset(&mut self, price: f64, now: i64) {
+        self.window.clear();
+        self.record(price, now);
+    }
+
+    pub fn trip(&mut self, reason: HaltReason, now: i64) {
+        let until = match (reason, self.price_band) {
+            (HaltReason::Manual, _) | (_, None) => None,
+            (_, Some(band)) => Some(now + band.halt_nanos)
+        };
+        self.halt = Some(Halt { reason, since: now, until });
+    }
+
+    pub fn halt_expired(&self, now: i64) -> bool {
+        matches!(self.halt, Some(Halt { until: Some(until), .. }) if until <= now)
+    }
+
+    pub fn halt_reason(&self) -> Option<HaltReason> {
+        self.halt.map(|h| h.reason)
+    }
+}
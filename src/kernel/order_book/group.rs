@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+
+use crate::classes::shared::order::*;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupKind {
+    // the first leg to trade cancels the others
+    Oco,
+    // an entry whose fills put a take profit and a stop loss on the book, the first of those to trade
+    // cancels the other along with whatever is left of the entry
+    Bracket
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LegRole {
+    Leg,
+    Entry,
+    TakeProfit,
+    StopLoss
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LegState {
+    // a bracket exit waiting on its entry to fill
+    Waiting,
+    Working,
+    Filled,
+    Cancelled
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GroupLeg {
+    pub role: LegRole,
+    // the order as placed, a bracket exit's amount follows what the entry has filled
+    pub order: Order,
+    pub filled: u64,
+    // whether the order has gone on the book yet
+    pub active: bool
+}
+
+/// Orders linked by a group id, kept with the book so that a fill cancels the rest of the group
+/// in the same matching pass
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OrderGroup {
+    pub id: u64,
+    pub kind: GroupKind,
+    pub legs: Vec<GroupLeg>,
+    // one of the legs traded and the others were cancelled, or the whole group was
+    pub done: bool
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LegStatus {
+    pub order_id: Option<u64>,
+    pub role: LegRole,
+    pub side: OrderType,
+    pub amount: u64,
+    pub filled: u64,
+    pub state: LegState
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GroupStatus {
+    pub id: u64,
+    pub kind: GroupKind,
+    pub done: bool,
+    pub legs: Vec<LegStatus>
+}
+
+impl OrderGroup {
+    pub fn oco(id: u64, legs: Vec<Order>) -> Self {
+        OrderGroup {
+            id: id,
+            kind: GroupKind::Oco,
+            legs: legs.into_iter().map(|order| GroupLeg::new(LegRole::Leg, order)).collect(),
+            done: false
+        }
+    }
+
+    /// The exits are held back until the entry fills, each then works as much as the entry has filled
+    pub fn bracket(id: u64, entry: Order, take_profit: Order, stop_loss: Order) -> Self {
+        OrderGroup {
+            id: id,
+            kind: GroupKind::Bracket,
+            legs: vec![
+                GroupLeg::new(LegRole::Entry, entry),
+                GroupLeg::new(LegRole::TakeProfit, take_profit),
+                GroupLeg::new(LegRole::StopLoss, stop_loss)
+            ],
+            done: false
+        }
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.legs.iter().filter_map(|leg| leg.order.id)
+    }
+
+    pub fn leg_mut(&mut self, id: u64) -> Option<&mut GroupLeg> {
+        self.legs.iter_mut().find(|leg| leg.order.id == Some(id))
+    }
+
+    /// Where each leg has got to, `pending` says whether an order is still on the book
+    pub fn status(&self, pending: impl Fn(u64) -> bool) -> GroupStatus {
+        let is_pending = |leg: &GroupLeg| leg.order.id.is_some_and(&pending);
+        let entry_pending = self.legs.iter().any(|leg| leg.role == LegRole::Entry && is_pending(leg));
+        GroupStatus {
+            id: self.id,
+            kind: self.kind,
+            done: self.done,
+            legs: self.legs.iter().map(|leg| {
+                let amount = leg.order.details.amount;
+                let state = if leg.active && leg.filled >= amount {
+                    LegState::Filled
+                } else if leg.active && is_pending(leg) {
+                    LegState::Working
+                } else if !leg.active && !self.done && entry_pending {
+                    LegState::Waiting
+                } else {
+                    LegState::Cancelled
+                };
+                LegStatus {
+                    order_id: leg.order.id,
+                    role: leg.role,
+                    side: leg.order.order_type,
+                    amount: amount,
+                    filled: leg.filled,
+                    state: state
+                }
+            }).collect()
+        }
+    }
+}
+
+impl GroupLeg {
+    fn new(role: LegRole, order: Order) -> Self {
+        GroupLeg {
+            role: role,
+            order: order,
+            filled: 0,
+            active: false
+        }
+    }
+}
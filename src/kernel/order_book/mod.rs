@@ -1,5 +1,7 @@
 pub mod book;
 pub mod record;
 pub mod stats;
+pub mod circuit_breaker;
+pub mod group;
 
 mod tests;
\ No newline at end of file
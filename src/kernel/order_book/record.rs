@@ -1,4 +1,5 @@
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
 use crate::globals::GRANULARITY;
 use crate::kernel::market_time::market_time::MTime;
@@ -29,14 +30,16 @@ const fn next_granularity(granularity: GRANULARITY) -> GRANULARITY {
     
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct HistoryBuffer {
     pub _live_data: Vec<Vec<ObStat>>,
     pub _historic_data: Vec<Vec<ObStat>>
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Serialize, Deserialize)]
 pub struct ObStat {
-    pub tick: u64,
+    // seconds, minutes, hours or days of market time, negative for bars from before the market started
+    pub tick: i64,
     pub granularity: GRANULARITY, 
     pub volume: u64,
     pub high: f64,
@@ -71,6 +74,21 @@ impl HistoryBuffer {
         }
     }
 
+    /// The last `count` bars at a granularity, oldest first, including the bars of the
+    /// still incomplete slice that have not been compressed yet
+    pub fn recent(&self, granularity: GRANULARITY, count: usize) -> Vec<ObStat> {
+        let i = granularity_index(granularity);
+        // days are never compressed any further, the historic copy is the live data
+        let bars: Vec<ObStat> = if i == 3 {
+            self._live_data[3].clone()
+        } else {
+            [&self._historic_data[i][..], &self._live_data[i][..]].concat()
+        };
+
+        let skip = bars.len().saturating_sub(count);
+        bars[skip..].to_vec()
+    }
+
     pub fn process_transactions(&mut self, measurements: &Vec<Transaction>){
         // take a list of transactions, convert to _live_data, group by.
         for (second_num, record) in &measurements.iter().group_by(|t| MTime::which_second(t.timestamp)) {
@@ -81,7 +99,7 @@ impl HistoryBuffer {
 
             self._live_data[0].push(ObStat {
                 granularity: GRANULARITY::SECOND,
-                tick: second_num,
+                tick: second_num as i64,
                 volume: vol,
                 high: max_p,
                 low: min_p,
@@ -130,7 +148,7 @@ impl HistoryBuffer {
         }
 
         let slice_size = granularity_max_measurements(granularity) as usize;
-        let mut last_tick_in_slice = measurements[0].tick + (slice_size as u64 - 1);
+        let mut last_tick_in_slice = measurements[0].tick + (slice_size as i64 - 1);
         
         let last_tick = measurements.last().unwrap().tick;
 
@@ -152,7 +170,7 @@ impl HistoryBuffer {
             }); 
  
             let first = subject[0];
-            let tick: u64 = first.tick / slice_size as u64;
+            let tick: i64 = first.tick.div_euclid(slice_size as i64);
             target.push(ObStat {
                 granularity: next_granularity(granularity),
                 tick: tick,
@@ -163,7 +181,7 @@ impl HistoryBuffer {
                 close: subject.last().unwrap().close
             });
 
-            last_tick_in_slice += slice_size as u64;
+            last_tick_in_slice += slice_size as i64;
         }
         (target, subject) 
     }
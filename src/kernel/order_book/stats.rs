@@ -1,15 +1,28 @@
 use actix_web::{http::header::PERMISSIONS_POLICY, test::call_service};
 
+use serde::{Deserialize, Deserializer, Serialize};
+
 use super::record::ObStat;
 
+// JSON has no NaN, serde_json writes it as null
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Stats {
+    #[serde(deserialize_with = "nan_from_null")]
     minute_volatility: f64,
+    #[serde(deserialize_with = "nan_from_null")]
     hour_volatility: f64,
+    #[serde(deserialize_with = "nan_from_null")]
     day_volatility: f64,
+    #[serde(deserialize_with = "nan_from_null")]
     month_volatility: f64,
+    #[serde(deserialize_with = "nan_from_null")]
     rsi: f64
 }
 
+fn nan_from_null<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    Ok(Option::<f64>::deserialize(deserializer)?.unwrap_or(f64::NAN))
+}
+
 impl Stats {
     pub fn new() -> Self {
         Stats {
@@ -30,18 +43,40 @@ impl Stats {
         self.rsi = Self::calculate_rsi(&history_matrix[3]);
     }
 
+    pub fn minute_volatility(&self) -> f64 {
+        self.minute_volatility
+    }
+
+    pub fn hour_volatility(&self) -> f64 {
+        self.hour_volatility
+    }
+
+    pub fn day_volatility(&self) -> f64 {
+        self.day_volatility
+    }
+
+    pub fn month_volatility(&self) -> f64 {
+        self.month_volatility
+    }
+
+    pub fn rsi(&self) -> f64 {
+        self.rsi
+    }
+
+    // sample standard deviation of the per-bar returns
     fn calculate_volatility(stats: &Vec<ObStat>) -> f64 {
         let n = stats.len() as f64;
-        if n < 2.0 { return n };
+        if n < 2.0 { return 0.0 };
         
         let return_lambda = |s: &ObStat| (s.close - s.open)/s.open;
         let avg_return: f64 = stats.iter()
             .map(return_lambda)
             .sum::<f64>() / n;
 
-        stats.iter()
-            .map(|s| return_lambda(s) - avg_return)
-            .sum::<f64>() / (n - 1.0)
+        let variance = stats.iter()
+            .map(|s| (return_lambda(s) - avg_return).powi(2))
+            .sum::<f64>() / (n - 1.0);
+        variance.sqrt()
     }
 
     fn calculate_rsi(stats: &Vec<ObStat>) -> f64 {
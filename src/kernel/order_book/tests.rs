@@ -4,7 +4,7 @@
 mod tests {
     //gpt says i don't need this, rust analyzer disagrees :(
     use crate::kernel::market::*;
-    use crate::kernel::order_book::record::*;
+    use crate::kernel::order_book::{book::*, record::*};
     use crate::classes::shared::{order::*, transaction::*};
     use crate::globals::*;
 
@@ -21,22 +21,23 @@ mod tests {
         let ipo_price = 10.0;
 
         let stock: Stock = Stock::AAPL;
+        let market = Market::new();
 
         // ipo, then offer sells at a better price, see if they're at the front of the ask queue
-        ipo(stock, ipo_size, ipo_price, None);
-        sell(stock, market_order_size,None, None, None);
-        sell(stock, limit_order_size, Some(limit_order_price), None, None);
+        market.ipo(stock, ipo_size, ipo_price, None);
+        market.sell(stock, market_order_size,None, None, None);
+        market.sell(stock, limit_order_size, Some(limit_order_price), None, None);
         
-        find_trades(stock);
-        _assert_top_ask(&stock, &OrderVariant::Market, market_order_size, 0.0);
+        market.find_trades(stock);
+        _assert_top_ask(&market, &stock, &OrderVariant::Market, market_order_size, 0.0);
         
-        buy(stock, market_order_size, None, None, None);
-        find_trades(stock);
-        _assert_top_ask(&stock, &_limit, limit_order_size, limit_order_price);
+        market.buy(stock, market_order_size, None, None, None);
+        market.find_trades(stock);
+        _assert_top_ask(&market, &stock, &_limit, limit_order_size, limit_order_price);
     
-        buy(stock, limit_order_size, None, None, None);
-        find_trades(stock);
-        _assert_top_ask(&stock, &_limit, ipo_size, ipo_price);
+        market.buy(stock, limit_order_size, None, None, None);
+        market.find_trades(stock);
+        _assert_top_ask(&market, &stock, &_limit, ipo_size, ipo_price);
     }
 
 
@@ -44,39 +45,41 @@ mod tests {
     fn test_clean_book_works() {
         let stock = Stock::AAPL;
         let lifetime = 100;
+        let market = Market::new();
 
         // put some unmatched orders on, sleep, clean, assert they're empty
-        ipo(stock, 0, 0.0, None);
-        buy(stock, 2, Some(100.9), Some(lifetime), None);
+        market.ipo(stock, 0, 0.0, None);
+        market.buy(stock, 2, Some(100.9), Some(lifetime), None);
         std::thread::sleep(std::time::Duration::from_nanos((lifetime * 20) as u64));
         {
-            let market = get_market().read().unwrap();
-            let mut book = &mut market.get(&stock).unwrap().write().unwrap().order_book;
+            let books = market.stock_book().read().unwrap();
+            let mut book = &mut books.get(&stock).unwrap().write().unwrap().order_book;
 
             book.clean_book();
         }
-        _assert_no_bids(&stock);
+        _assert_no_bids(&market, &stock);
     }
 
     #[cfg(test)]
-    fn _assert_top_ask(stock: &Stock, variant: &OrderVariant, amount: u64, price: f64){
+    fn _assert_top_ask(market: &Market, stock: &Stock, variant: &OrderVariant, amount: u64, price: f64){
         // unwrap  all the way into market
-        let market = get_market().read().unwrap();
-        let book = &market.get(&stock).unwrap().read().unwrap().order_book;
+        let books = market.stock_book().read().unwrap();
+        let book = &books.get(&stock).unwrap().read().unwrap().order_book;
         
         let ask_queue = book.get_asks_for_testing();
         let ask = ask_queue.peek();
 
         match variant {
             OrderVariant::Market => _assert_market_sell(ask, amount),
-            OrderVariant::Limit { price: _ } => _assert_limit_sell(ask, amount, price) 
+            OrderVariant::Limit { price: _ } => _assert_limit_sell(ask, amount, price),
+            _ => panic!("Unexpected variant {:?}", variant)
         }
     }
 
     #[cfg(test)]
-    fn _assert_no_bids(stock: &Stock) {
-        let market = get_market().read().unwrap();
-        let book = &market.get(&stock).unwrap().read().unwrap().order_book;
+    fn _assert_no_bids(market: &Market, stock: &Stock) {
+        let books = market.stock_book().read().unwrap();
+        let book = &books.get(&stock).unwrap().read().unwrap().order_book;
 
         let bid_queue = book.get_bids_for_testing();
         println!("empty {}", bid_queue.is_empty());
@@ -154,7 +157,7 @@ mod tests {
         let magic = 86400 + 3661;
         for i in 0..magic {
             h._live_data[0].push(ObStat {
-                tick: i as u64,
+                tick: i as i64,
                 granularity: GRANULARITY::SECOND,
                 volume: 100,
                 high: 10.0,
@@ -189,7 +192,7 @@ mod tests {
         let magic = 31;
         for i in 0..magic {
             h._live_data[0].push(ObStat {
-                tick: i as u64 * 2,
+                tick: i as i64 * 2,
                 granularity: GRANULARITY::SECOND,
                 volume: 100,
                 high: 10.0,
@@ -268,5 +271,399 @@ mod tests {
         assert!(h_m.len() == 0)
     }
 
+    #[test]
+    fn test_recent_bars_span_historic_and_live_data() {
+        let mut h = HistoryBuffer::new();
+        for i in 0..90 {
+            h._live_data[0].push(ObStat {
+                tick: i,
+                granularity: GRANULARITY::SECOND,
+                volume: 100,
+                high: 10.0,
+                low: 1.0,
+                open: 0.0,
+                close: 0.0
+            });
+        };
+        h.compress();
+
+        let recent = h.recent(GRANULARITY::SECOND, 40);
+        let ticks: Vec<i64> = recent.iter().map(|b| b.tick).collect();
+        assert!(ticks == (50..90).collect::<Vec<i64>>(), "Expected the last 40 seconds in order, found {:?}", ticks);
+        assert!(h.recent(GRANULARITY::MINUTE, 10).len() == 1);
+    }
+
+    #[test]
+    fn test_uncross_maximises_volume() {
+        let mut book = _new_book();
+        book.process_order(_limit_order(OrderType::Buy, 10, 10.2, TimeInForce::GoodTillCancel));
+        book.process_order(_limit_order(OrderType::Buy, 5, 10.0, TimeInForce::GoodTillCancel));
+        book.process_order(_limit_order(OrderType::Sell, 8, 9.9, TimeInForce::GoodTillCancel));
+        book.process_order(_limit_order(OrderType::Sell, 10, 10.1, TimeInForce::GoodTillCancel));
+
+        let price = book.uncross();
+
+        assert!(price == Some(10.1), "Expected the auction to uncross at 10.1, found {:?}", price);
+        assert!(book.transaction_record.iter().all(|t| t.price == 10.1));
+        let volume: u64 = book.transaction_record.iter().map(|t| t.volume).sum();
+        assert!(volume == 10, "Expected 10 shares to trade in the auction, found {}", volume);
+
+        _assert_limit_sell(book.get_asks_for_testing().peek(), 8, 10.1);
+        assert!(book.get_bids_for_testing().len() == 1);
+    }
+
+    #[test]
+    fn test_uncross_without_cross_does_nothing() {
+        let mut book = _new_book();
+        book.process_order(_limit_order(OrderType::Buy, 10, 9.0, TimeInForce::GoodTillCancel));
+        book.process_order(_limit_order(OrderType::Sell, 10, 10.0, TimeInForce::GoodTillCancel));
+
+        assert!(book.uncross().is_none());
+        assert!(book.transaction_record.is_empty());
+    }
+
+    #[test]
+    fn test_day_orders_expire() {
+        let mut book = _new_book();
+        book.process_order(_limit_order(OrderType::Buy, 10, 9.0, TimeInForce::Day));
+        book.process_order(_limit_order(OrderType::Buy, 10, 8.0, TimeInForce::GoodTillCancel));
+
+        book.expire_day_orders();
+
+        let bids = book.get_bids_for_testing();
+        assert!(bids.len() == 1, "Expected only the GTC bid to survive, found {} bids", bids.len());
+        assert!(bids.peek().unwrap().details.time_in_force == TimeInForce::GoodTillCancel);
+    }
+
+    #[test]
+    fn test_session_schedule_phases() {
+        use chrono::{NaiveDate, TimeZone, Utc};
+        use crate::kernel::market_time::session::*;
+
+        let mut schedule = SessionSchedule::default();
+        schedule.calendar.holidays.push(NaiveDate::from_ymd_opt(2024, 12, 25).unwrap());
+        // 2024-03-04 is a Monday
+        let at = |d, h, m| Utc.with_ymd_and_hms(2024, 3, d, h, m, 0).unwrap();
+
+        assert!(schedule.phase_at(at(4, 7, 0)) == SessionPhase::Closed);
+        assert!(schedule.phase_at(at(4, 9, 0)) == SessionPhase::PreOpen);
+        assert!(schedule.phase_at(at(4, 9, 27)) == SessionPhase::OpeningAuction);
+        assert!(schedule.phase_at(at(4, 12, 0)) == SessionPhase::Continuous);
+        assert!(schedule.phase_at(at(4, 16, 2)) == SessionPhase::ClosingAuction);
+        assert!(schedule.phase_at(at(4, 17, 0)) == SessionPhase::Closed);
+        assert!(schedule.phase_at(at(9, 12, 0)) == SessionPhase::Closed, "Expected markets to be closed on a Saturday");
+        assert!(schedule.phase_at(Utc.with_ymd_and_hms(2024, 12, 25, 12, 0, 0).unwrap()) == SessionPhase::Closed);
+    }
+
+    #[test]
+    fn test_price_band_halts_instead_of_trading() {
+        use crate::kernel::order_book::circuit_breaker::*;
+
+        let mut book = _new_book();
+        book.breaker.price_band = Some(PriceBand { band: 0.1, halt_nanos: 1000, window_nanos: None });
+        book.breaker.reference_price = Some(10.0);
+
+        book.process_order(_limit_order(OrderType::Buy, 10, 12.0, TimeInForce::GoodTillCancel));
+        book.process_order(_limit_order(OrderType::Sell, 10, 11.5, TimeInForce::GoodTillCancel));
+        book.find_trade();
+
+        assert!(book.breaker.halt_reason() == Some(HaltReason::LimitUp), "Expected a limit up halt, found {:?}", book.breaker.halt);
+        assert!(book.transaction_record.is_empty(), "Expected no trades outside of the price band");
+        assert!(book.get_bids_for_testing().len() == 1 && book.get_asks_for_testing().len() == 1);
+
+        // still halted, nothing trades
+        book.find_trade();
+        assert!(book.transaction_record.is_empty());
+
+        // the reopening auction trades and re-references the band
+        assert!(book.resume() == Some(11.5));
+        assert!(book.breaker.reference_price == Some(11.5));
+        assert!(!book.breaker.is_halted());
+    }
+
+    #[test]
+    fn test_price_band_follows_a_drifting_price() {
+        use crate::kernel::order_book::circuit_breaker::*;
+
+        let mut book = _new_book();
+        book.breaker.price_band = Some(PriceBand { band: 0.1, halt_nanos: 1000, window_nanos: None });
+        book.breaker.reference_price = Some(10.0);
+
+        // 5% a trade takes the price 50% past where the band started without halting
+        for step in 1..=10 {
+            let price = 10.0 * 1.05_f64.powi(step);
+            book.process_order(_limit_order(OrderType::Buy, 1, price, TimeInForce::GoodTillCancel));
+            book.process_order(_limit_order(OrderType::Sell, 1, price, TimeInForce::GoodTillCancel));
+            book.find_trade();
+        }
+        assert!(!book.breaker.is_halted(), "Expected a gradual drift not to halt, found {:?}", book.breaker.halt);
+        assert!(book.transaction_record.len() == 10);
+        assert!(book.breaker.reference_price == Some(book.price));
+
+        // averaged over a window the reference lags behind, the same steps soon leave the band
+        let mut book = _new_book();
+        book.breaker.price_band = Some(PriceBand { band: 0.1, halt_nanos: 1000, window_nanos: Some(1_000_000) });
+        book.breaker.reference_price = Some(10.0);
+        for step in 1..=10 {
+            let price = 10.0 * 1.05_f64.powi(step);
+            book.process_order(_limit_order(OrderType::Buy, 1, price, TimeInForce::GoodTillCancel));
+            book.process_order(_limit_order(OrderType::Sell, 1, price, TimeInForce::GoodTillCancel));
+            book.find_trade();
+        }
+        assert!(book.breaker.halt_reason() == Some(HaltReason::LimitUp), "Expected the averaged reference to halt, found {:?}", book.breaker.halt);
+    }
+
+    #[test]
+    fn test_manual_halt_stops_auctions() {
+        use crate::kernel::order_book::circuit_breaker::*;
+
+        let mut book = _new_book();
+        book.process_order(_limit_order(OrderType::Buy, 10, 10.0, TimeInForce::GoodTillCancel));
+        book.process_order(_limit_order(OrderType::Sell, 10, 10.0, TimeInForce::GoodTillCancel));
+        book.halt(HaltReason::Manual);
+
+        assert!(book.uncross().is_none());
+        assert!(!book.breaker.halt_expired(i64::MAX), "Expected manual halts to never expire");
+    }
+
+    #[test]
+    fn test_depth_aggregates_levels() {
+        let mut book = _new_book();
+        book.process_order(_limit_order(OrderType::Buy, 10, 9.0, TimeInForce::GoodTillCancel));
+        book.process_order(_limit_order(OrderType::Buy, 5, 9.0, TimeInForce::GoodTillCancel));
+        book.process_order(_limit_order(OrderType::Buy, 7, 8.0, TimeInForce::GoodTillCancel));
+        book.process_order(_limit_order(OrderType::Buy, 1, 7.0, TimeInForce::GoodTillCancel));
+        book.process_order(_limit_order(OrderType::Sell, 3, 11.0, TimeInForce::GoodTillCancel));
+        book.process_order(_limit_order(OrderType::Sell, 4, 10.0, TimeInForce::GoodTillCancel));
+
+        let (bids, asks) = book.depth(2);
+        assert!(bids == vec![(9.0, 15), (8.0, 7)], "Unexpected bid levels {:?}", bids);
+        assert!(asks == vec![(10.0, 4), (11.0, 3)], "Unexpected ask levels {:?}", asks);
+    }
+
+    #[test]
+    fn test_iceberg_replenishes_behind_the_queue() {
+        let mut book = _new_book();
+        let mut iceberg = _limit_order(OrderType::Sell, 30, 10.0, TimeInForce::GoodTillCancel);
+        iceberg.id = Some(1);
+        iceberg.details.visibility = Visibility::iceberg(10);
+        let mut displayed = _limit_order(OrderType::Sell, 10, 10.0, TimeInForce::GoodTillCancel);
+        displayed.id = Some(2);
+        displayed.details.time = 1;
+        book.process_order(iceberg);
+        book.process_order(displayed);
+        assert!(book.depth(5).1 == vec![(10.0, 20)], "Expected only the iceberg's peak on show, found {:?}", book.depth(5).1);
+
+        // the first slice trades, the next one queues up behind the displayed order
+        book.process_order(_limit_order(OrderType::Buy, 15, 10.0, TimeInForce::GoodTillCancel));
+        book.find_trade();
+        let fills: Vec<(Option<u64>, u64)> = book.transaction_record.iter().map(|t| (t.sell_id, t.volume)).collect();
+        assert!(fills == vec![(Some(1), 10), (Some(2), 5)], "Unexpected fills {:?}", fills);
+        assert!(book.depth(5).1 == vec![(10.0, 15)], "Unexpected ask levels {:?}", book.depth(5).1);
+
+        // a buyer bigger than the slice still works through the reserve
+        book.process_order(_limit_order(OrderType::Buy, 25, 10.0, TimeInForce::GoodTillCancel));
+        book.find_trade();
+        let volume: u64 = book.transaction_record.iter().filter(|t| t.sell_id == Some(1)).map(|t| t.volume).sum();
+        assert!(volume == 30, "Expected the whole iceberg to trade, found {}", volume);
+        assert!(book.get_asks_for_testing().is_empty() && book.get_bids_for_testing().is_empty());
+    }
+
+    #[test]
+    fn test_hidden_orders_trade_behind_displayed() {
+        let mut book = _new_book();
+        let mut hidden = _limit_order(OrderType::Buy, 10, 10.0, TimeInForce::GoodTillCancel);
+        hidden.id = Some(1);
+        hidden.details.visibility = Visibility::Hidden;
+        let mut displayed = _limit_order(OrderType::Buy, 10, 10.0, TimeInForce::GoodTillCancel);
+        displayed.id = Some(2);
+        displayed.details.time = 5;
+        book.process_order(hidden);
+        book.process_order(displayed);
+        assert!(book.depth(5).0 == vec![(10.0, 10)], "Expected the hidden bid off the book's depth, found {:?}", book.depth(5).0);
+
+        book.process_order(_limit_order(OrderType::Sell, 15, 10.0, TimeInForce::GoodTillCancel));
+        book.find_trade();
+        let fills: Vec<(Option<u64>, u64)> = book.transaction_record.iter().map(|t| (t.buy_id, t.volume)).collect();
+        assert!(fills == vec![(Some(2), 10), (Some(1), 5)], "Unexpected fills {:?}", fills);
+        assert!(book.depth(5).0.is_empty(), "Expected nothing on show, found {:?}", book.depth(5).0);
+        assert!(book.is_pending_bid(1));
+    }
+
+    #[test]
+    fn test_post_only_never_takes() {
+        let mut book = _new_book();
+        book.process_order(_limit_order(OrderType::Sell, 10, 10.0, TimeInForce::GoodTillCancel));
+
+        let rejected = _variant_order(OrderType::Buy, 5, OrderVariant::PostOnly { price: 10.0, slide: None });
+        assert!(!book.process_order(rejected), "Expected a crossing post-only bid to be rejected");
+        assert!(book.get_bids_for_testing().is_empty());
+
+        let repriced = _variant_order(OrderType::Buy, 5, OrderVariant::PostOnly { price: 10.5, slide: Some(0.5) });
+        assert!(book.process_order(repriced));
+        let resting = _variant_order(OrderType::Buy, 5, OrderVariant::PostOnly { price: 9.0, slide: None });
+        assert!(book.process_order(resting));
+        book.find_trade();
+
+        assert!(book.transaction_record.is_empty(), "Expected post-only orders to never trade on arrival");
+        assert!(book.depth(5).0 == vec![(9.5, 5), (9.0, 5)], "Unexpected bid levels {:?}", book.depth(5).0);
+    }
+
+    #[test]
+    fn test_pegged_orders_follow_the_quote() {
+        let mut book = _new_book();
+        book.process_order(_limit_order(OrderType::Buy, 10, 9.0, TimeInForce::GoodTillCancel));
+        book.process_order(_limit_order(OrderType::Sell, 10, 11.0, TimeInForce::GoodTillCancel));
+        book.process_order(_variant_order(OrderType::Buy, 5, OrderVariant::Pegged { reference: PegReference::Primary, offset: 0.0, price: None }));
+        book.process_order(_variant_order(OrderType::Sell, 5, OrderVariant::Pegged { reference: PegReference::Mid, offset: 0.5, price: None }));
+        assert!(book.depth(5) == (vec![(9.0, 15)], vec![(10.5, 5), (11.0, 10)]), "Unexpected depth {:?}", book.depth(5));
+
+        // a better bid moves both pegs on the next matching pass
+        book.process_order(_limit_order(OrderType::Buy, 10, 10.0, TimeInForce::GoodTillCancel));
+        book.find_trade();
+        assert!(book.transaction_record.is_empty());
+        assert!(book.depth(5) == (vec![(10.0, 15), (9.0, 10)], vec![(11.0, 15)]), "Unexpected depth {:?}", book.depth(5));
+
+        // nothing to peg to yet, the order waits off the book
+        let mut waiting = _variant_order(OrderType::Sell, 5, OrderVariant::Pegged { reference: PegReference::Market, offset: 0.0, price: None });
+        waiting.id = Some(9);
+        let mut empty = _new_book();
+        empty.process_order(waiting);
+        assert!(empty.is_pending_ask(9) && empty.depth(5).1.is_empty());
+        empty.process_order(_limit_order(OrderType::Buy, 5, 8.0, TimeInForce::GoodTillCancel));
+        empty.find_trade();
+        let fills: Vec<(Option<u64>, f64)> = empty.transaction_record.iter().map(|t| (t.sell_id, t.price)).collect();
+        assert!(fills == vec![(Some(9), 8.0)], "Expected the market peg to trade at the bid, found {:?}", fills);
+    }
+
+    #[test]
+    fn test_pegs_follow_the_quote_within_a_pass() {
+        let mut book = _new_book();
+        book.process_order(_limit_order(OrderType::Buy, 5, 10.0, TimeInForce::GoodTillCancel));
+        book.process_order(_variant_order(OrderType::Buy, 5, OrderVariant::Pegged { reference: PegReference::Primary, offset: 0.0, price: None }));
+        book.process_order(_limit_order(OrderType::Buy, 5, 9.0, TimeInForce::GoodTillCancel));
+
+        // the bid it follows trades away, so the peg drops back to 9 before it can trade itself
+        book.process_order(_limit_order(OrderType::Sell, 12, 9.5, TimeInForce::GoodTillCancel));
+        book.find_trade();
+        assert!(book.transaction_record.len() == 1, "Expected one trade, found {:?}", book.transaction_record);
+        assert!(book.depth(5) == (vec![(9.0, 10)], vec![(9.5, 7)]), "Unexpected depth {:?}", book.depth(5));
+    }
+
+    #[test]
+    fn test_pegs_stay_on_the_tick() {
+        let mut book = _new_book();
+        book.tick_size = Some(0.5);
+        book.process_order(_limit_order(OrderType::Buy, 10, 10.0, TimeInForce::GoodTillCancel));
+        book.process_order(_limit_order(OrderType::Sell, 10, 10.5, TimeInForce::GoodTillCancel));
+
+        // the mid is between ticks, each peg goes to the side that doesn't cross
+        book.process_order(_variant_order(OrderType::Buy, 5, OrderVariant::Pegged { reference: PegReference::Mid, offset: 0.0, price: None }));
+        book.process_order(_variant_order(OrderType::Sell, 5, OrderVariant::Pegged { reference: PegReference::Mid, offset: 0.0, price: None }));
+        book.find_trade();
+        assert!(book.transaction_record.is_empty());
+        assert!(book.depth(5) == (vec![(10.0, 15)], vec![(10.5, 15)]), "Unexpected depth {:?}", book.depth(5));
+    }
+
+    #[test]
+    fn test_trailing_stop_follows_the_price() {
+        let mut book = _new_book();
+        book.price = 10.0;
+        let mut stop = _variant_order(OrderType::Sell, 5, OrderVariant::TrailingStop { trail: Trail::Offset(1.0), stop: None });
+        stop.id = Some(3);
+        book.process_order(stop);
+        book.process_order(_limit_order(OrderType::Buy, 5, 10.9, TimeInForce::GoodTillCancel));
+
+        // the stop ratchets up to 11 and stays there as the price falls back
+        for price in [12.0, 11.5] {
+            book.price = price;
+            book.find_trade();
+            assert!(book.transaction_record.is_empty(), "Expected the stop to hold at {}", price);
+            assert!(book.is_pending_ask(3) && book.depth(5).1.is_empty());
+        }
+        book.price = 11.0;
+        book.find_trade();
+        let fills: Vec<(Option<u64>, f64, u64)> = book.transaction_record.iter().map(|t| (t.sell_id, t.price, t.volume)).collect();
+        assert!(fills == vec![(Some(3), 10.9, 5)], "Expected the stop to sell into the bid, found {:?}", fills);
+
+        let mut book = _new_book();
+        book.price = 10.0;
+        book.process_order(_variant_order(OrderType::Buy, 5, OrderVariant::TrailingStop { trail: Trail::Percent(0.1), stop: None }));
+        book.price = 8.0;
+        book.find_trade();
+        assert!(book.get_bids_for_testing().is_empty(), "Expected the buy stop to trail down to 8.8");
+        book.price = 9.0;
+        book.find_trade();
+        assert!(book.get_bids_for_testing().peek().is_some_and(|o| o.variant == OrderVariant::Market));
+    }
+
+    #[test]
+    fn test_self_trade_prevention_modes() {
+        // a resting bid of 10 from owner 1 meets a newer ask of 4 from the same owner, then one of 3 from owner 2
+        let cases = [
+            (None, vec![4, 3], vec![(10.0, 3)], vec![]),
+            (Some(SelfTradePrevention::CancelNewest), vec![3], vec![(10.0, 7)], vec![]),
+            (Some(SelfTradePrevention::CancelOldest), vec![], vec![], vec![(10.0, 7)]),
+            (Some(SelfTradePrevention::CancelBoth), vec![], vec![], vec![(10.0, 3)]),
+            (Some(SelfTradePrevention::Decrement), vec![3], vec![(10.0, 3)], vec![])
+        ];
+        for (mode, volumes, bids, asks) in cases {
+            let mut book = _new_book();
+            book.self_trade_prevention = mode;
+            book.process_order(_owned_order(OrderType::Buy, 10, 10.0, 1, 1));
+            book.process_order(_owned_order(OrderType::Sell, 4, 10.0, 1, 2));
+            book.process_order(_owned_order(OrderType::Sell, 3, 10.0, 2, 3));
+            book.find_trade();
+
+            let traded: Vec<u64> = book.transaction_record.iter().map(|t| t.volume).collect();
+            assert!(traded == volumes, "Expected {:?} to trade {:?}, found {:?}", mode, volumes, traded);
+            let (bid, ask) = book.depth(5);
+            assert!(bid == bids, "Expected {:?} to leave bids {:?}, found {:?}", mode, bids, bid);
+            assert!(ask == asks, "Expected {:?} to leave asks {:?}, found {:?}", mode, asks, ask);
+        }
+    }
+
+    #[test]
+    fn test_cancel_removes_order() {
+        let mut book = _new_book();
+        let mut order = _limit_order(OrderType::Sell, 10, 10.0, TimeInForce::GoodTillCancel);
+        order.id = Some(7);
+        book.process_order(order);
+
+        assert!(book.cancel(7));
+        assert!(book.get_asks_for_testing().is_empty());
+        assert!(!book.cancel(7), "Expected cancelling twice to fail");
+    }
+
+    #[cfg(test)]
+    fn _new_book() -> OrderBook {
+        OrderBook::new(Stock::GOOGL, Default::default(), Default::default())
+    }
+
+    #[cfg(test)]
+    fn _limit_order(order_type: OrderType, amount: u64, price: f64, time_in_force: TimeInForce) -> Order {
+        Order {
+            id: None,
+            order_type,
+            variant: OrderVariant::Limit { price },
+            details: OrderDetails { time: 0, stock: Stock::GOOGL, amount, lifetime_nanos: None, time_in_force, visibility: Visibility::Displayed, owner: None }
+        }
+    }
+
+    #[cfg(test)]
+    fn _owned_order(order_type: OrderType, amount: u64, price: f64, owner: u64, time: i64) -> Order {
+        let mut order = _limit_order(order_type, amount, price, TimeInForce::GoodTillCancel);
+        order.details.owner = Some(owner);
+        order.details.time = time;
+        order
+    }
+
+    #[cfg(test)]
+    fn _variant_order(order_type: OrderType, amount: u64, variant: OrderVariant) -> Order {
+        let mut order = _limit_order(order_type, amount, 0.0, TimeInForce::GoodTillCancel);
+        order.variant = variant;
+        order
+    }
+
 
 }
\ No newline at end of file
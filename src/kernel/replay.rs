@@ -0,0 +1,265 @@
+use std::collections::{HashMap, VecDeque};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::classes::shared::{order::*, transaction::Transaction};
+use crate::kernel::journal::{JournalEntry, JournalRecord};
+use crate::kernel::market::Market;
+
+// only the first few differences are kept, after one the books have usually diverged anyway
+const MAX_MISMATCHES: usize = 100;
+// how many entries go by between updates of the published progress
+const STATUS_INTERVAL: usize = 1000;
+
+/// How fast a journal is fed back in. Market time between entries is kept as it was,
+/// scaled down by `factor`, or dropped altogether.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplaySpeed {
+    // a market nanosecond per real nanosecond, the pace of a market at the default acceleration
+    #[default]
+    Original,
+    Accelerated { factor: f64 },
+    Max
+}
+
+impl ReplaySpeed {
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            ReplaySpeed::Accelerated { factor } if !(factor > 0.0 && factor.is_finite()) => Err("factor must be positive".to_string()),
+            _ => Ok(())
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TradeMismatch {
+    pub seq: u64,
+    pub stock: Stock,
+    pub recorded: Transaction,
+    // None when the book had nothing to match
+    pub replayed: Option<Transaction>
+}
+
+/// Progress of a replay, and how the trades it regenerated compare with the recorded ones
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ReplayReport {
+    pub entries: usize,
+    pub replayed: usize,
+    pub orders: u64,
+    pub trades: u64,
+    pub matched: u64,
+    pub mismatched: u64,
+    // auction trades the replay produced that the journal doesn't have
+    pub unrecorded: u64,
+    pub mismatches: Vec<TradeMismatch>,
+    pub finished: bool
+}
+
+impl ReplayReport {
+    pub fn verified(&self) -> bool {
+        self.mismatched == 0 && self.unrecorded == 0
+    }
+}
+
+// what a replayed auction did, checked off against the entries journaled after it
+struct Auction {
+    trades: VecDeque<Transaction>,
+    self_trades: usize
+}
+
+// what an expiry entry says about the order that expired
+struct Expired {
+    order_id: Option<u64>,
+    order_type: OrderType,
+    amount: u64,
+    variant: OrderVariant,
+    placed: i64
+}
+
+impl Expired {
+    fn is(&self, order: &Order) -> bool {
+        order.id == self.order_id
+            && order.order_type == self.order_type
+            && order.details.amount == self.amount
+            && order.variant == self.variant
+            && order.details.time == self.placed
+    }
+}
+
+
+/// The progress of the running (or last) replay, if there has been one
+pub fn status(market: &Market) -> Option<ReplayReport> {
+    market.replay_status.lock().unwrap().clone()
+}
+
+/// Rebuilds the market from a journal. Every stock it mentions is relisted with an empty book, then
+/// accepted orders go back on the books and trades are matched again one at a time as the journal
+/// reaches them, each checked against the recorded trade. Market time follows the journal on the
+/// virtual clock, so the replayed market can be served over the API as it goes.
+pub fn run(market: &Market, records: &[JournalRecord], speed: ReplaySpeed) -> ReplayReport {
+    let mut report = ReplayReport { entries: records.len(), ..ReplayReport::default() };
+    *market.replay_status.lock().unwrap() = Some(report.clone());
+
+    let mut stocks: Vec<Stock> = Vec::new();
+    for record in records {
+        let stock = record.entry.stock();
+        if !stocks.contains(&stock) {
+            stocks.push(stock);
+        }
+    }
+    for &stock in &stocks {
+        market.list(stock);
+    }
+
+    let start = records.first().map_or(0, |r| r.timestamp);
+    market.clock.use_virtual_clock(start);
+    let started = Instant::now();
+    let mut last_second = market.clock.current_second();
+
+    // trades and self-trade steps of an auction, journaled straight after it
+    let mut auctions: HashMap<Stock, Auction> = HashMap::new();
+    // a clean up journals its expiries one after the other, they are taken off the book together
+    let mut expiries: HashMap<Stock, Vec<Expired>> = HashMap::new();
+
+    for record in records {
+        if market.is_stopped() {
+            break;
+        }
+        pace(speed, started, record.timestamp - start);
+        market.clock.set_now(record.timestamp);
+        let stock = record.entry.stock();
+
+        if let JournalEntry::Expiry { order_id, order_type, amount, variant, placed, .. } = record.entry {
+            expiries.entry(stock).or_default().push(Expired { order_id, order_type, amount, variant, placed });
+        } else {
+            remove_expired(market, stock, &mut expiries);
+        }
+        if !matches!(record.entry, JournalEntry::Trade { .. } | JournalEntry::SelfTrade { .. }) {
+            if let Some(auction) = auctions.remove(&stock) {
+                report.unrecorded += auction.trades.len() as u64;
+            }
+        }
+
+        match &record.entry {
+            JournalEntry::OrderAccepted { order } => {
+                market.accept_order(order.clone());
+                report.orders += 1;
+            },
+            JournalEntry::Cancel { order_id, .. } => {
+                market.cancel(stock, *order_id);
+            },
+            JournalEntry::GroupAccepted { group, .. } => {
+                market.accept_group(stock, group.clone());
+                report.orders += group.legs.len() as u64;
+            },
+            JournalEntry::GroupCancel { group_id, .. } => {
+                market.cancel_group(*group_id);
+            },
+            JournalEntry::SelfTrade { mode, .. } => {
+                // the replayed auction has taken its own steps already
+                match auctions.get_mut(&stock).filter(|auction| auction.self_trades > 0) {
+                    Some(auction) => auction.self_trades -= 1,
+                    None => {
+                        market.self_trade(stock, *mode);
+                    }
+                }
+            },
+            JournalEntry::Expiry { .. } => {},
+            JournalEntry::Halt { reason, .. } => market.halt_for(stock, *reason),
+            // a reopening auction is journaled separately
+            JournalEntry::Resume { .. } => market.lift_halt(stock),
+            JournalEntry::Uncross { self_trade_prevention, .. } => {
+                let (trades, self_trades) = market.uncross(stock, *self_trade_prevention);
+                auctions.insert(stock, Auction { trades: trades.into(), self_trades: self_trades });
+            },
+            JournalEntry::Trade { transaction, .. } => {
+                let replayed = match auctions.get_mut(&stock).and_then(|auction| auction.trades.pop_front()) {
+                    Some(trade) => Some(trade),
+                    None => {
+                        market.clock.set_now(transaction.timestamp);
+                        market.match_next(stock)
+                    }
+                };
+                report.trades += 1;
+                if replayed.is_some_and(|t| same_trade(&t, transaction)) {
+                    report.matched += 1;
+                } else {
+                    report.mismatched += 1;
+                    if report.mismatches.len() < MAX_MISMATCHES {
+                        report.mismatches.push(TradeMismatch {
+                            seq: record.seq,
+                            stock: stock,
+                            recorded: *transaction,
+                            replayed: replayed
+                        });
+                    }
+                }
+            },
+            JournalEntry::Event { event, .. } => market.record_event(stock, *event)
+        }
+
+        // fill in the history as each second of the session goes by
+        let second = market.clock.current_second();
+        if second != last_second {
+            for &stock in &stocks {
+                market.report_transactions(stock);
+                market.update_stats(stock);
+            }
+            last_second = second;
+        }
+
+        report.replayed += 1;
+        if report.replayed.is_multiple_of(STATUS_INTERVAL) {
+            *market.replay_status.lock().unwrap() = Some(report.clone());
+        }
+    }
+
+    for &stock in &stocks {
+        remove_expired(market, stock, &mut expiries);
+        report.unrecorded += auctions.get(&stock).map_or(0, |auction| auction.trades.len() as u64);
+        market.report_transactions(stock);
+        market.update_stats(stock);
+    }
+    report.finished = true;
+    *market.replay_status.lock().unwrap() = Some(report.clone());
+    report
+}
+
+fn remove_expired(market: &Market, stock: Stock, expiries: &mut HashMap<Stock, Vec<Expired>>) {
+    let Some(mut expired) = expiries.remove(&stock) else {
+        return
+    };
+    market.remove_orders(stock, |order| {
+        match expired.iter().position(|e| e.is(order)) {
+            Some(i) => {
+                expired.swap_remove(i);
+                false
+            },
+            None => true
+        }
+    });
+}
+
+// the replayed trade happens when the journal gets to it, a moment after the original
+fn same_trade(replayed: &Transaction, recorded: &Transaction) -> bool {
+    replayed.buy_id == recorded.buy_id
+        && replayed.sell_id == recorded.sell_id
+        && replayed.price == recorded.price
+        && replayed.volume == recorded.volume
+}
+
+fn pace(speed: ReplaySpeed, started: Instant, elapsed: i64) {
+    let real_nanos = match speed {
+        ReplaySpeed::Original => elapsed as f64,
+        ReplaySpeed::Accelerated { factor } => elapsed as f64 / factor,
+        ReplaySpeed::Max => return
+    };
+    let due = started + Duration::from_nanos(real_nanos.max(0.0) as u64);
+    let now = Instant::now();
+    if due > now {
+        sleep(due - now);
+    }
+}
@@ -0,0 +1,134 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::classes::shared::order::Stock;
+use crate::kernel::agents::registry::{self, RegistrySnapshot};
+use crate::kernel::agents::strategies::value_process::{self, FundamentalValue};
+use crate::kernel::events::{self, ScheduledEvent};
+use crate::kernel::market::{Market, StockRecord};
+
+const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+pub struct ClockSnapshot {
+    pub now: i64,
+    pub simulated_start: Option<DateTime<Utc>>
+}
+
+/// Everything needed to pick a simulation back up: the books with their history and stats,
+/// the agents, the fundamental values, pending events and the clock. Random generators
+/// aren't part of it, a restored market carries on from fresh draws.
+#[derive(Serialize, Deserialize)]
+pub struct MarketSnapshot {
+    pub version: u32,
+    pub clock: ClockSnapshot,
+    pub next_order_id: u64,
+    pub stocks: Vec<StockRecord>,
+    pub agents: RegistrySnapshot,
+    fundamentals: Vec<(Stock, FundamentalValue)>,
+    scheduled_events: Vec<ScheduledEvent>
+}
+
+pub fn take(market: &Market) -> MarketSnapshot {
+    MarketSnapshot {
+        version: SNAPSHOT_VERSION,
+        clock: ClockSnapshot {
+            now: market.clock.now(),
+            simulated_start: market.clock.simulated_start()
+        },
+        next_order_id: market.peek_next_order_id(),
+        stocks: market.snapshot_records(),
+        agents: registry::snapshot(market),
+        fundamentals: value_process::fundamentals(market),
+        scheduled_events: events::scheduled(market)
+    }
+}
+
+/// Writes a snapshot of the market to `path`. It goes to a temporary file first,
+/// so a crash halfway through never leaves a truncated snapshot behind.
+pub fn save(market: &Market, path: &Path) -> io::Result<()> {
+    let snapshot = take(market);
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+
+    let mut writer = BufWriter::new(File::create(&temporary)?);
+    serde_json::to_writer(&mut writer, &snapshot)?;
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    fs::rename(&temporary, path)
+}
+
+/// Resolves a snapshot name given over the API against `dir`. Names are relative and stay
+/// inside it, so a request can't write anywhere else the server could.
+pub fn resolve(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(name);
+    let inside = !name.is_empty() && relative.components().all(|c| matches!(c, Component::Normal(_)));
+    if inside {
+        Ok(dir.join(relative))
+    } else {
+        Err(format!("'{}' is not a snapshot name, use a relative path without '..'", name))
+    }
+}
+
+pub fn load(path: &Path) -> io::Result<MarketSnapshot> {
+    let reader = BufReader::new(File::open(path)?);
+    let snapshot: MarketSnapshot = serde_json::from_reader(reader)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if snapshot.version != SNAPSHOT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("snapshot version {} is not supported, expected {}", snapshot.version, SNAPSHOT_VERSION)
+        ));
+    }
+    Ok(snapshot)
+}
+
+/// Replaces the whole market with the snapshot, returning the stocks it lists
+pub fn restore(market: &Market, snapshot: MarketSnapshot) -> Result<Vec<Stock>, String> {
+    // the agents are the only part that can fail, bring them back before touching anything else
+    registry::restore(market, snapshot.agents)?;
+
+    market.clock.set_now(snapshot.clock.now);
+    if let Some(start) = snapshot.clock.simulated_start {
+        market.clock.set_simulated_start(start);
+    }
+    market.set_next_order_id(snapshot.next_order_id);
+
+    let stocks = snapshot.stocks.iter().map(|r| r.order_book.stock()).collect();
+    market.restore_records(snapshot.stocks);
+    value_process::set_fundamentals(market, snapshot.fundamentals);
+    events::set_scheduled(market, snapshot.scheduled_events);
+    Ok(stocks)
+}
+
+/// Saves the market to the same file every `interval` market nanoseconds, when polled
+pub struct Checkpoint {
+    path: PathBuf,
+    interval: i64,
+    due: i64
+}
+
+impl Checkpoint {
+    pub fn new(path: PathBuf, interval: i64, now: i64) -> Self {
+        Checkpoint {
+            path: path,
+            interval: interval,
+            due: now + interval
+        }
+    }
+
+    pub fn poll(&mut self, market: &Market) {
+        let now = market.clock.now();
+        if now < self.due {
+            return;
+        }
+        if let Err(e) = save(market, &self.path) {
+            eprintln!("checkpoint to {} failed: {}", self.path.display(), e);
+        }
+        self.due = now + self.interval;
+    }
+}
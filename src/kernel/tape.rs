@@ -0,0 +1,89 @@
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::RwLock;
+
+use crate::classes::shared::{order::Stock, transaction::Transaction};
+
+// trades kept per stock unless a scenario says otherwise, about 70MB of them
+pub const DEFAULT_TAPE_LIMIT: usize = 1_000_000;
+
+/// The trades of each stock since it listed, oldest first, up to a limit after which the
+/// oldest are dropped. The books only keep the trades of the current second, and the history
+/// only their bars. Positions count every trade since listing, dropped ones included
+pub struct Tape {
+    trades: RwLock<HashMap<Stock, StockTape>>,
+    limit: AtomicUsize
+}
+
+#[derive(Default)]
+struct StockTape {
+    // trades that were on the tape before the ones it still has
+    dropped: usize,
+    trades: VecDeque<Transaction>
+}
+
+impl Default for Tape {
+    fn default() -> Self {
+        Tape { trades: RwLock::new(HashMap::new()), limit: AtomicUsize::new(DEFAULT_TAPE_LIMIT) }
+    }
+}
+
+impl Tape {
+    /// How many trades each stock keeps, the oldest go first once there are more
+    pub fn set_limit(&self, limit: usize) {
+        self.limit.store(limit, Ordering::SeqCst);
+        for tape in self.trades.write().unwrap().values_mut() {
+            tape.trim(limit);
+        }
+    }
+
+    pub fn append(&self, stock: Stock, transactions: &[Transaction]) {
+        if transactions.is_empty() {
+            return;
+        }
+        let mut trades = self.trades.write().unwrap();
+        let tape = trades.entry(stock).or_default();
+        tape.trades.extend(transactions.iter().copied());
+        tape.trim(self.limit.load(Ordering::SeqCst));
+    }
+
+    pub fn clear(&self, stock: Stock) {
+        self.trades.write().unwrap().remove(&stock);
+    }
+
+    /// How many trades have gone on the tape, whether or not they have been dropped since
+    pub fn len(&self, stock: Stock) -> usize {
+        self.trades.read().unwrap().get(&stock).map_or(0, |tape| tape.dropped + tape.trades.len())
+    }
+
+    /// Positions on the tape of the trades still on it with a timestamp in [from, to)
+    pub fn between(&self, stock: Stock, from: i64, to: i64) -> Range<usize> {
+        let trades = self.trades.read().unwrap();
+        let Some(tape) = trades.get(&stock) else {
+            return 0..0
+        };
+        let start = tape.trades.partition_point(|t| t.timestamp < from);
+        let end = tape.trades.partition_point(|t| t.timestamp < to).max(start);
+        tape.dropped + start..tape.dropped + end
+    }
+
+    /// A copy of part of the tape, without whatever has been dropped or cleared since
+    pub fn slice(&self, stock: Stock, range: Range<usize>) -> Vec<Transaction> {
+        let trades = self.trades.read().unwrap();
+        let Some(tape) = trades.get(&stock) else {
+            return Vec::new()
+        };
+        let end = range.end.saturating_sub(tape.dropped).min(tape.trades.len());
+        let start = range.start.saturating_sub(tape.dropped).min(end);
+        tape.trades.range(start..end).copied().collect()
+    }
+}
+
+impl StockTape {
+    fn trim(&mut self, limit: usize) {
+        let excess = self.trades.len().saturating_sub(limit);
+        self.trades.drain(..excess);
+        self.dropped += excess;
+    }
+}
@@ -2,5 +2,7 @@ pub mod handlers;
 pub mod kernel;
 pub mod globals;
 pub mod classes;
+pub mod scenario;
+pub mod sessions;
 
-pub use globals::{ACCELERATION_PARAMETER, MARKET_EPOCH};
+pub use globals::ACCELERATION_PARAMETER;
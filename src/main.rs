@@ -1,70 +1,237 @@
-use std::thread;
+use std::path::Path;
+use std::sync::Arc;
 
 use actix_cors::Cors;
-use actix_web::{get, post, web, App, Error, HttpResponse, HttpServer, Result};
+use actix_web::{delete, get, post, put, web, App, Error, HttpResponse, HttpServer, Result};
 
 mod kernel;
 mod handlers;
 mod globals;
 mod classes;
+mod scenario;
+mod sessions;
 
-use handlers::api_handler::*;
+use handlers::{admin_handler, api_handler, admin_handler::*, api_handler::*, export_handler::*, session_handler::*};
 use classes::shared::order::*;
 use classes::api::*;
-use kernel::market;
+use kernel::market::Market;
+use scenario::Scenario;
+use sessions::{Sessions, DEFAULT_SESSION};
 
 #[post("/buy")]
-async fn buy(details: web::Json<request_classes::OrderDTO>) -> Result<HttpResponse, Error> {
-    handle_order(details, OrderType::Buy)
+async fn buy(market: SessionMarket, details: web::Json<request_classes::OrderDTO>) -> Result<HttpResponse, Error> {
+    handle_order(market.0, details, OrderType::Buy)
 }
 
 #[post("/sell")]
-async fn sell(details: web::Json<request_classes::OrderDTO>) -> Result<HttpResponse, Error> {
-    handle_order(details, OrderType::Sell)
+async fn sell(market: SessionMarket, details: web::Json<request_classes::OrderDTO>) -> Result<HttpResponse, Error> {
+    handle_order(market.0, details, OrderType::Sell)
 }
 
 #[post("/ipo")]
-async fn ipo(details: web::Json<request_classes::IpoDTO>) -> Result<HttpResponse, Error> {
-    handle_ipo(details)
+async fn ipo(market: SessionMarket, details: web::Json<request_classes::IpoDTO>) -> Result<HttpResponse, Error> {
+    handle_ipo(market.0, details)
 }
 
 #[get("/price")]
-async fn price(query: web::Query<request_classes::StockQuery>) -> Result<HttpResponse, Error> {
-    handle_price(query)
+async fn price(market: SessionMarket, query: web::Query<request_classes::StockQuery>) -> Result<HttpResponse, Error> {
+    handle_price(market.0, query)
+}
+
+#[get("/depth")]
+async fn depth(market: SessionMarket, query: web::Query<request_classes::DepthQuery>) -> Result<HttpResponse, Error> {
+    handle_depth(market.0, query)
+}
+
+#[post("/admin/halt")]
+async fn admin_halt(market: SessionMarket, details: web::Json<request_classes::StockQuery>) -> Result<HttpResponse, Error> {
+    handle_halt(market.0, details)
+}
+
+#[post("/admin/resume")]
+async fn admin_resume(market: SessionMarket, details: web::Json<request_classes::StockQuery>) -> Result<HttpResponse, Error> {
+    handle_resume(market.0, details)
+}
+
+#[post("/admin/price_band")]
+async fn admin_price_band(market: SessionMarket, details: web::Json<request_classes::PriceBandDTO>) -> Result<HttpResponse, Error> {
+    handle_price_band(market.0, details)
+}
+
+#[get("/admin/agents")]
+async fn list_agents(market: SessionMarket) -> Result<HttpResponse, Error> {
+    handle_list_agents(market.0)
+}
+
+#[post("/admin/agents")]
+async fn add_agent(market: SessionMarket, details: web::Json<request_classes::AgentDTO>) -> Result<HttpResponse, Error> {
+    handle_add_agent(market.0, details)
+}
+
+#[put("/admin/agents/{id}")]
+async fn configure_agent(market: SessionMarket, id: web::Path<u64>, details: web::Json<request_classes::AgentParamsDTO>) -> Result<HttpResponse, Error> {
+    handle_configure_agent(market.0, id, details)
+}
+
+#[delete("/admin/agents/{id}")]
+async fn remove_agent(market: SessionMarket, id: web::Path<u64>) -> Result<HttpResponse, Error> {
+    handle_remove_agent(market.0, id)
+}
+
+#[get("/session")]
+async fn session(market: SessionMarket, query: web::Query<request_classes::StockQuery>) -> Result<HttpResponse, Error> {
+    handle_session(market.0, query)
+}
+
+#[post("/admin/events")]
+async fn admin_events(market: SessionMarket, details: web::Json<Vec<request_classes::EventDTO>>) -> Result<HttpResponse, Error> {
+    admin_handler::handle_events(market.0, details)
+}
+
+#[post("/admin/snapshot")]
+async fn admin_snapshot(market: SessionMarket, sessions: web::Data<Sessions>, details: web::Json<request_classes::SnapshotDTO>) -> Result<HttpResponse, Error> {
+    handle_snapshot(market.0, &sessions.snapshot_dir(&market.1), details)
+}
+
+#[get("/admin/replay")]
+async fn admin_replay(market: SessionMarket) -> Result<HttpResponse, Error> {
+    handle_replay_status(market.0)
+}
+
+#[post("/oco")]
+async fn oco(market: SessionMarket, details: web::Json<request_classes::OcoDTO>) -> Result<HttpResponse, Error> {
+    handle_oco(market.0, details)
+}
+
+#[post("/bracket")]
+async fn bracket(market: SessionMarket, details: web::Json<request_classes::BracketDTO>) -> Result<HttpResponse, Error> {
+    handle_bracket(market.0, details)
+}
+
+#[get("/group/{id}")]
+async fn group_status(market: SessionMarket, id: web::Path<u64>) -> Result<HttpResponse, Error> {
+    handle_group_status(market.0, id)
+}
+
+#[delete("/group/{id}")]
+async fn cancel_group(market: SessionMarket, id: web::Path<u64>) -> Result<HttpResponse, Error> {
+    handle_cancel_group(market.0, id)
+}
+
+#[post("/algo")]
+async fn algo(market: SessionMarket, details: web::Json<request_classes::AlgoDTO>) -> Result<HttpResponse, Error> {
+    handle_algo(market.0, details)
+}
+
+#[get("/algo/{id}")]
+async fn algo_status(market: SessionMarket, id: web::Path<u64>) -> Result<HttpResponse, Error> {
+    handle_algo_status(market.0, id)
+}
+
+#[delete("/algo/{id}")]
+async fn cancel_algo(market: SessionMarket, id: web::Path<u64>) -> Result<HttpResponse, Error> {
+    handle_cancel_algo(market.0, id)
+}
+
+#[get("/events")]
+async fn events(market: SessionMarket, query: web::Query<request_classes::StockQuery>) -> Result<HttpResponse, Error> {
+    api_handler::handle_events(market.0, query)
+}
+
+#[get("/export/trades")]
+async fn export_trades(market: SessionMarket, query: web::Query<request_classes::ExportQuery>) -> Result<HttpResponse, Error> {
+    handle_export_trades(market.0, query)
+}
+
+#[get("/export/candles")]
+async fn export_candles(market: SessionMarket, query: web::Query<request_classes::ExportQuery>) -> Result<HttpResponse, Error> {
+    handle_export_candles(market.0, query)
 }
 
 #[get("/stock_history")]
-async fn stock_history(details: web::Json<request_classes::PriceHistoryDTO>) -> Result<HttpResponse, Error> {
-   handle_stock_history(details)
+async fn stock_history(market: SessionMarket, details: web::Json<request_classes::PriceHistoryDTO>) -> Result<HttpResponse, Error> {
+   handle_stock_history(market.0, details)
+}
+
+#[get("/admin/sessions")]
+async fn list_sessions(sessions: web::Data<Sessions>) -> Result<HttpResponse, Error> {
+    handle_list_sessions(sessions)
+}
+
+#[post("/admin/sessions")]
+async fn create_session(sessions: web::Data<Sessions>, details: web::Json<request_classes::NewSessionDTO>) -> Result<HttpResponse, Error> {
+    handle_create_session(sessions, details)
+}
+
+#[delete("/admin/sessions/{name}")]
+async fn remove_session(sessions: web::Data<Sessions>, name: web::Path<String>) -> Result<HttpResponse, Error> {
+    handle_remove_session(sessions, name)
+}
+
+// every route that trades on a market, served for the default session at the root and for each session under /sessions/{name}
+fn market_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(buy)
+        .service(sell)
+        .service(ipo)
+        .service(price)
+        .service(depth)
+        .service(oco)
+        .service(bracket)
+        .service(group_status)
+        .service(cancel_group)
+        .service(algo)
+        .service(algo_status)
+        .service(cancel_algo)
+        .service(session)
+        .service(admin_halt)
+        .service(admin_resume)
+        .service(admin_price_band)
+        .service(list_agents)
+        .service(add_agent)
+        .service(configure_agent)
+        .service(remove_agent)
+        .service(admin_events)
+        .service(admin_snapshot)
+        .service(admin_replay)
+        .service(events)
+        .service(stock_history)
+        .service(export_trades)
+        .service(export_candles);
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    use Stock::*;
-    
-    let stock_list = vec![MSFT];
-    for stock in stock_list {
-        market::ipo(stock, 1, 10.0, None);
-
-        thread::spawn(move || { 
-            // println!("Started digesst for {:?}", stock);
-            kernel::agents::digest_cycle::make_market(stock);
-        });
-    }
-
-    HttpServer::new(|| {
+    // an optional scenario file as the first argument, the built in market otherwise
+    let scenario = match std::env::args().nth(1) {
+        Some(path) => Scenario::load(Path::new(&path)).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }),
+        None => Scenario::default()
+    };
+    let market = Arc::new(Market::new());
+    scenario.start(&market)?;
+
+    let sessions = web::Data::new(Sessions::new(scenario.snapshot_dir.clone().into()));
+    sessions.insert(DEFAULT_SESSION, market);
+
+    let data = sessions.clone();
+    HttpServer::new(move || {
         App::new()
+            .app_data(data.clone())
             .wrap(
                 Cors::default()
                     .allowed_origin("http://localhost:*")
             )
-            .service(buy)
-            .service(sell)
-            .service(ipo)
-            .service(price)
-            .service(stock_history)
+            .service(list_sessions)
+            .service(create_session)
+            .service(remove_session)
+            .configure(market_routes)
+            .service(web::scope("/sessions/{name}").configure(market_routes))
     })
-    .bind(("127.0.0.1", 8080))?
+    .bind(scenario.bind_address().unwrap())?
     .run()
-    .await
-}
\ No newline at end of file
+    .await?;
+
+    sessions.remove_all()
+}
@@ -0,0 +1,594 @@
+use std::{fs, io, net::SocketAddr, path::{Path, PathBuf}, sync::Arc, thread::{self, JoinHandle}, time::Duration};
+
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::classes::api::request_classes::STOCKMAP;
+use crate::classes::shared::{order::Stock, transaction::Transaction};
+use crate::kernel::agents::{agent::Agent, digest_cycle::{self, DeterministicCycle}, registry};
+use crate::kernel::events::{self, MarketEvent};
+use crate::kernel::history_import;
+use crate::kernel::journal::{self, FsyncPolicy};
+use crate::kernel::market::Market;
+use crate::kernel::replay::{self, ReplaySpeed};
+use crate::kernel::snapshot::{self, Checkpoint};
+use crate::kernel::tape::DEFAULT_TAPE_LIMIT;
+use crate::kernel::market_time::session::*;
+use crate::kernel::order_book::book::SelfTradePrevention;
+use crate::kernel::order_book::circuit_breaker::PriceBand;
+use crate::globals::ACCELERATION_PARAMETER;
+use crate::sessions::DEFAULT_SNAPSHOT_DIR;
+
+/// Everything needed to bring up a simulation: the instruments, who trades them,
+/// how fast the clock runs and where the API listens
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default = "default_bind")]
+    pub bind: String,
+    #[serde(default)]
+    pub clock: ClockConfig,
+    // seeds every agent and value process not given a seed of its own
+    #[serde(default)]
+    pub seed: Option<u64>,
+    // where to journal every order, cancel, expiry, halt and trade, nowhere by default
+    #[serde(default)]
+    pub journal: Option<JournalConfig>,
+    // a snapshot to carry on from, the instruments and agents then come from it
+    #[serde(default)]
+    pub restore: Option<String>,
+    // where and how often to snapshot the market, never by default
+    #[serde(default)]
+    pub checkpoint: Option<CheckpointConfig>,
+    // the directory POST /admin/snapshot writes into, the names it is given can't leave it
+    #[serde(default = "default_snapshot_dir")]
+    pub snapshot_dir: String,
+    // trades each stock keeps for /export/trades, the oldest are dropped past it
+    #[serde(default = "default_tape_limit")]
+    pub tape_limit: usize,
+    // a recorded journal to play back instead of running agents
+    #[serde(default)]
+    pub replay: Option<ReplayConfig>,
+    #[serde(default)]
+    pub instruments: Vec<InstrumentConfig>
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClockMode {
+    // market time follows the wall clock, sped up by the acceleration
+    #[default]
+    Realtime,
+    // a single threaded event loop on a virtual clock, reproducible for a given seed
+    Deterministic
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ClockConfig {
+    #[serde(default)]
+    pub mode: ClockMode,
+    // simulated seconds per real second
+    #[serde(default = "default_acceleration")]
+    pub acceleration: f64,
+    // RFC 3339 simulated date and time the market starts at, now by default
+    // (midnight on Monday 1 January 2024 in deterministic mode)
+    #[serde(default)]
+    pub start: Option<String>,
+    // deterministic mode only, simulated seconds each tick of the event loop moves the clock by
+    #[serde(default)]
+    pub tick_seconds: Option<f64>,
+    // deterministic mode only, how much simulated time to run for, None runs forever
+    #[serde(default)]
+    pub duration_seconds: Option<f64>
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct JournalConfig {
+    pub path: String,
+    #[serde(default)]
+    pub fsync: FsyncPolicy
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CheckpointConfig {
+    pub path: String,
+    // simulated seconds between snapshots
+    pub interval_seconds: f64
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ReplayConfig {
+    pub journal: String,
+    #[serde(default)]
+    pub speed: ReplaySpeed
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct InstrumentConfig {
+    pub symbol: String,
+    pub ipo_size: u64,
+    // not needed with a history, the last close is the listing price
+    #[serde(default)]
+    pub ipo_price: Option<f64>,
+    // a CSV file of OHLCV bars to seed the stock's history with
+    #[serde(default)]
+    pub history: Option<String>,
+    #[serde(default)]
+    pub tick_size: Option<f64>,
+    // None trades continuously around the clock
+    #[serde(default)]
+    pub session: Option<SessionConfig>,
+    #[serde(default)]
+    pub price_band: Option<PriceBandConfig>,
+    // None lets an owner's orders trade with each other
+    #[serde(default)]
+    pub self_trade_prevention: Option<SelfTradePrevention>,
+    // None runs the default population
+    #[serde(default)]
+    pub agents: Option<Vec<AgentConfig>>,
+    #[serde(default)]
+    pub events: Vec<ScenarioEvent>
+}
+
+/// Times of day as "HH:MM" or "HH:MM:SS" in simulated UTC, holidays as "YYYY-MM-DD"
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+    pub pre_open: String,
+    pub opening_auction: String,
+    pub continuous: String,
+    pub closing_auction: String,
+    pub close: String,
+    pub trade_weekends: bool,
+    pub holidays: Vec<String>
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PriceBandConfig {
+    pub band: f64,
+    pub halt_seconds: f64,
+    // the reference is the average trade over this window, the last trade without one
+    #[serde(default)]
+    pub window_seconds: Option<f64>
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AgentConfig {
+    pub kind: String,
+    #[serde(default)]
+    pub params: Value,
+    #[serde(default = "default_count")]
+    pub count: usize
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ScenarioEvent {
+    #[serde(flatten)]
+    pub event: MarketEvent,
+    // simulated seconds after the start
+    #[serde(default)]
+    pub delay_seconds: f64
+}
+
+fn default_bind() -> String {
+    "127.0.0.1:8080".to_string()
+}
+
+fn default_tape_limit() -> usize {
+    DEFAULT_TAPE_LIMIT
+}
+
+fn default_snapshot_dir() -> String {
+    DEFAULT_SNAPSHOT_DIR.to_string()
+}
+
+fn default_acceleration() -> f64 {
+    ACCELERATION_PARAMETER
+}
+
+fn default_count() -> usize {
+    1
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        ClockConfig {
+            mode: ClockMode::default(),
+            acceleration: default_acceleration(),
+            start: None,
+            tick_seconds: None,
+            duration_seconds: None
+        }
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            pre_open: "08:00".to_string(),
+            opening_auction: "09:25".to_string(),
+            continuous: "09:30".to_string(),
+            closing_auction: "16:00".to_string(),
+            close: "16:05".to_string(),
+            trade_weekends: false,
+            holidays: Vec::new()
+        }
+    }
+}
+
+impl Default for Scenario {
+    // what the server runs without a scenario file
+    fn default() -> Self {
+        Scenario {
+            bind: default_bind(),
+            clock: ClockConfig::default(),
+            seed: None,
+            journal: None,
+            restore: None,
+            checkpoint: None,
+            snapshot_dir: default_snapshot_dir(),
+            tape_limit: DEFAULT_TAPE_LIMIT,
+            replay: None,
+            instruments: vec![InstrumentConfig {
+                symbol: "MSFT".to_string(),
+                ipo_size: 1,
+                ipo_price: Some(10.0),
+                history: None,
+                tick_size: None,
+                session: None,
+                price_band: None,
+                self_trade_prevention: None,
+                agents: None,
+                events: Vec::new()
+            }]
+        }
+    }
+}
+
+impl ClockConfig {
+    pub fn start_datetime(&self) -> Result<Option<DateTime<Utc>>, String> {
+        match (&self.start, self.mode) {
+            (Some(start), _) => DateTime::parse_from_rfc3339(start)
+                .map(|t| Some(t.with_timezone(&Utc)))
+                .map_err(|_| format!("start '{}' is not an RFC 3339 date time", start)),
+            (None, ClockMode::Deterministic) => Ok(Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())),
+            (None, ClockMode::Realtime) => Ok(None)
+        }
+    }
+
+    /// Market nanoseconds each tick of the deterministic event loop moves the clock by
+    pub fn tick_nanos(&self) -> i64 {
+        self.tick_seconds
+            .map(events::seconds_to_nanos)
+            .unwrap_or(DeterministicCycle::default_tick_nanos())
+    }
+
+    fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if !(self.acceleration > 0.0 && self.acceleration.is_finite()) {
+            errors.push("acceleration must be positive".to_string());
+        }
+        if let Err(e) = self.start_datetime() {
+            errors.push(e);
+        }
+        for (field, value) in [("tick_seconds", self.tick_seconds), ("duration_seconds", self.duration_seconds)] {
+            match (value, self.mode) {
+                (Some(_), ClockMode::Realtime) => errors.push(format!("{} only applies in deterministic mode", field)),
+                (Some(v), _) if !(v > 0.0 && v.is_finite()) => errors.push(format!("{} must be positive", field)),
+                _ => {}
+            }
+        }
+        errors
+    }
+}
+
+impl SessionConfig {
+    pub fn schedule(&self) -> Result<SessionSchedule, String> {
+        let pre_open = parse_time("pre_open", &self.pre_open)?;
+        let opening_auction = parse_time("opening_auction", &self.opening_auction)?;
+        let continuous = parse_time("continuous", &self.continuous)?;
+        let closing_auction = parse_time("closing_auction", &self.closing_auction)?;
+        let close = parse_time("close", &self.close)?;
+        if !(pre_open <= opening_auction && opening_auction <= continuous && continuous < closing_auction && closing_auction <= close) {
+            return Err("session times must run pre_open <= opening_auction <= continuous < closing_auction <= close".to_string());
+        }
+
+        let holidays = self.holidays.iter()
+            .map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|_| format!("holiday '{}' is not a YYYY-MM-DD date", d)))
+            .collect::<Result<Vec<NaiveDate>, String>>()?;
+
+        Ok(SessionSchedule {
+            pre_open: pre_open,
+            opening_auction: opening_auction,
+            continuous: continuous,
+            closing_auction: closing_auction,
+            close: close,
+            calendar: TradingCalendar {
+                trade_weekends: self.trade_weekends,
+                holidays: holidays
+            }
+        })
+    }
+}
+
+fn parse_time(field: &str, time: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(time, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+        .map_err(|_| format!("{} '{}' is not a HH:MM time", field, time))
+}
+
+impl PriceBandConfig {
+    pub fn price_band(&self) -> PriceBand {
+        PriceBand {
+            band: self.band,
+            halt_nanos: events::seconds_to_nanos(self.halt_seconds),
+            window_nanos: self.window_seconds.map(events::seconds_to_nanos)
+        }
+    }
+}
+
+impl InstrumentConfig {
+    pub fn stock(&self) -> Option<Stock> {
+        STOCKMAP.get(&self.symbol).copied()
+    }
+
+    pub fn build_agents(&self) -> Result<Vec<Box<dyn Agent>>, String> {
+        let Some(configs) = &self.agents else {
+            return Ok(digest_cycle::default_agents())
+        };
+        let mut agents = Vec::new();
+        for (i, config) in configs.iter().enumerate() {
+            for _ in 0..config.count {
+                let agent = registry::create_agent(&config.kind, &config.params)
+                    .map_err(|e| format!("agents[{}] ({}): {}", i, config.kind, e))?;
+                agents.push(agent);
+            }
+        }
+        Ok(agents)
+    }
+
+    fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.stock().is_none() {
+            let known: Vec<&str> = STOCKMAP.keys().copied().collect();
+            errors.push(format!("unknown symbol, expected one of {}", known.join(", ")));
+        }
+        if self.ipo_size == 0 {
+            errors.push("ipo_size must be positive".to_string());
+        }
+        match (self.ipo_price, &self.history) {
+            (None, None) => errors.push("ipo_price is needed without a history".to_string()),
+            (Some(_), Some(_)) => errors.push("ipo_price comes from the last bar of the history".to_string()),
+            (Some(price), None) if !(price > 0.0 && price.is_finite()) => errors.push("ipo_price must be positive".to_string()),
+            (None, Some(path)) => if let Err(e) = history_import::read(Path::new(path)) {
+                errors.push(format!("history: {}", e.replace('\n', "\n    ")));
+            },
+            _ => {}
+        }
+        if matches!(self.tick_size, Some(tick) if !(tick > 0.0 && tick.is_finite())) {
+            errors.push("tick_size must be positive".to_string());
+        }
+        if let Some(Err(e)) = self.session.as_ref().map(|s| s.schedule()) {
+            errors.push(format!("session: {}", e));
+        }
+        if let Some(Err(e)) = self.price_band.as_ref().map(|band| band.price_band().validate()) {
+            errors.push(format!("price_band: {}", e));
+        }
+        if let Some(configs) = &self.agents {
+            for (i, config) in configs.iter().enumerate() {
+                if config.count == 0 {
+                    errors.push(format!("agents[{}] ({}): count must be positive", i, config.kind));
+                }
+            }
+        }
+        if let Err(e) = self.build_agents() {
+            errors.push(e);
+        }
+        for (i, e) in self.events.iter().enumerate() {
+            if let Err(err) = e.event.validate() {
+                errors.push(format!("events[{}]: {}", i, err));
+            } else if e.delay_seconds < 0.0 {
+                errors.push(format!("events[{}]: delay_seconds must not be negative", i));
+            }
+        }
+        errors
+    }
+}
+
+impl Scenario {
+    /// Reads a scenario from a .toml or .json file and checks it, every problem found is listed in the error
+    pub fn load(path: &Path) -> Result<Scenario, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("could not read scenario {}: {}", path.display(), e))?;
+
+        let is_toml = path.extension().is_some_and(|ext| ext == "toml");
+        let parsed = if is_toml {
+            toml::from_str::<Scenario>(&contents).map_err(|e| e.to_string())
+        } else {
+            serde_json::from_str::<Scenario>(&contents).map_err(|e| e.to_string())
+        };
+        let scenario = parsed.map_err(|e| format!("invalid scenario {}: {}", path.display(), e))?;
+
+        scenario.validate().map_err(|e| format!("invalid scenario {}:\n{}", path.display(), e))?;
+        Ok(scenario)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        if self.bind_address().is_err() {
+            errors.push(format!("bind: '{}' is not a host:port address", self.bind));
+        }
+        errors.extend(self.clock.errors().into_iter().map(|e| format!("clock: {}", e)));
+        if self.tape_limit == 0 {
+            errors.push("tape_limit must be positive".to_string());
+        }
+        if matches!(&self.journal, Some(config) if config.path.is_empty()) {
+            errors.push("journal: path must not be empty".to_string());
+        }
+        match (&self.restore, &self.replay, self.instruments.is_empty()) {
+            (Some(path), _, _) if path.is_empty() => errors.push("restore: path must not be empty".to_string()),
+            (Some(_), Some(_), _) => errors.push("restore: a replay starts from its journal, not a snapshot".to_string()),
+            (Some(_), _, false) => errors.push("instruments: the instruments come from the snapshot when restoring".to_string()),
+            (_, Some(_), false) => errors.push("instruments: the instruments come from the journal when replaying".to_string()),
+            (None, None, true) => errors.push("instruments: at least one instrument is needed".to_string()),
+            _ => {}
+        }
+        if let Some(config) = &self.replay {
+            if config.journal.is_empty() {
+                errors.push("replay: journal must not be empty".to_string());
+            }
+            if let Err(e) = config.speed.validate() {
+                errors.push(format!("replay: speed: {}", e));
+            }
+            if self.journal.is_some() || self.checkpoint.is_some() {
+                errors.push("replay: a replay can't be journaled or checkpointed".to_string());
+            }
+        }
+        if let Some(config) = &self.checkpoint {
+            if config.path.is_empty() {
+                errors.push("checkpoint: path must not be empty".to_string());
+            }
+            if !(config.interval_seconds > 0.0 && config.interval_seconds.is_finite()) {
+                errors.push("checkpoint: interval_seconds must be positive".to_string());
+            }
+        }
+        for (i, instrument) in self.instruments.iter().enumerate() {
+            let context = format!("instruments[{}] ({})", i, instrument.symbol);
+            if self.instruments[..i].iter().any(|other| other.symbol == instrument.symbol) {
+                errors.push(format!("{}: listed more than once", context));
+            }
+            errors.extend(instrument.errors().into_iter().map(|e| format!("{}: {}", context, e)));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.iter().map(|e| format!("  - {}", e)).collect::<Vec<String>>().join("\n"))
+        }
+    }
+
+    pub fn bind_address(&self) -> Result<SocketAddr, std::net::AddrParseError> {
+        self.bind.parse()
+    }
+
+    /// Sets the clock, opens the journal, lists the instruments (or restores the snapshot) and starts
+    /// their markets, the scenario must have been validated. In deterministic mode this hands back the event loop's thread, which
+    /// returns the trade tape once `duration_seconds` of simulated time have passed.
+    pub fn start(&self, market: &Arc<Market>) -> io::Result<Option<JoinHandle<Vec<Transaction>>>> {
+        // the replay runs the clock itself, from the journal's timestamps
+        if let Some(config) = &self.replay {
+            market.seeds.set(self.seed);
+            market.tape.set_limit(self.tape_limit);
+            if let Some(start) = self.clock.start_datetime().unwrap() {
+                market.clock.set_simulated_start(start);
+            }
+            let records = journal::read(Path::new(&config.journal))?;
+            let speed = config.speed;
+            let market = Arc::clone(market);
+            thread::spawn(move || {
+                let report = replay::run(&market, &records, speed);
+                println!(
+                    "replay finished: {} of {} trades matched, {} mismatched, {} unrecorded",
+                    report.matched, report.trades, report.mismatched, report.unrecorded
+                );
+            });
+            return Ok(None);
+        }
+        let stocks = self.prepare(market)?;
+
+        let checkpoint = self.checkpoint.as_ref()
+            .map(|c| Checkpoint::new(PathBuf::from(&c.path), events::seconds_to_nanos(c.interval_seconds), market.clock.now()));
+        if self.clock.mode == ClockMode::Realtime {
+            if let Some(mut checkpoint) = checkpoint {
+                let market = Arc::clone(market);
+                thread::spawn(move || {
+                    while !market.is_stopped() {
+                        thread::sleep(Duration::from_millis(100));
+                        checkpoint.poll(&market);
+                    }
+                });
+            }
+            return Ok(None);
+        }
+        let tick_nanos = self.clock.tick_nanos();
+        let end = self.clock.duration_seconds.map(|d| market.clock.now() + events::seconds_to_nanos(d));
+        let market = Arc::clone(market);
+        Ok(Some(thread::spawn(move || {
+            let mut cycle = DeterministicCycle::new(market, stocks, tick_nanos);
+            if let Some(checkpoint) = checkpoint {
+                cycle.set_checkpoint(checkpoint);
+            }
+            match end {
+                Some(end) => cycle.run_until(end),
+                None => {
+                    cycle.run();
+                    Vec::new()
+                }
+            }
+        })))
+    }
+
+    /// Sets the clock, opens the journal and lists the instruments (or restores the snapshot), handing back
+    /// the market's stocks. In realtime mode they start trading straight away, in deterministic mode nothing
+    /// moves until a DeterministicCycle steps them. Replays are set up by start alone.
+    pub fn prepare(&self, market: &Arc<Market>) -> io::Result<Vec<Stock>> {
+        if let Some(config) = &self.journal {
+            market.journal.open(Path::new(&config.path), config.fsync)?;
+        }
+        market.seeds.set(self.seed);
+        market.tape.set_limit(self.tape_limit);
+        if let Some(start) = self.clock.start_datetime().unwrap() {
+            market.clock.set_simulated_start(start);
+        }
+        match self.clock.mode {
+            ClockMode::Realtime => market.clock.set_acceleration(self.clock.acceleration),
+            ClockMode::Deterministic => market.clock.use_virtual_clock(0)
+        }
+
+        let mut stocks = Vec::new();
+        if let Some(path) = &self.restore {
+            let snapshot = snapshot::load(Path::new(path))?;
+            stocks = snapshot::restore(market, snapshot).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            if self.clock.mode == ClockMode::Realtime {
+                for &stock in &stocks {
+                    digest_cycle::run_market(market, stock);
+                }
+            }
+        }
+        for instrument in &self.instruments {
+            let stock = instrument.stock().unwrap();
+            match &instrument.history {
+                Some(path) => {
+                    let history = history_import::read(Path::new(path))
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    market.ipo(stock, instrument.ipo_size, history.last_close(), None);
+                    history_import::seed(market, stock, &history);
+                },
+                None => market.ipo(stock, instrument.ipo_size, instrument.ipo_price.unwrap(), None)
+            }
+            market.set_tick_size(stock, instrument.tick_size);
+            market.set_schedule(stock, instrument.session.as_ref().map(|s| s.schedule().unwrap()));
+            market.set_price_band(stock, instrument.price_band.as_ref().map(|b| b.price_band()));
+            market.set_self_trade_prevention(stock, instrument.self_trade_prevention);
+            for e in &instrument.events {
+                events::schedule(market, stock, e.event, e.delay_seconds);
+            }
+
+            let agents = instrument.build_agents().unwrap();
+            match self.clock.mode {
+                ClockMode::Realtime => digest_cycle::make_market(market, stock, agents),
+                ClockMode::Deterministic => digest_cycle::populate(market, stock, agents)
+            }
+            stocks.push(stock);
+        }
+        Ok(stocks)
+    }
+}
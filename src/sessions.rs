@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+
+use crate::classes::shared::transaction::Transaction;
+use crate::kernel::market::Market;
+use crate::scenario::Scenario;
+
+// the market the server starts with, the routes outside /sessions/{name} trade on it
+pub const DEFAULT_SESSION: &str = "default";
+pub const DEFAULT_SNAPSHOT_DIR: &str = "snapshots";
+
+/// The markets a server hosts, each known by name and sharing nothing with the others
+pub struct Sessions {
+    markets: RwLock<BTreeMap<String, Session>>,
+    // where snapshots asked for over the API are written, a directory per session
+    snapshot_dir: PathBuf
+}
+
+struct Session {
+    market: Arc<Market>,
+    // a deterministic session's event loop, joined once the market is shut down
+    event_loop: Option<JoinHandle<Vec<Transaction>>>
+}
+
+impl Default for Sessions {
+    fn default() -> Self {
+        Sessions::new(PathBuf::from(DEFAULT_SNAPSHOT_DIR))
+    }
+}
+
+impl Sessions {
+    pub fn new(snapshot_dir: PathBuf) -> Self {
+        Sessions { markets: RwLock::new(BTreeMap::new()), snapshot_dir: snapshot_dir }
+    }
+
+    /// The directory a session's snapshots go in, sessions never share one
+    pub fn snapshot_dir(&self, name: &str) -> PathBuf {
+        self.snapshot_dir.join(name)
+    }
+
+    /// Brings up a new market from a scenario under `name`. It runs inside the server, so the scenario
+    /// can't name files to read or write, nor an address or snapshot directory of its own.
+    pub fn create(&self, name: &str, scenario: &Scenario) -> Result<Arc<Market>, String> {
+        validate_name(name)?;
+        validate_hosted(scenario)?;
+        scenario.validate()?;
+        // held throughout, so two sessions of the same name can't both start
+        let mut markets = self.markets.write().unwrap();
+        if markets.contains_key(name) {
+            return Err(format!("session '{}' already exists", name));
+        }
+        let market = Arc::new(Market::new());
+        let event_loop = match scenario.start(&market) {
+            Ok(event_loop) => event_loop,
+            Err(e) => {
+                let _ = market.shut_down();
+                return Err(format!("session '{}' could not start: {}", name, e));
+            }
+        };
+        markets.insert(name.to_string(), Session { market: market.clone(), event_loop: event_loop });
+        Ok(market)
+    }
+
+    /// Hosts a market that is already running
+    pub fn insert(&self, name: &str, market: Arc<Market>) {
+        self.markets.write().unwrap().insert(name.to_string(), Session { market: market, event_loop: None });
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<Market>> {
+        self.markets.read().unwrap().get(name).map(|session| session.market.clone())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.markets.read().unwrap().keys().cloned().collect()
+    }
+
+    /// Shuts a session's market down, waits for its event loop to stop and forgets it, None if there is no such session
+    pub fn remove(&self, name: &str) -> Option<io::Result<()>> {
+        let session = self.markets.write().unwrap().remove(name)?;
+        Some(session.stop())
+    }
+
+    /// Shuts every session down, the first journal that fails to close is reported
+    pub fn remove_all(&self) -> io::Result<()> {
+        let markets = std::mem::take(&mut *self.markets.write().unwrap());
+        let mut result = Ok(());
+        for session in markets.into_values() {
+            let closed = session.stop();
+            if result.is_ok() {
+                result = closed;
+            }
+        }
+        result
+    }
+}
+
+impl Session {
+    fn stop(self) -> io::Result<()> {
+        let closed = self.market.shut_down();
+        // the loop checks for the shutdown every step, so this doesn't wait long
+        if let Some(event_loop) = self.event_loop {
+            if event_loop.join().is_err() {
+                return Err(io::Error::other("the session's event loop panicked"));
+            }
+        }
+        closed
+    }
+}
+
+// a hosted session shares the server's files and address, so it may only simulate
+fn validate_hosted(scenario: &Scenario) -> Result<(), String> {
+    let defaults = Scenario::default();
+    let mut refused = Vec::new();
+    if scenario.bind != defaults.bind {
+        refused.push("bind".to_string());
+    }
+    if scenario.snapshot_dir != defaults.snapshot_dir {
+        refused.push("snapshot_dir".to_string());
+    }
+    for (field, set) in [
+        ("journal", scenario.journal.is_some()),
+        ("restore", scenario.restore.is_some()),
+        ("checkpoint", scenario.checkpoint.is_some()),
+        ("replay", scenario.replay.is_some())
+    ] {
+        if set {
+            refused.push(field.to_string());
+        }
+    }
+    for instrument in scenario.instruments.iter().filter(|instrument| instrument.history.is_some()) {
+        refused.push(format!("instruments ({}): history", instrument.symbol));
+    }
+
+    if refused.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("a session runs inside the server and can't set {}", refused.join(", ")))
+    }
+}
+
+fn validate_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("'{}' is not a session name, use up to 64 letters, digits, '-' or '_'", name))
+    }
+}
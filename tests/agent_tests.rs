@@ -0,0 +1,384 @@
+use fssm::classes::shared::order::{OrderType, Stock};
+use fssm::kernel::market::Market;
+use fssm::kernel::agents::{agent::*, registry};
+use fssm::kernel::agents::trend::{chaotic_trend_generator::*, market_maker::*};
+use fssm::kernel::agents::population::{arrivals::*, hawkes::*};
+use fssm::kernel::agents::strategies::{chartist::*, value_process::*};
+use fssm::kernel::order_book::record::ObStat;
+use fssm::globals::GRANULARITY;
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    struct CountingAgent {
+        ticks: u64
+    }
+
+    impl Agent for CountingAgent {
+        fn kind(&self) -> &'static str {
+            "counting"
+        }
+
+        fn on_tick(&mut self, _ctx: &AgentContext) {
+            self.ticks += 1;
+        }
+
+        fn config(&self) -> Value {
+            json!({ "ticks": self.ticks })
+        }
+
+        fn configure(&mut self, _params: &Value) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn registry_ticks_and_removes_agents() {
+        let market = Market::new();
+        // GOOGL isn't listed here, ticking the counting agent never touches the market
+        let id = registry::add(&market, Stock::GOOGL, Box::new(CountingAgent { ticks: 0 }));
+        registry::tick(&market, Stock::GOOGL);
+        registry::tick(&market, Stock::GOOGL);
+
+        let info = registry::info(&market, id).expect("Expected the agent to be registered");
+        assert_eq!(info.kind, "counting");
+        assert_eq!(info.config["ticks"], 2);
+        assert!(registry::list(&market).iter().any(|a| a.id == id));
+
+        assert!(registry::remove(&market, id));
+        assert!(registry::info(&market, id).is_none());
+        assert!(!registry::remove(&market, id), "Expected removing twice to fail");
+    }
+
+    // buys once on its first tick and counts what it gets filled
+    struct FillingAgent {
+        placed: bool,
+        filled: std::sync::Arc<std::sync::atomic::AtomicU64>
+    }
+
+    impl Agent for FillingAgent {
+        fn kind(&self) -> &'static str {
+            "filling"
+        }
+
+        fn on_tick(&mut self, ctx: &AgentContext) {
+            if !self.placed {
+                self.placed = true;
+                ctx.buy(5, Some(10.0), None);
+            }
+        }
+
+        fn on_fill(&mut self, _ctx: &AgentContext, fill: &Fill) {
+            self.filled.fetch_add(fill.volume, std::sync::atomic::Ordering::SeqCst);
+        }
+
+        fn config(&self) -> Value {
+            Value::Null
+        }
+
+        fn configure(&mut self, _params: &Value) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn fills_reach_the_agent_after_its_order_ages_out() {
+        let second = GRANULARITY::SECOND as i64;
+        let market = Market::new();
+        let stock = Stock::AAPL;
+        market.clock.use_virtual_clock(0);
+        market.ipo(stock, 1, 10.0, None);
+
+        let filled = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0));
+        registry::add(&market, stock, Box::new(FillingAgent { placed: false, filled: filled.clone() }));
+        registry::tick(&market, stock);
+
+        // the order fills just before the owner horizon, in a second that isn't over when the next report runs
+        market.clock.set_now(second * 5 / 2);
+        market.sell(stock, 5, Some(10.0), None, None);
+        market.find_trades(stock);
+        market.clock.set_now(second * 7 / 2);
+        let reported = market.report_transactions(stock);
+        registry::publish(&market, stock, &reported);
+        assert_eq!(filled.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+        // a later trade closes the second and the fill goes out
+        market.clock.set_now(second * 9 / 2);
+        market.buy(stock, 1, None, None, None);
+        market.find_trades(stock);
+        let reported = market.report_transactions(stock);
+        registry::publish(&market, stock, &reported);
+        assert_eq!(filled.load(std::sync::atomic::Ordering::SeqCst), 5, "Expected the fill to reach the agent");
+    }
+
+    #[test]
+    fn create_agent_applies_params() {
+        let agent = registry::create_agent("straddle", &json!({ "levels": 5 })).unwrap();
+        assert_eq!(agent.kind(), "straddle");
+        assert_eq!(agent.config()["levels"], 5);
+        assert_eq!(agent.config()["level_gap"], 0.01, "Expected unset params to keep their defaults");
+    }
+
+    #[test]
+    fn create_agent_rejects_bad_input() {
+        assert!(registry::create_agent("nonsense", &Value::Null).is_err());
+        assert!(registry::create_agent("straddle", &json!({ "not_a_param": 1 })).is_err());
+        assert!(registry::create_agent("straddle", &json!({ "levels": "many" })).is_err());
+        assert!(registry::create_agent("straddle", &json!([1, 2])).is_err());
+    }
+
+    #[test]
+    fn lorenz_state_is_reproducible_from_seed() {
+        let config = TrendConfig::default();
+        let run = |seed| {
+            let mut state = LorenzState::new(seed);
+            (0..1000).map(|_| state.lorenz_dy(&config)).collect::<Vec<f64>>()
+        };
+
+        assert_eq!(run(Some(42)), run(Some(42)), "Expected the same seed to give the same trend");
+        assert_ne!(run(Some(42)), run(Some(43)), "Expected different seeds to give different trends");
+    }
+
+    #[test]
+    fn default_trend_generators_diverge_across_stocks() {
+        let market = Market::new();
+        registry::add(&market, Stock::MSFT, Box::new(ChaoticTrendGenerator::default()));
+        registry::add(&market, Stock::AAPL, Box::new(ChaoticTrendGenerator::default()));
+        let states: Vec<Value> = registry::snapshot(&market).agents.into_iter().map(|a| a.state).collect();
+        assert_ne!(states[0], states[1], "Expected unseeded generators to start apart");
+
+        // handed a seed from the simulation, the same seed starts the same attractor
+        let reseeded = |seed| {
+            let mut generator = ChaoticTrendGenerator::default();
+            generator.reseed(seed);
+            generator.snapshot()
+        };
+        assert_eq!(reseeded(1), reseeded(1));
+        assert_ne!(reseeded(1), reseeded(2));
+    }
+
+    #[test]
+    fn trend_generator_validates_params() {
+        assert!(registry::create_agent("chaotic_trend", &json!({ "seed": 7, "correlation": 0.5 })).is_ok());
+        assert!(registry::create_agent("chaotic_trend", &json!({ "correlation": 1.5 })).is_err());
+        assert!(registry::create_agent("chaotic_trend", &json!({ "damping_factor": 0.0 })).is_err());
+    }
+
+    #[test]
+    fn market_maker_tracks_fills_and_skews_quotes() {
+        let market = Market::new();
+        let stock = Stock::AAPL;
+        market.ipo(stock, 1, 10.0, None);
+        market.buy(stock, 1, None, None, None);
+        market.find_trades(stock);
+
+        let ctx = AgentContext { id: 0, stock, market: &market };
+        let mut mm = InventoryMarketMaker::default();
+        mm.configure(&json!({ "levels": 1, "base_size": 100, "max_position": 1000 })).unwrap();
+        mm.on_fill(&ctx, &Fill { order_id: 0, order_type: OrderType::Buy, price: 10.0, volume: 500, timestamp: 0 });
+        mm.on_fill(&ctx, &Fill { order_id: 0, order_type: OrderType::Sell, price: 11.0, volume: 100, timestamp: 0 });
+
+        assert_eq!(mm.state()["inventory"], 400);
+        assert_eq!(mm.state()["cash"], -3900.0);
+
+        mm.on_tick(&ctx);
+        let (bids, asks) = market.get_depth(stock, 1);
+        assert_eq!(bids.len(), 1);
+        assert_eq!(asks.len(), 1);
+        assert!(bids[0].1 < asks[0].1, "Expected a long maker to quote less on the bid, found {:?} {:?}", bids, asks);
+        assert!(bids[0].0 < 10.0 && asks[0].0 > bids[0].0);
+
+        // a bid taken off the book behind the maker's back is put back on the next tick
+        market.remove_orders(stock, |order| order.order_type != OrderType::Buy);
+        assert!(market.get_depth(stock, 1).0.is_empty());
+        mm.on_tick(&ctx);
+        let (bids, asks) = market.get_depth(stock, 1);
+        assert_eq!((bids.len(), asks.len()), (1, 1), "Expected the maker to requote its empty side");
+        assert_eq!(market.pending_order_ids(stock).len(), 2, "Expected the old ask to be replaced, not added to");
+
+        // pulling the agent pulls its quotes
+        mm.on_remove(&ctx);
+        let (bids, asks) = market.get_depth(stock, 1);
+        assert!(bids.is_empty() && asks.is_empty(), "Expected quotes to be cancelled, found {:?} {:?}", bids, asks);
+    }
+
+    #[test]
+    fn size_distributions_sample_positive_sizes() {
+        let mut rng = seeded_rng(Some(1));
+        let distributions = [
+            SizeDistribution::Fixed { size: 5 },
+            SizeDistribution::Uniform { min: 1, max: 3 },
+            SizeDistribution::LogNormal { location: 2.0, scale: 0.5 },
+            SizeDistribution::Exponential { mean: 10.0 }
+        ];
+        for d in distributions.iter() {
+            assert!(d.validate().is_ok());
+            assert!((0..100).all(|_| d.sample(&mut rng) >= 1), "Expected sizes of at least one from {:?}", d);
+        }
+        assert_eq!(SizeDistribution::Fixed { size: 5 }.sample(&mut rng), 5);
+        assert!((0..100).all(|_| SizeDistribution::Uniform { min: 1, max: 3 }.sample(&mut rng) <= 3));
+
+        assert!(SizeDistribution::Uniform { min: 3, max: 1 }.validate().is_err());
+        assert!(SizeDistribution::Exponential { mean: 0.0 }.validate().is_err());
+    }
+
+    #[test]
+    fn poisson_arrivals_survive_bad_rates() {
+        let mut rng = seeded_rng(Some(1));
+        let second = GRANULARITY::SECOND as i64;
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let mut arrivals = PoissonArrivals::default();
+            arrivals.arrivals(rate, 0, &mut rng);
+            assert_eq!(arrivals.arrivals(rate, second, &mut rng), 0, "Expected no arrivals at rate {}", rate);
+        }
+        assert!(!valid_rate(f64::NAN) && !valid_rate(f64::INFINITY) && !valid_rate(-1.0) && valid_rate(0.0));
+    }
+
+    #[test]
+    fn population_agents_fill_the_book_and_cancellers_thin_it() {
+        let market = common::traded_market(10.0);
+        let stock = Stock::MSFT;
+
+        let noise = registry::create_agent("noise_trader", &json!({
+            "arrival_rate": 100.0, "market_order_probability": 0.0, "seed": 3,
+            "size": { "distribution": "fixed", "size": 10 }
+        })).unwrap();
+        let noise_id = registry::add(&market, stock, noise);
+
+        registry::tick(&market, stock);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        registry::tick(&market, stock);
+
+        let targets = vec!["noise_trader".to_string()];
+        let placed = registry::resting_orders_of(&market, stock, &targets).len();
+        assert!(placed > 0, "Expected the noise trader to have placed orders");
+
+        let canceller = registry::create_agent("random_canceller", &json!({ "cancel_rate": 1e6, "seed": 3 })).unwrap();
+        let canceller_id = registry::add(&market, stock, canceller);
+        registry::remove(&market, noise_id);
+        registry::tick(&market, stock);
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        registry::tick(&market, stock);
+
+        let remaining = registry::resting_orders_of(&market, stock, &targets).len();
+        assert!(remaining < placed, "Expected the canceller to cancel some of {} orders, {} remain", placed, remaining);
+        registry::remove(&market, canceller_id);
+    }
+
+    #[test]
+    fn population_agents_quote_around_the_mid_not_the_last_trade() {
+        let agents = vec![
+            ("noise_trader", json!({
+                "arrival_rate": 100.0, "market_order_probability": 0.0, "price_offset_std": 0.001, "seed": 3,
+                "size": { "distribution": "fixed", "size": 10 }
+            })),
+            ("hawkes_trader", json!({
+                "baseline_intensity": 1000.0, "market_order_probability": 0.0, "price_offset_std": 0.001, "seed": 3,
+                "size": { "distribution": "fixed", "size": 10 }
+            }))
+        ];
+
+        for (kind, config) in agents {
+            let market = common::traded_market(10.0);
+            let stock = Stock::MSFT;
+            // the book has moved on from the last trade at 10
+            market.buy(stock, 1, Some(19.9), None, None);
+            market.sell(stock, 1, Some(20.1), None, None);
+
+            let agent = registry::create_agent(kind, &config).unwrap();
+            let id = registry::add(&market, stock, agent);
+
+            registry::tick(&market, stock);
+            std::thread::sleep(std::time::Duration::from_millis(5));
+            registry::tick(&market, stock);
+
+            let targets = vec![kind.to_string()];
+            assert!(!registry::resting_orders_of(&market, stock, &targets).is_empty(), "Expected the {} to have placed orders", kind);
+            let (bids, asks) = market.get_depth(stock, 100);
+            for (price, _) in bids.iter().chain(asks.iter()) {
+                assert!((19.0..=21.0).contains(price), "Expected the {} to quote around the mid of 20, found {}", kind, price);
+            }
+            registry::remove(&market, id);
+        }
+    }
+
+    #[test]
+    fn population_agents_validate_params() {
+        assert!(registry::create_agent("zero_intelligence", &json!({ "value_range": 1.5 })).is_err());
+        assert!(registry::create_agent("noise_trader", &json!({ "market_order_probability": 2.0 })).is_err());
+        assert!(registry::create_agent("noise_trader", &json!({ "size": { "distribution": "uniform", "min": 5, "max": 1 } })).is_err());
+        assert!(registry::create_agent("random_canceller", &json!({ "cancel_rate": -1.0 })).is_err());
+        assert!(registry::create_agent("zero_intelligence", &json!({ "seed": 9 })).is_ok());
+    }
+
+    #[test]
+    fn fundamental_value_is_reproducible_from_seed() {
+        let day = GRANULARITY::DAY as i64;
+        let model = ValueModel::JumpDiffusion { drift: 0.0, volatility: 0.2, jump_intensity: 0.5, jump_mean: 0.0, jump_std: 0.1 };
+        let run = |seed| {
+            let mut process = FundamentalValue::new(100.0, model, Some(seed), 0);
+            (1..100).map(|i| { process.advance_to(i * day); process.value }).collect::<Vec<f64>>()
+        };
+
+        let path = run(5);
+        assert_eq!(path, run(5));
+        assert_ne!(path, run(6));
+        assert!(path.iter().all(|v| *v > 0.0), "Expected the value to stay positive");
+    }
+
+    #[test]
+    fn chartist_signals() {
+        let bar = |open: f64, close: f64| ObStat { tick: 0, granularity: GRANULARITY::SECOND, volume: 1, high: open.max(close), low: open.min(close), open, close };
+        let rising = vec![bar(10.0, 10.5), bar(10.5, 11.0)];
+
+        assert!((signal(ChartistStyle::Momentum, &rising, 11.0) - 0.1).abs() < 1e-9);
+        assert!(signal(ChartistStyle::MeanReversion, &rising, 11.0) < 0.0, "Expected a price above its average to be a sell");
+        assert_eq!(signal(ChartistStyle::Momentum, &rising[..1], 11.0), 0.0);
+    }
+
+    #[test]
+    fn strategy_agents_validate_params() {
+        assert!(registry::create_agent("momentum_trader", &json!({ "lookback": 1 })).is_err());
+        assert!(registry::create_agent("mean_reversion_trader", &json!({ "granularity": "MINUTE", "lookback": 10 })).is_ok());
+        assert!(registry::create_agent("fundamental_trader", &json!({ "threshold": 0.0 })).is_err());
+        assert!(registry::create_agent("fundamental_trader", &json!({
+            "value_model": { "model": "geometric_brownian", "drift": 0.0, "volatility": -1.0 }
+        })).is_err());
+    }
+
+    #[test]
+    fn hawkes_events_cluster_and_reproduce() {
+        let params = json!({ "seed": 11, "baseline_intensity": 1.0, "self_excitation": 0.8, "cross_excitation": 0.0, "decay": 1.0 });
+        let run = || {
+            let mut hawkes = HawkesTrader::default();
+            hawkes.configure(&params).unwrap();
+            hawkes.simulate(0.0, 1000.0)
+        };
+
+        let events = run();
+        let times: Vec<f64> = events.iter().map(|(t, _)| *t).collect();
+        assert_eq!(times, run().iter().map(|(t, _)| *t).collect::<Vec<f64>>(), "Expected the same seed to give the same events");
+        assert!(times.windows(2).all(|w| w[0] <= w[1]) && times.iter().all(|t| *t > 0.0 && *t <= 1000.0));
+
+        // stationary mean rate is mu / (1 - alpha / beta) per side, 10 events a second over both sides
+        let rate = events.len() as f64 / 1000.0;
+        assert!(rate > 5.0 && rate < 15.0, "Expected about 10 events a second, found {}", rate);
+
+        // clustering shows up as overdispersed counts, variance well above the poisson mean
+        let counts: Vec<f64> = (0..1000).map(|s| times.iter().filter(|t| **t > s as f64 && **t <= (s + 1) as f64).count() as f64).collect();
+        let mean = counts.iter().sum::<f64>() / counts.len() as f64;
+        let variance = counts.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / counts.len() as f64;
+        assert!(variance > 1.5 * mean, "Expected clustered arrivals, found mean {} variance {}", mean, variance);
+    }
+
+    #[test]
+    fn hawkes_rejects_explosive_params() {
+        assert!(registry::create_agent("hawkes_trader", &json!({ "self_excitation": 0.8, "cross_excitation": 0.3, "decay": 1.0 })).is_err());
+        assert!(registry::create_agent("hawkes_trader", &json!({ "decay": 0.0 })).is_err());
+        assert!(registry::create_agent("hawkes_trader", &json!({ "baseline_intensity": 2.0 })).is_ok());
+    }
+}
@@ -1,7 +1,10 @@
 use fssm::classes::api::{request_classes::*, response_classes::*};
 
 use fssm::handlers::api_handler::*;
+use fssm::handlers::admin_handler::handle_price_band;
+use fssm::kernel::market::Market;
 use fssm::classes::shared::order::OrderType::*;
+use fssm::classes::shared::order::Stock;
 
 #[cfg(test)]
 mod tests {
@@ -19,6 +22,15 @@ mod tests {
             stock_name: "MSFT".to_string(),
             amount: 10,
             price: None, // Market price
+            time_in_force: Default::default(),
+            display_amount: None,
+            hidden: false,
+            post_only: false,
+            slide: None,
+            peg: None,
+            offset: 0.0,
+            trail: None,
+            owner: None,
         };
 
         let ipo_dto = IpoDTO {
@@ -37,10 +49,57 @@ mod tests {
             .to_http_request();
         let payload = web::Json(order_dto);
 
-        let response = handle_ipo(payload_ipo);
-        let resp = handle_order(payload, Sell).unwrap();
+        let market = web::Data::new(Market::new());
+        let response = handle_ipo(market.clone(), payload_ipo);
+        let resp = handle_order(market, payload, Sell).unwrap();
         assert_eq!(resp.status(), http::StatusCode::OK);
         // Further assertions based on the expected behavior of buy_market
     }
-}
 
+    #[actix_rt::test]
+    async fn test_order_variants_are_checked() {
+        let market = web::Data::new(Market::new());
+        handle_ipo(market.clone(), web::Json(IpoDTO { stock_name: "MSFT".to_string(), amount: 10, price: 10.0 })).unwrap();
+        let order = |body: serde_json::Value| -> web::Json<OrderDTO> {
+            web::Json(serde_json::from_value(body).unwrap())
+        };
+
+        // crosses the ipo, and without a slide there's nothing to reprice it to
+        let resp = handle_order(market.clone(), order(json!({"stock_name": "MSFT", "amount": 5, "price": 10.0, "post_only": true})), Buy).unwrap();
+        assert_eq!(resp.status(), http::StatusCode::CONFLICT);
+        let resp = handle_order(market.clone(), order(json!({"stock_name": "MSFT", "amount": 5, "price": 10.0, "post_only": true, "slide": 0.5})), Buy).unwrap();
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(market.get_depth(Stock::MSFT, 1).0, vec![(9.5, 5)]);
+
+        let resp = handle_order(market.clone(), order(json!({"stock_name": "MSFT", "amount": 5, "price": 10.0, "peg": "Mid"})), Buy).unwrap();
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+        let resp = handle_order(market.clone(), order(json!({"stock_name": "MSFT", "amount": 5, "trail": {"Percent": 0.05}, "hidden": true})), Sell).unwrap();
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+        let resp = handle_order(market.clone(), order(json!({"stock_name": "MSFT", "amount": 5, "trail": {"Offset": 0.5}})), Sell).unwrap();
+        assert_eq!(resp.status(), http::StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn test_price_bands_are_checked() {
+        let market = web::Data::new(Market::new());
+        market.ipo(Stock::MSFT, 10, 10.0, None);
+        let band = |body: serde_json::Value| -> web::Json<PriceBandDTO> {
+            web::Json(serde_json::from_value(body).unwrap())
+        };
+
+        // the same rules as a scenario's price_band
+        for body in [
+            json!({"stock_name": "MSFT", "band": 0.0, "halt_seconds": 60.0}),
+            json!({"stock_name": "MSFT", "band": 1.0, "halt_seconds": 60.0}),
+            json!({"stock_name": "MSFT", "band": 0.1, "halt_seconds": 0.0}),
+            json!({"stock_name": "MSFT", "band": 0.1, "halt_seconds": 60.0, "window_seconds": 0.0})
+        ] {
+            let resp = handle_price_band(market.clone(), band(body.clone())).unwrap();
+            assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST, "{}", body);
+        }
+        let resp = handle_price_band(market.clone(), band(json!({"stock_name": "MSFT", "band": 0.1, "halt_seconds": 60.0, "window_seconds": 30.0}))).unwrap();
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let resp = handle_price_band(market.clone(), band(json!({"stock_name": "MSFT", "band": null, "halt_seconds": 0.0}))).unwrap();
+        assert_eq!(resp.status(), http::StatusCode::OK);
+    }
+}
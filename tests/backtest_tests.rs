@@ -0,0 +1,82 @@
+use fssm::classes::shared::order::Stock;
+use fssm::kernel::backtest::*;
+use fssm::kernel::gym::{Action, EnvConfig, Fill, Observation};
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // buys once at the first look and holds, counting what it is told was filled
+    struct BuyAndHold {
+        bought: bool,
+        filled: u64
+    }
+
+    impl Strategy for BuyAndHold {
+        fn on_data(&mut self, _observation: &Observation) -> Vec<Action> {
+            if self.bought {
+                return Vec::new();
+            }
+            self.bought = true;
+            vec![Action::Buy { amount: 10, price: Some(100.0) }]
+        }
+
+        fn on_fill(&mut self, fill: &Fill) {
+            self.filled += fill.volume;
+        }
+    }
+
+    // bids far below the market every step, never filled in a market with no one else in it
+    struct LowBall;
+
+    impl Strategy for LowBall {
+        fn on_data(&mut self, _observation: &Observation) -> Vec<Action> {
+            vec![Action::Buy { amount: 1, price: Some(0.01) }]
+        }
+    }
+
+    fn _backtest_helper(strategy: &mut dyn Strategy, agents: bool) -> BacktestReport {
+        let mut scenario = common::scenario(100.0, agents);
+        scenario.clock.tick_seconds = Some(5.0);
+        let mut env = EnvConfig::new(Stock::MSFT);
+        env.step_seconds = 60.0;
+        env.cash = 10_000.0;
+        env.fee_rate = 0.001;
+        run(strategy, &scenario, &BacktestConfig { env: env, days: 0.05, seed: 11 }).unwrap()
+    }
+
+    #[test]
+    fn backtests_report_on_the_whole_run() {
+        let mut strategy = BuyAndHold { bought: false, filled: 0 };
+        let report = _backtest_helper(&mut strategy, true);
+
+        // 0.05 days of minute steps, and the start
+        assert_eq!(report.equity_curve.len(), 73);
+        assert!(report.equity_curve.windows(2).all(|w| w[0].time < w[1].time));
+        assert_eq!(report.equity_curve[0].equity, 10_000.0);
+        assert_eq!((report.orders, report.ordered_volume, report.filled_volume), (1, 10, 10));
+        assert_eq!(strategy.filled, 10);
+        assert_eq!(report.fill_ratio, 1.0);
+        assert!(report.traded_value > 0.0);
+        assert!((report.fees - report.traded_value * 0.001).abs() < 1e-9);
+        assert!((report.turnover - report.traded_value / 10_000.0).abs() < 1e-12);
+        assert!((report.pnl - (report.equity_curve.last().unwrap().equity - 10_000.0)).abs() < 1e-9);
+        assert!((0.0..1.0).contains(&report.max_drawdown));
+        assert!(report.sharpe.is_finite());
+
+        let again = _backtest_helper(&mut BuyAndHold { bought: false, filled: 0 }, true);
+        assert_eq!(serde_json::to_string(&report).unwrap(), serde_json::to_string(&again).unwrap());
+    }
+
+    #[test]
+    fn unfilled_orders_count_against_the_fill_ratio() {
+        let report = _backtest_helper(&mut LowBall, false);
+        assert_eq!(report.orders, 72);
+        assert_eq!(report.filled_volume, 0);
+        assert_eq!(report.fill_ratio, 0.0);
+        assert_eq!(report.pnl, 0.0);
+        assert_eq!(report.max_drawdown, 0.0);
+    }
+}
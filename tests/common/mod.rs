@@ -0,0 +1,35 @@
+// fixtures shared by the integration tests, each test file only uses some of them
+#![allow(dead_code)]
+
+use serde_json::json;
+
+use fssm::classes::shared::order::Stock;
+use fssm::kernel::market::Market;
+use fssm::scenario::Scenario;
+
+/// A deterministic scenario listing 100 MSFT at `price` on a 0.01 tick, with the default
+/// population or no agents at all
+pub fn scenario(price: f64, agents: bool) -> Scenario {
+    let mut instrument = json!({ "symbol": "MSFT", "ipo_size": 100, "ipo_price": price, "tick_size": 0.01 });
+    if !agents {
+        instrument["agents"] = json!([]);
+    }
+    serde_json::from_value(json!({ "clock": { "mode": "deterministic" }, "instruments": [instrument] })).unwrap()
+}
+
+/// A market on a virtual clock stopped at zero that has listed `ipo_size` MSFT at `price`
+pub fn listed_market(ipo_size: u64, price: f64) -> Market {
+    let market = Market::new();
+    market.clock.use_virtual_clock(0);
+    market.ipo(Stock::MSFT, ipo_size, price, None);
+    market
+}
+
+/// An empty MSFT book that last traded at `price`, on the real clock
+pub fn traded_market(price: f64) -> Market {
+    let market = Market::new();
+    market.ipo(Stock::MSFT, 1, price, None);
+    market.buy(Stock::MSFT, 1, None, None, None);
+    market.find_trades(Stock::MSFT);
+    market
+}
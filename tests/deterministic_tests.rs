@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use fssm::classes::shared::{order::Stock, transaction::Transaction};
+use fssm::kernel::market::Market;
+use fssm::scenario::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn _run(symbol: &str, seed: u64) -> (Arc<Market>, Vec<Transaction>) {
+        let scenario: Scenario = serde_json::from_value(json!({
+            "clock": { "mode": "deterministic", "duration_seconds": 600.0 },
+            "seed": seed,
+            "instruments": [{
+                "symbol": symbol, "ipo_size": 1, "ipo_price": 100.0, "tick_size": 0.01,
+                "events": [{ "event": "volume_surge", "multiplier": 3.0, "seconds": 60.0, "delay_seconds": 120.0 }]
+            }]
+        })).unwrap();
+        scenario.validate().unwrap();
+        let market = Arc::new(Market::new());
+        let tape = scenario.start(&market).unwrap().unwrap().join().unwrap();
+        (market, tape)
+    }
+
+    fn _tape(transactions: &[Transaction]) -> Vec<(Option<u64>, Option<u64>, u64, u64, i64)> {
+        transactions.iter().map(|t| (t.buy_id, t.sell_id, t.price.to_bits(), t.volume, t.timestamp)).collect()
+    }
+
+    #[test]
+    fn same_seed_gives_identical_tapes() {
+        let (market, first) = _run("MSFT", 7);
+        assert!(market.clock.is_virtual());
+        assert!(first.len() > 100, "Expected a busy market, found {} trades", first.len());
+        assert!(first.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+        assert_eq!(market.get_events(Stock::MSFT).len(), 1);
+
+        // every market counts its own order ids, so even those line up
+        let (_, second) = _run("AAPL", 7);
+        assert_eq!(_tape(&first), _tape(&second), "Expected bit identical tapes for the same seed");
+
+        let (_, other) = _run("three", 8);
+        assert_ne!(_tape(&first), _tape(&other), "Expected a different seed to give a different tape");
+    }
+
+    #[test]
+    fn markets_run_side_by_side() {
+        // two simulations in one process, each on its own clock and books
+        let runs: Vec<_> = [("MSFT", 7), ("MSFT", 7)].into_iter()
+            .map(|(symbol, seed)| std::thread::spawn(move || _run(symbol, seed)))
+            .collect();
+        let tapes: Vec<_> = runs.into_iter().map(|run| run.join().unwrap().1).collect();
+        assert_eq!(_tape(&tapes[0]), _tape(&tapes[1]), "Expected concurrent markets not to disturb each other");
+    }
+}
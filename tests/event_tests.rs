@@ -0,0 +1,82 @@
+use fssm::classes::shared::order::Stock;
+use fssm::kernel::events::{self, MarketEvent};
+use fssm::kernel::agents::registry;
+use fssm::kernel::agents::strategies::value_process::*;
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn events_move_the_market_and_agents_react() {
+        let market = common::traded_market(10.0);
+        let stock = Stock::MSFT;
+        set_fundamental_value(&market, stock, 10.0);
+
+        let maker = registry::create_agent("inventory_market_maker", &json!({ "levels": 2 })).unwrap();
+        let id = registry::add(&market, stock, maker);
+        let makers = vec!["inventory_market_maker".to_string()];
+
+        registry::tick(&market, stock);
+        assert_eq!(registry::resting_orders_of(&market, stock, &makers).len(), 4);
+
+        // the maker pulls its quotes and stays out during a drought
+        events::trigger(&market, stock, MarketEvent::LiquidityDrought { seconds: 3600.0 });
+        assert!(registry::resting_orders_of(&market, stock, &makers).is_empty());
+        registry::tick(&market, stock);
+        assert!(registry::resting_orders_of(&market, stock, &makers).is_empty());
+        assert_eq!(registry::info(&market, id).unwrap().state["withdrawn"], true);
+
+        // the shock moves the value, the price waits for trades
+        events::trigger(&market, stock, MarketEvent::PriceShock { pct: -20.0 });
+        assert_eq!(market.get_price(stock), 10.0);
+        assert!((fundamental_value(&market, stock, 0.0) - 8.0).abs() < 0.1);
+
+        // scheduled events wait for their time to come
+        events::schedule(&market, stock, MarketEvent::VolumeSurge { multiplier: 3.0, seconds: 10.0 }, 3600.0);
+        events::fire_due(&market, stock);
+
+        let recorded: Vec<MarketEvent> = market.get_events(stock).iter().map(|r| r.event).collect();
+        assert_eq!(recorded, vec![
+            MarketEvent::LiquidityDrought { seconds: 3600.0 },
+            MarketEvent::PriceShock { pct: -20.0 }
+        ]);
+        let timestamps: Vec<i64> = market.get_events(stock).iter().map(|r| r.timestamp).collect();
+        assert!(timestamps[0] <= timestamps[1]);
+
+        registry::remove(&market, id);
+    }
+
+    #[test]
+    fn price_shocks_trade_the_price_there() {
+        let market = common::traded_market(10.0);
+        let stock = Stock::MSFT;
+        market.buy(stock, 1000, Some(9.0), None, None);
+
+        let maker = registry::create_agent("inventory_market_maker", &json!({ "levels": 1 })).unwrap();
+        registry::add(&market, stock, maker);
+        events::trigger(&market, stock, MarketEvent::PriceShock { pct: -20.0 });
+        assert_eq!(market.get_price(stock), 10.0);
+
+        // the maker offers around 8, into the bid at 9
+        registry::tick(&market, stock);
+        market.find_trades(stock);
+        let last = *market.trades_since(stock, 0).last().unwrap();
+        assert!(market.get_price(stock) < 9.0, "Expected the maker to sell the price down, found {}", market.get_price(stock));
+        assert_eq!(last.price, market.get_price(stock), "Expected the last price to come from the tape");
+    }
+
+    #[test]
+    fn events_parse_and_validate() {
+        let event: MarketEvent = serde_json::from_value(json!({ "event": "volume_surge", "multiplier": 5.0, "seconds": 60.0 })).unwrap();
+        assert_eq!(event, MarketEvent::VolumeSurge { multiplier: 5.0, seconds: 60.0 });
+        assert!(event.validate().is_ok());
+
+        assert!(MarketEvent::PriceShock { pct: -100.0 }.validate().is_err());
+        assert!(MarketEvent::LiquidityDrought { seconds: 0.0 }.validate().is_err());
+        assert!(serde_json::from_value::<MarketEvent>(json!({ "event": "earthquake" })).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+use chrono::{TimeZone, Utc};
+
+use fssm::classes::shared::order::Stock;
+use fssm::globals::GRANULARITY;
+use fssm::kernel::agents::registry;
+use fssm::kernel::events::seconds_to_nanos;
+use fssm::kernel::market::Market;
+use fssm::kernel::order_book::record::ObStat;
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn _market_helper() -> Market {
+        let market = common::listed_market(1000, 10.0);
+        market.clock.set_simulated_start(Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap());
+        market
+    }
+
+    fn _algo_helper(market: &Market, kind: &str, params: Value) -> u64 {
+        registry::add(market, Stock::MSFT, registry::create_agent(kind, &params).unwrap())
+    }
+
+    // runs the algos and matches orders for `seconds` of simulated time, a tenth of a second at a time
+    fn _run_helper(market: &Market, seconds: f64) {
+        for _ in 0..(seconds * 10.0).round() as u64 {
+            market.clock.advance(seconds_to_nanos(0.1));
+            registry::tick(market, Stock::MSFT);
+            market.find_trades(Stock::MSFT);
+        }
+    }
+
+    fn _state_helper(market: &Market, id: u64) -> Value {
+        registry::info(market, id).unwrap().state
+    }
+
+    #[test]
+    fn twap_slices_evenly_over_the_duration() {
+        let market = _market_helper();
+        let id = _algo_helper(&market, "twap", json!({ "side": "Buy", "amount": 100, "duration_seconds": 10.0, "slices": 5 }));
+
+        for slice in 1..=4 {
+            _run_helper(&market, 2.0);
+            assert_eq!(_state_helper(&market, id)["filled"], 20 * slice, "Expected {} slices filled", slice);
+        }
+        _run_helper(&market, 2.0);
+        let state = _state_helper(&market, id);
+        assert_eq!(state["status"], "completed");
+        assert_eq!(state["filled"], 100);
+        assert_eq!(state["average_price"], 10.0);
+    }
+
+    #[test]
+    fn limit_caps_hold_and_expiry_pulls_the_child() {
+        let market = _market_helper();
+        let id = _algo_helper(&market, "twap", json!({ "side": "Buy", "amount": 100, "limit_price": 9.5, "duration_seconds": 4.0, "slices": 2 }));
+
+        _run_helper(&market, 3.0);
+        let state = _state_helper(&market, id);
+        assert_eq!(state["filled"], 0);
+        let child = state["child_order"].as_u64().expect("Expected a child order resting at the cap");
+        assert!(market.pending_order_ids(Stock::MSFT).contains(&child));
+
+        _run_helper(&market, 2.0);
+        let state = _state_helper(&market, id);
+        assert_eq!(state["status"], "expired");
+        assert_eq!(state["remaining"], 100);
+        assert!(!market.pending_order_ids(Stock::MSFT).contains(&child));
+    }
+
+    #[test]
+    fn pov_follows_the_volume_of_others() {
+        let market = _market_helper();
+        let id = _algo_helper(&market, "pov", json!({ "side": "Buy", "amount": 1000, "participation": 0.5, "interval_seconds": 1.0, "duration_seconds": 60.0 }));
+
+        for _ in 0..10 {
+            market.buy(Stock::MSFT, 20, None, None, None);
+            _run_helper(&market, 1.0);
+        }
+        let state = _state_helper(&market, id);
+        assert_eq!(state["market_volume"], 200);
+        // the last interval's share is still to go
+        assert_eq!(state["filled"], 90);
+        assert_eq!(state["status"], "working");
+
+        // cancelling the parent leaves nothing of it on the book
+        assert!(registry::remove(&market, id));
+        assert!(market.pending_order_ids(Stock::MSFT).len() <= 1);
+    }
+
+    #[test]
+    fn vwap_follows_the_volume_profile() {
+        let market = _market_helper();
+        // the day before traded only between 01:00 and 02:00
+        let bars: Vec<ObStat> = (-24..0).map(|tick| ObStat {
+            tick: tick,
+            granularity: GRANULARITY::HOUR,
+            volume: if tick == -23 { 1000 } else { 0 },
+            high: 10.0,
+            low: 10.0,
+            open: 10.0,
+            close: 10.0
+        }).collect();
+        market.seed_history(Stock::MSFT, bars);
+        let id = _algo_helper(&market, "vwap", json!({ "side": "Buy", "amount": 50, "duration_seconds": 7200.0, "slices": 4 }));
+
+        _run_helper(&market, 3500.0);
+        assert_eq!(_state_helper(&market, id)["filled"], 0, "Expected nothing bought while the profile is quiet");
+        _run_helper(&market, 1850.0);
+        assert_eq!(_state_helper(&market, id)["filled"], 25);
+        _run_helper(&market, 1800.0);
+        assert_eq!(_state_helper(&market, id)["filled"], 50);
+    }
+
+    #[test]
+    fn algos_need_an_amount() {
+        assert!(registry::create_agent("twap", &Value::Null).is_err());
+        assert!(registry::create_agent("pov", &json!({ "amount": 10, "participation": 1.5 })).is_err());
+        assert!(registry::create_agent("vwap", &json!({ "side": "Sell", "amount": 10, "limit_price": 101.0 })).is_ok());
+    }
+}
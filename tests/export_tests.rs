@@ -0,0 +1,93 @@
+use actix_web::{body, web};
+
+use fssm::classes::api::request_classes::ExportQuery;
+use fssm::classes::shared::order::Stock;
+use fssm::globals::GRANULARITY;
+use fssm::handlers::export_handler::*;
+use fssm::kernel::export::ExportFormat;
+use fssm::kernel::market::Market;
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn _export_helper(market: &web::Data<Market>, trades: bool, query: ExportQuery) -> String {
+        let response = if trades {
+            handle_export_trades(market.clone(), web::Query(query)).unwrap()
+        } else {
+            handle_export_candles(market.clone(), web::Query(query)).unwrap()
+        };
+        assert!(response.status().is_success());
+        String::from_utf8(body::to_bytes(response.into_body()).await.unwrap().to_vec()).unwrap()
+    }
+
+    fn _query_helper(format: ExportFormat, from: Option<&str>) -> ExportQuery {
+        ExportQuery {
+            stock_name: "MSFT".to_string(),
+            from: from.map(str::to_string),
+            to: None,
+            format: format,
+            granularity: Some(GRANULARITY::SECOND)
+        }
+    }
+
+    #[actix_rt::test]
+    async fn exports_stream_the_whole_tape() {
+        // more trades than fit in one chunk, spread over a few hundred seconds
+        let market = web::Data::new(common::listed_market(30_000, 10.0));
+        let stock = Stock::MSFT;
+        for i in 0..25_000 {
+            market.buy(stock, 1, None, None, None);
+            market.find_trades(stock);
+            if i % 100 == 99 {
+                market.clock.advance(GRANULARITY::SECOND as i64);
+            }
+        }
+        market.report_transactions(stock);
+        let traded = market.tape.len(stock);
+        assert!(traded > 20_000, "Expected most trades on the tape, found {}", traded);
+
+        let csv = _export_helper(&market, true, _query_helper(ExportFormat::Csv, None)).await;
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("timestamp,simulated_time,price,volume,buy_id,sell_id"));
+        assert_eq!(lines.count(), traded, "Expected one header and a row per trade");
+
+        // starting a hundred simulated seconds in leaves out the first 10000 trades
+        let start = market.clock.simulated_datetime(100 * GRANULARITY::SECOND as i64).to_rfc3339();
+        let ndjson = _export_helper(&market, true, _query_helper(ExportFormat::Ndjson, Some(&start))).await;
+        assert_eq!(ndjson.lines().count(), traded - 10_000);
+        let first: serde_json::Value = serde_json::from_str(ndjson.lines().next().unwrap()).unwrap();
+        assert_eq!(first["price"], 10.0);
+
+        let candles = _export_helper(&market, false, _query_helper(ExportFormat::Ndjson, None)).await;
+        assert_eq!(candles.lines().count(), market.get_bars(stock, GRANULARITY::SECOND, usize::MAX).len());
+        assert!(candles.lines().all(|line| line.contains("\"granularity\":\"SECOND\"")));
+    }
+
+    #[actix_rt::test]
+    async fn the_tape_keeps_the_latest_trades() {
+        let market = web::Data::new(common::listed_market(300, 10.0));
+        market.tape.set_limit(100);
+        let stock = Stock::MSFT;
+        for _ in 0..250 {
+            market.buy(stock, 1, None, None, None);
+            market.find_trades(stock);
+            market.clock.advance(GRANULARITY::SECOND as i64);
+        }
+        market.report_transactions(stock);
+
+        // positions still count from the listing, only the oldest trades are gone
+        assert_eq!(market.tape.len(stock), 250);
+        assert_eq!(market.trade_count(stock), 250);
+        assert_eq!(market.trades_since(stock, 0).len(), 100);
+        assert_eq!(market.trades_since(stock, 200).len(), 50);
+
+        // dates before nanosecond timestamps reach back to export everything that's left, without overflowing
+        for from in ["1680-01-01T00:00:00Z", "0100-01-01T00:00:00Z"] {
+            let csv = _export_helper(&market, true, _query_helper(ExportFormat::Csv, Some(from))).await;
+            assert_eq!(csv.lines().count(), 101, "{}", from);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use fssm::classes::shared::order::{OrderType, OrderVariant, Stock};
+use fssm::kernel::journal::{self, FsyncPolicy};
+use fssm::kernel::market::Market;
+use fssm::kernel::order_book::{book::FINISHED_GROUPS_KEPT, group::*};
+use fssm::kernel::replay::{self, ReplaySpeed};
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn _states_helper(market: &Market, id: u64) -> Vec<(LegRole, LegState, u64, u64)> {
+        market.group_status(id).unwrap().legs.iter().map(|leg| (leg.role, leg.state, leg.amount, leg.filled)).collect()
+    }
+
+    #[test]
+    fn oco_fill_cancels_the_other_leg() {
+        let stock = Stock::MSFT;
+        let market = common::traded_market(10.0);
+        let id = market.place_oco(stock, &[
+            (OrderType::Sell, 5, OrderVariant::Limit { price: 12.0 }),
+            (OrderType::Sell, 5, OrderVariant::Stop { stop: 8.0 })
+        ]).unwrap();
+        assert_eq!(market.get_depth(stock, 5).1, vec![(12.0, 5)]);
+        assert_eq!(_states_helper(&market, id), vec![
+            (LegRole::Leg, LegState::Working, 5, 0),
+            (LegRole::Leg, LegState::Working, 5, 0)
+        ]);
+
+        market.buy(stock, 3, Some(12.0), None, None);
+        market.find_trades(stock);
+        let status = market.group_status(id).unwrap();
+        assert!(status.done);
+        assert_eq!(_states_helper(&market, id), vec![
+            (LegRole::Leg, LegState::Working, 5, 3),
+            (LegRole::Leg, LegState::Cancelled, 5, 0)
+        ]);
+        assert!(!market.pending_order_ids(stock).contains(&status.legs[1].order_id.unwrap()));
+
+        // the stop is gone, a falling price doesn't set it off
+        market.sell(stock, 1, Some(7.0), None, None);
+        market.buy(stock, 1, Some(7.0), None, None);
+        market.find_trades(stock);
+        assert_eq!(market.get_depth(stock, 5).1, vec![(12.0, 2)]);
+
+        assert!(!market.cancel_group(id), "Expected a finished group to have nothing to cancel");
+        assert!(market.place_oco(stock, &[(OrderType::Buy, 5, OrderVariant::Market)]).is_err());
+        assert!(market.group_status(id + 100).is_none());
+    }
+
+    #[test]
+    fn finished_groups_are_forgotten_oldest_first() {
+        let stock = Stock::MSFT;
+        let market = common::traded_market(10.0);
+        let legs = [
+            (OrderType::Sell, 5, OrderVariant::Limit { price: 12.0 }),
+            (OrderType::Buy, 5, OrderVariant::Limit { price: 8.0 })
+        ];
+        // an oco whose leg traded is done, but the rest of that leg is still on the book
+        let working = market.place_oco(stock, &legs).unwrap();
+        market.buy(stock, 3, Some(12.0), None, None);
+        market.find_trades(stock);
+        assert!(market.group_status(working).unwrap().done);
+        let live = market.place_oco(stock, &legs).unwrap();
+
+        let cancelled: Vec<u64> = (0..FINISHED_GROUPS_KEPT * 2 + 1).map(|_| {
+            let id = market.place_oco(stock, &legs).unwrap();
+            assert!(market.cancel_group(id));
+            id
+        }).collect();
+        market.place_oco(stock, &legs).unwrap();
+
+        assert!(market.group_status(cancelled[0]).is_none(), "Expected the oldest finished group to be forgotten");
+        assert!(market.group_status(*cancelled.last().unwrap()).is_some(), "Expected the latest finished groups to be kept");
+        assert!(!market.group_status(live).unwrap().done);
+        assert_eq!(_states_helper(&market, working)[0], (LegRole::Leg, LegState::Working, 5, 3));
+
+        // the legs of a live group still cancel each other
+        market.sell(stock, 5, Some(8.0), None, None);
+        market.find_trades(stock);
+        assert!(market.group_status(live).unwrap().done);
+    }
+
+    #[test]
+    fn bracket_exits_follow_the_entry() {
+        let stock = Stock::MSFT;
+        let market = common::traded_market(10.0);
+        let path = std::env::temp_dir().join(format!("fssm_groups_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        market.journal.open(&path, FsyncPolicy::Never).unwrap();
+
+        assert!(market.place_bracket(stock, OrderType::Buy, 10, OrderVariant::Limit { price: 10.0 }, 9.0, 12.0).is_err());
+        let id = market.place_bracket(stock, OrderType::Buy, 10, OrderVariant::Limit { price: 10.0 }, 12.0, 9.0).unwrap();
+        assert_eq!(_states_helper(&market, id), vec![
+            (LegRole::Entry, LegState::Working, 10, 0),
+            (LegRole::TakeProfit, LegState::Waiting, 10, 0),
+            (LegRole::StopLoss, LegState::Waiting, 10, 0)
+        ]);
+
+        // the exits work whatever the entry has filled so far
+        market.sell(stock, 4, Some(10.0), None, None);
+        market.find_trades(stock);
+        assert_eq!(market.get_depth(stock, 5).1, vec![(12.0, 4)]);
+        market.sell(stock, 6, Some(10.0), None, None);
+        market.find_trades(stock);
+        assert_eq!(market.get_depth(stock, 5).1, vec![(12.0, 10)]);
+        assert_eq!(_states_helper(&market, id), vec![
+            (LegRole::Entry, LegState::Filled, 10, 10),
+            (LegRole::TakeProfit, LegState::Working, 10, 0),
+            (LegRole::StopLoss, LegState::Working, 10, 0)
+        ]);
+
+        // the price falls through the stop, which sells into the bid and takes the take profit with it
+        market.buy(stock, 10, Some(8.5), None, None);
+        market.sell(stock, 1, Some(8.9), None, None);
+        market.buy(stock, 1, Some(8.9), None, None);
+        market.find_trades(stock);
+        assert_eq!(_states_helper(&market, id), vec![
+            (LegRole::Entry, LegState::Filled, 10, 10),
+            (LegRole::TakeProfit, LegState::Cancelled, 10, 0),
+            (LegRole::StopLoss, LegState::Filled, 10, 10)
+        ]);
+        assert!(market.get_depth(stock, 5).1.is_empty());
+        assert_eq!(market.get_price(stock), 8.5);
+
+        // a second bracket cancelled before it fills
+        let cancelled = market.place_bracket(stock, OrderType::Sell, 5, OrderVariant::Limit { price: 9.0 }, 8.0, 9.5).unwrap();
+        assert!(market.cancel_group(cancelled));
+        assert!(_states_helper(&market, cancelled).iter().all(|leg| leg.1 == LegState::Cancelled));
+        market.journal.close().unwrap();
+
+        // groups come back from the journal and cancel their legs again in the same places
+        let depth = market.get_depth(stock, 10);
+        let records = journal::read(&path).unwrap();
+        let report = replay::run(&market, &records, ReplaySpeed::Max);
+        assert!(report.finished && report.verified(), "Expected every trade to match, found {:?}", report);
+        assert_eq!(market.get_depth(stock, 10), depth);
+        assert!(market.group_status(id).unwrap().done);
+        let _ = std::fs::remove_file(&path);
+    }
+}
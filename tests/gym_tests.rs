@@ -0,0 +1,75 @@
+use fssm::classes::shared::order::{OrderType, Stock};
+use fssm::kernel::gym::*;
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a fixed script of actions, the observations and rewards it gets serialized for comparison
+    fn _episode_helper(seed: u64) -> Vec<String> {
+        let mut config = EnvConfig::new(Stock::MSFT);
+        config.step_seconds = 30.0;
+        let mut env = Environment::new(config);
+        let mut out = vec![serde_json::to_string(&env.reset(seed, &common::scenario(10.0, true)).unwrap()).unwrap()];
+        for i in 0..20 {
+            let actions = match i {
+                0 => vec![Action::Buy { amount: 5, price: None }],
+                5 => vec![Action::Sell { amount: 5, price: Some(10.5) }, Action::Buy { amount: 5, price: Some(9.5) }],
+                15 => vec![Action::CancelAll],
+                _ => Vec::new()
+            };
+            let step = env.step(&actions).unwrap();
+            out.push(serde_json::to_string(&(step.observation, step.reward)).unwrap());
+        }
+        out
+    }
+
+    #[test]
+    fn episodes_are_deterministic_under_a_seed() {
+        let first = _episode_helper(3);
+        assert_eq!(first, _episode_helper(3), "Expected the same seed and actions to give the same episode");
+        assert_ne!(first, _episode_helper(4), "Expected a different seed to give a different episode");
+    }
+
+    #[test]
+    fn fills_move_inventory_and_cash() {
+        let mut config = EnvConfig::new(Stock::MSFT);
+        config.max_steps = Some(3);
+        let mut env = Environment::new(config);
+        assert!(env.step(&[]).is_err(), "Expected stepping before a reset to fail");
+        let start = env.reset(1, &common::scenario(10.0, false)).unwrap();
+        assert_eq!(start.asks, vec![(10.0, 100)]);
+
+        let step = env.step(&[Action::Buy { amount: 10, price: None }]).unwrap();
+        assert_eq!(step.fills.len(), 1);
+        assert_eq!((step.fills[0].side, step.fills[0].volume, step.fills[0].order_id), (OrderType::Buy, 10, step.orders[0]));
+        assert_eq!((step.observation.inventory, step.observation.cash), (10, -100.0));
+        assert_eq!(step.reward, 0.0);
+
+        let step = env.step(&[Action::Sell { amount: 10, price: Some(12.0) }]).unwrap();
+        assert_eq!(step.observation.open_orders, step.orders);
+        assert!(!step.done);
+        let step = env.step(&[Action::Cancel { order_id: step.orders[0] }]).unwrap();
+        assert!(step.observation.open_orders.is_empty());
+        assert!(step.done);
+        assert!(env.step(&[]).is_err(), "Expected stepping a finished episode to fail");
+    }
+
+    #[test]
+    fn shortfall_episodes_end_once_filled() {
+        let mut config = EnvConfig::new(Stock::MSFT);
+        config.reward = Reward::ImplementationShortfall { side: OrderType::Buy, amount: 30 };
+        let mut env = Environment::new(config);
+        env.reset(1, &common::scenario(10.0, false)).unwrap();
+
+        let step = env.step(&[Action::Buy { amount: 20, price: None }]).unwrap();
+        assert!(!step.done);
+        // bought at the arrival price, no shortfall
+        assert_eq!(step.reward, 0.0);
+        let step = env.step(&[Action::Buy { amount: 10, price: Some(10.0) }]).unwrap();
+        assert!(step.done);
+        assert_eq!(step.observation.inventory, 30);
+    }
+}
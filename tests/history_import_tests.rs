@@ -0,0 +1,65 @@
+use fssm::classes::shared::order::Stock;
+use fssm::globals::GRANULARITY;
+use fssm::kernel::agents::strategies::value_process;
+use fssm::kernel::history_import;
+use fssm::kernel::market::Market;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 90 minute bars from 09:30, with the 10:00 bar missing
+    fn _minute_bars_helper() -> String {
+        let mut csv = "Date,Open,High,Low,Close,Volume\n".to_string();
+        for i in 0..91 {
+            if i == 30 {
+                continue;
+            }
+            let open = 100.0 + i as f64 * 0.1;
+            csv += &format!("2024-03-01 {:02}:{:02},{},{},{},{},{}\n",
+                9 + (30 + i) / 60, (30 + i) % 60, open, open + 0.5, open - 0.5, open + 0.1, 1000 + i);
+        }
+        csv
+    }
+
+    #[test]
+    fn imported_bars_seed_history_price_and_value() {
+        let market = Market::new();
+        let history = history_import::parse(_minute_bars_helper().as_bytes()).unwrap();
+        assert!(matches!(history.granularity, GRANULARITY::MINUTE));
+        assert_eq!(history.bars.len(), 90);
+        assert!((history.last_close() - 109.1).abs() < 1e-9);
+
+        market.clock.use_virtual_clock(0);
+        let stock = Stock::AAPL;
+        market.ipo(stock, 10, history.last_close(), None);
+        history_import::seed(&market, stock, &history);
+
+        assert_eq!(market.get_price(stock), history.last_close());
+        assert_eq!(value_process::fundamental_value(&market, stock, 1.0), history.last_close());
+
+        // the last bar sits just before the listing and the missing bar leaves its gap
+        let minutes = market.get_bars(stock, GRANULARITY::MINUTE, 100);
+        assert_eq!(minutes.len(), 90);
+        assert_eq!(minutes.last().unwrap().tick, -1);
+        assert_eq!(minutes[0].tick, -91);
+        assert_eq!(minutes[29].tick + 2, minutes[30].tick);
+        assert_eq!(market.get_bars(stock, GRANULARITY::HOUR, 10).len(), 1);
+    }
+
+    #[test]
+    fn bad_bars_are_listed_by_line() {
+        let csv = "timestamp,open,high,low,close\n\
+            2024-03-01T00:00:00Z,10,11,9,10.5\n\
+            2024-03-01T01:00:00Z,10,9,9,10.5\n\
+            2024-03-01T01:00:00Z,-1,11,9,10.5\n";
+        let e = history_import::parse(csv.as_bytes()).err().unwrap();
+        assert!(e.contains("line 3: high 9 and low 9"), "{}", e);
+        assert!(e.contains("line 4: open '-1'"), "{}", e);
+
+        // 90 minutes apart is a whole number of minutes but not the length of any bar
+        let csv = "time,open,high,low,close\n1709251200,10,11,9,10\n1709256600,10,11,9,10\n";
+        assert!(history_import::parse(csv.as_bytes()).err().unwrap().contains("5400 seconds apart"));
+        assert!(history_import::parse("date,open,close\n".as_bytes()).is_err());
+    }
+}
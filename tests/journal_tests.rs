@@ -0,0 +1,110 @@
+use fssm::classes::shared::order::{OrderType, Stock};
+use fssm::kernel::journal::{self, FsyncPolicy, JournalEntry};
+use fssm::kernel::market::Market;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn _kind(entry: &JournalEntry) -> &'static str {
+        match entry {
+            JournalEntry::OrderAccepted { .. } => "order_accepted",
+            JournalEntry::Cancel { .. } => "cancel",
+            JournalEntry::Expiry { .. } => "expiry",
+            JournalEntry::Halt { .. } => "halt",
+            JournalEntry::Resume { .. } => "resume",
+            JournalEntry::Uncross { .. } => "uncross",
+            JournalEntry::Trade { .. } => "trade",
+            JournalEntry::Event { .. } => "event",
+            JournalEntry::GroupAccepted { .. } => "group_accepted",
+            JournalEntry::GroupCancel { .. } => "group_cancel",
+            JournalEntry::SelfTrade { .. } => "self_trade"
+        }
+    }
+
+    #[test]
+    fn journal_records_the_life_of_a_book() {
+        let market = Market::new();
+        let path = std::env::temp_dir().join(format!("fssm_journal_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        market.journal.open(&path, FsyncPolicy::EveryEntry).unwrap();
+
+        let stock = Stock::MSFT;
+        market.ipo(stock, 10, 10.0, Some(1));
+        market.buy(stock, 5, Some(9.0), None, Some(2));
+        market.cancel(stock, 2);
+        market.buy(stock, 4, None, None, Some(3));
+        market.find_trades(stock);
+        market.buy(stock, 1, Some(5.0), Some(0), Some(4));
+        market.clean_books(stock);
+        market.halt(stock);
+        market.resume(stock);
+        market.journal.close().unwrap();
+
+        let records = journal::read(&path).unwrap();
+        let kinds: Vec<&str> = records.iter().map(|r| _kind(&r.entry)).collect();
+        assert_eq!(kinds, vec![
+            "order_accepted", "order_accepted", "cancel", "order_accepted", "trade",
+            "order_accepted", "expiry", "halt", "resume"
+        ]);
+        assert!(records.iter().enumerate().all(|(i, r)| r.seq == i as u64 + 1));
+
+        match &records[4].entry {
+            JournalEntry::Trade { stock: s, transaction } => {
+                assert_eq!(*s, stock);
+                assert_eq!((transaction.buy_id, transaction.sell_id, transaction.volume), (Some(3), Some(1), 4));
+                assert_eq!(transaction.price, 10.0);
+            },
+            other => panic!("Expected a trade, found {:?}", other)
+        }
+        match &records[6].entry {
+            JournalEntry::Expiry { order_id, order_type, amount, .. } => {
+                assert_eq!((*order_id, *order_type, *amount), (Some(4), OrderType::Buy, 1));
+            },
+            other => panic!("Expected an expiry, found {:?}", other)
+        }
+
+        // reopening appends, carrying the sequence on
+        market.journal.open(&path, FsyncPolicy::Never).unwrap();
+        market.cancel(stock, 1);
+        market.journal.close().unwrap();
+        let records = journal::read(&path).unwrap();
+        assert_eq!(records.len(), 10);
+        assert_eq!(records.last().unwrap().seq, 10);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn journal_recovers_from_a_torn_last_line() {
+        let market = Market::new();
+        let path = std::env::temp_dir().join(format!("fssm_journal_torn_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let stock = Stock::MSFT;
+        market.journal.open(&path, FsyncPolicy::EveryEntry).unwrap();
+        market.ipo(stock, 10, 10.0, Some(1));
+        market.buy(stock, 5, Some(9.0), None, Some(2));
+        market.journal.close().unwrap();
+
+        // a crash halfway through writing the next record
+        let whole = std::fs::read_to_string(&path).unwrap();
+        let last = whole.lines().last().unwrap();
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        std::io::Write::write_all(&mut file, last[..last.len() / 2].as_bytes()).unwrap();
+        drop(file);
+        assert_eq!(journal::read(&path).unwrap().len(), 2, "Expected the torn line to be left out");
+
+        // reopening cuts it off and carries on from the last good record
+        market.journal.open(&path, FsyncPolicy::EveryEntry).unwrap();
+        market.cancel(stock, 2);
+        market.journal.close().unwrap();
+        let records = journal::read(&path).unwrap();
+        assert_eq!(records.iter().map(|r| r.seq).collect::<Vec<u64>>(), vec![1, 2, 3]);
+        assert_eq!(_kind(&records[2].entry), "cancel");
+
+        // a bad line with good ones after it is not a torn write
+        std::fs::write(&path, format!("{{\"seq\": 1\n{}", whole)).unwrap();
+        assert!(journal::read(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
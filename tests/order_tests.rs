@@ -10,13 +10,13 @@ mod tests {
             id: None,
             order_type: OrderType::Buy,
             variant: OrderVariant::Market,
-            details: OrderDetails { time: 1, stock: Stock::AAPL, amount: 1, lifetime_nanos: None },
+            details: OrderDetails { time: 1, stock: Stock::AAPL, amount: 1, lifetime_nanos: None, time_in_force: TimeInForce::GoodTillCancel, visibility: Visibility::Displayed, owner: None },
         };
         let limit_order = Order {
             id: None,
             order_type: OrderType::Buy,
             variant: OrderVariant::Limit { price: 100.0 },
-            details: OrderDetails { time: 2, stock: Stock::AAPL , amount: 1, lifetime_nanos: None},
+            details: OrderDetails { time: 2, stock: Stock::AAPL , amount: 1, lifetime_nanos: None, time_in_force: TimeInForce::GoodTillCancel, visibility: Visibility::Displayed, owner: None },
         };
 
         assert!(market_order > limit_order, "Market order should be greater than limit order");
@@ -28,13 +28,13 @@ mod tests {
             id: None,
             order_type: OrderType:: Sell,
             variant: OrderVariant::Limit { price: 95.0 },
-            details: OrderDetails { time: 2, stock: Stock::GOOGL , amount: 1, lifetime_nanos: None },
+            details: OrderDetails { time: 2, stock: Stock::GOOGL , amount: 1, lifetime_nanos: None, time_in_force: TimeInForce::GoodTillCancel, visibility: Visibility::Displayed, owner: None },
         };
         let higher_price_order = Order {
             id: None,
             order_type: OrderType::Sell,
             variant: OrderVariant::Limit { price: 100.0 },
-            details: OrderDetails { time: 1, stock: Stock::GOOGL, amount: 1, lifetime_nanos: None}
+            details: OrderDetails { time: 1, stock: Stock::GOOGL, amount: 1, lifetime_nanos: None, time_in_force: TimeInForce::GoodTillCancel, visibility: Visibility::Displayed, owner: None }
         };
 
         assert!(lower_price_order > higher_price_order, "Lower price sell order should have higher priority");
@@ -46,13 +46,13 @@ mod tests {
             id: None,
             order_type: OrderType::Buy,
             variant: OrderVariant::Limit { price: 95.0 },
-            details: OrderDetails { time: 2, stock: Stock::GOOGL , amount: 1, lifetime_nanos: None },
+            details: OrderDetails { time: 2, stock: Stock::GOOGL , amount: 1, lifetime_nanos: None, time_in_force: TimeInForce::GoodTillCancel, visibility: Visibility::Displayed, owner: None },
         };
         let higher_price_order = Order {
             id: None,
             order_type: OrderType::Buy,
             variant: OrderVariant::Limit { price: 100.0 },
-            details: OrderDetails { time: 1, stock: Stock::GOOGL, amount: 1, lifetime_nanos: None }
+            details: OrderDetails { time: 1, stock: Stock::GOOGL, amount: 1, lifetime_nanos: None, time_in_force: TimeInForce::GoodTillCancel, visibility: Visibility::Displayed, owner: None }
         };
 
         assert!(lower_price_order < higher_price_order, "Higher price buy order should have higher priority");
@@ -64,13 +64,13 @@ mod tests {
             id: None,
             order_type: OrderType::Buy,
             variant: OrderVariant::Limit { price: 150.0 },
-            details: OrderDetails { time: 1, stock: Stock::MSFT , amount: 1, lifetime_nanos: None},
+            details: OrderDetails { time: 1, stock: Stock::MSFT , amount: 1, lifetime_nanos: None, time_in_force: TimeInForce::GoodTillCancel, visibility: Visibility::Displayed, owner: None },
         };
         let later_order = Order {
             id: None,
             order_type: OrderType::Buy,
             variant: OrderVariant::Limit { price: 150.0 },
-            details: OrderDetails { time: 2, stock: Stock::MSFT , amount: 1, lifetime_nanos: None},
+            details: OrderDetails { time: 2, stock: Stock::MSFT , amount: 1, lifetime_nanos: None, time_in_force: TimeInForce::GoodTillCancel, visibility: Visibility::Displayed, owner: None },
         };
 
         assert!(earlier_order > later_order, "Earlier limit buy order should be less than later one with the same price");
@@ -82,13 +82,13 @@ mod tests {
             id: None,
             order_type: OrderType::Sell,
             variant: OrderVariant::Market,
-            details: OrderDetails { time: 1, stock: Stock::AAPL , amount: 1, lifetime_nanos: None},
+            details: OrderDetails { time: 1, stock: Stock::AAPL , amount: 1, lifetime_nanos: None, time_in_force: TimeInForce::GoodTillCancel, visibility: Visibility::Displayed, owner: None },
         };
         let later_order = Order {
             id: None,
             order_type: OrderType::Sell,
             variant: OrderVariant::Market,
-            details: OrderDetails { time: 2, stock: Stock::AAPL , amount: 1, lifetime_nanos: None},
+            details: OrderDetails { time: 2, stock: Stock::AAPL , amount: 1, lifetime_nanos: None, time_in_force: TimeInForce::GoodTillCancel, visibility: Visibility::Displayed, owner: None },
         };
 
         assert!(earlier_order > later_order, "Earlier market sell order should be greater than later one");
@@ -0,0 +1,110 @@
+use fssm::classes::shared::order::{Stock, TimeInForce, OrderType, OrderVariant, Visibility};
+use fssm::kernel::journal::{self, FsyncPolicy, JournalEntry};
+use fssm::kernel::market::Market;
+use fssm::kernel::order_book::book::SelfTradePrevention;
+use fssm::kernel::replay::{self, ReplaySpeed};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_regenerates_the_recorded_trades() {
+        let market = Market::new();
+        let path = std::env::temp_dir().join(format!("fssm_replay_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        market.journal.open(&path, FsyncPolicy::Never).unwrap();
+
+        let stock = Stock::MSFT;
+        market.ipo(stock, 10, 10.0, Some(1));
+        market.buy(stock, 4, None, None, Some(2));
+        market.find_trades(stock);
+        for i in 0..5 {
+            market.sell(stock, 3, Some(10.5 + i as f64 * 0.1), None, None);
+            market.buy(stock, 2, Some(9.5 - i as f64 * 0.1), Some(0), None);
+        }
+        market.clean_books(stock);
+        market.buy(stock, 5, Some(9.0), None, Some(3));
+        market.cancel(stock, 3);
+        market.buy(stock, 12, None, None, None);
+        market.find_trades(stock);
+
+        // an auction after a halt
+        market.halt(stock);
+        market.place_order(stock, 6, OrderType::Buy, Some(11.0), None, TimeInForce::GoodTillCancel, Visibility::Displayed, Some(4));
+        market.sell(stock, 2, Some(10.8), None, Some(5));
+        market.resume(stock);
+        market.journal.close().unwrap();
+
+        let depth = market.get_depth(stock, 10);
+        let price = market.get_price(stock);
+        let records = journal::read(&path).unwrap();
+        let recorded_trades = records.iter().filter(|r| matches!(r.entry, JournalEntry::Trade { .. })).count() as u64;
+        assert!(records.iter().any(|r| matches!(r.entry, JournalEntry::Uncross { .. })), "Expected the resume to reopen with an auction");
+
+        let report = replay::run(&market, &records, ReplaySpeed::Max);
+        assert!(report.finished && report.verified(), "Expected every trade to match, found {:?}", report);
+        assert_eq!(report.trades, recorded_trades);
+        assert_eq!(report.matched, recorded_trades);
+        assert_eq!(market.get_depth(stock, 10), depth);
+        assert_eq!(market.get_price(stock), price);
+        assert_eq!(replay::status(&market).unwrap().replayed, records.len());
+
+        // a journal that doesn't add up is caught
+        let mut tampered = records.clone();
+        let trade = tampered.iter_mut().find_map(|r| match &mut r.entry {
+            JournalEntry::Trade { transaction, .. } => Some(transaction),
+            _ => None
+        }).unwrap();
+        trade.volume += 1;
+        let tampered_volume = trade.volume;
+        let report = replay::run(&market, &tampered, ReplaySpeed::Max);
+        assert!(!report.verified());
+        assert_eq!(report.mismatched, 1);
+        assert_eq!(report.mismatches[0].recorded.volume, tampered_volume);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn replay_repeats_self_trade_prevention() {
+        let market = Market::new();
+        let path = std::env::temp_dir().join(format!("fssm_replay_stp_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        market.journal.open(&path, FsyncPolicy::Never).unwrap();
+
+        let stock = Stock::MSFT;
+        market.ipo(stock, 1, 10.0, None);
+        market.buy(stock, 1, None, None, None);
+        market.find_trades(stock);
+        market.set_self_trade_prevention(stock, Some(SelfTradePrevention::CancelNewest));
+        let place = |order_type, amount, price, owner| {
+            let id = Some(market.next_order_id());
+            market.place_variant(stock, amount, order_type, OrderVariant::Limit { price: price }, None, TimeInForce::GoodTillCancel, Visibility::Displayed, Some(owner), id);
+        };
+        // the owner's own offer is cancelled rather than trading, a better one from someone else goes through
+        place(OrderType::Buy, 10, 10.0, 1);
+        place(OrderType::Sell, 4, 10.0, 1);
+        market.find_trades(stock);
+        place(OrderType::Sell, 3, 9.9, 2);
+        market.find_trades(stock);
+        assert_eq!(market.get_depth(stock, 5), (vec![(10.0, 7)], vec![]));
+        // and the same in the auction reopening after a halt
+        market.halt(stock);
+        place(OrderType::Sell, 5, 9.8, 1);
+        place(OrderType::Sell, 2, 9.9, 3);
+        market.resume(stock);
+        market.journal.close().unwrap();
+        assert_eq!(market.get_depth(stock, 5), (vec![(10.0, 5)], vec![]));
+
+        // the journal carries the steps, replay doesn't depend on the book being set up the same way
+        market.set_self_trade_prevention(stock, None);
+        let records = journal::read(&path).unwrap();
+        assert_eq!(records.iter().filter(|r| matches!(r.entry, JournalEntry::SelfTrade { .. })).count(), 2);
+        assert!(records.iter().any(|r| matches!(r.entry, JournalEntry::Uncross { self_trade_prevention: Some(_), .. })));
+        let report = replay::run(&market, &records, ReplaySpeed::Max);
+        assert!(report.finished && report.verified(), "Expected every trade to match, found {:?}", report);
+        assert_eq!(market.get_depth(stock, 5), (vec![(10.0, 5)], vec![]));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
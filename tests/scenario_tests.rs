@@ -0,0 +1,86 @@
+use std::path::Path;
+
+use fssm::classes::shared::order::Stock;
+use fssm::kernel::market::Market;
+use fssm::scenario::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn _write_scenario(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn example_scenario_loads() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios/example.toml");
+        let scenario = Scenario::load(&path).unwrap();
+
+        assert_eq!(scenario.instruments.len(), 2);
+        assert_eq!(scenario.clock.acceleration, 3600.0);
+        let msft = &scenario.instruments[0];
+        assert_eq!(msft.stock(), Some(Stock::MSFT));
+        // count expands into separate agents
+        assert_eq!(msft.build_agents().unwrap().len(), 5);
+        assert_eq!(msft.events.len(), 1);
+        // without an agent list an instrument runs the default population
+        assert!(!scenario.instruments[1].build_agents().unwrap().is_empty());
+    }
+
+    #[test]
+    fn json_scenarios_load_with_defaults() {
+        let path = _write_scenario("fssm_minimal_scenario.json", r#"{
+            "instruments": [{ "symbol": "MSFT", "ipo_size": 10, "ipo_price": 5.0, "session": {} }]
+        }"#);
+        let scenario = Scenario::load(&path).unwrap();
+
+        assert_eq!(scenario.bind, "127.0.0.1:8080");
+        let schedule = scenario.instruments[0].session.as_ref().unwrap().schedule().unwrap();
+        assert_eq!(schedule.continuous.to_string(), "09:30:00");
+    }
+
+    #[test]
+    fn invalid_scenarios_list_every_problem() {
+        let path = _write_scenario("fssm_invalid_scenario.json", r#"{
+            "bind": "nowhere",
+            "instruments": [
+                { "symbol": "TSLA", "ipo_size": 0, "ipo_price": 5.0 },
+                { "symbol": "MSFT", "ipo_size": 1, "ipo_price": 5.0, "tick_size": -1.0,
+                  "session": { "continuous": "25:00" },
+                  "agents": [{ "kind": "noise_trader", "params": { "arrival_rate": -1.0 } }, { "kind": "oracle" }] }
+            ]
+        }"#);
+        let error = Scenario::load(&path).err().unwrap();
+
+        for expected in [
+            "bind: 'nowhere'",
+            "instruments[0] (TSLA): unknown symbol",
+            "instruments[0] (TSLA): ipo_size must be positive",
+            "instruments[1] (MSFT): tick_size must be positive",
+            "instruments[1] (MSFT): session: continuous '25:00'",
+            "instruments[1] (MSFT): agents[0] (noise_trader): arrival_rate"
+        ] {
+            assert!(error.contains(expected), "Expected '{}' in:\n{}", expected, error);
+        }
+
+        // unknown fields are caught while parsing, with their position
+        let path = _write_scenario("fssm_typo_scenario.toml", "[[instruments]]\nsymbol = \"MSFT\"\nipo_size = 1\nipo_prise = 5.0\n");
+        let error = Scenario::load(&path).err().unwrap();
+        assert!(error.contains("ipo_prise") && error.contains("line 4"), "{}", error);
+    }
+
+    #[test]
+    fn limit_prices_round_to_tick_size() {
+        let market = Market::new();
+        let stock = Stock::GOOGL;
+        market.ipo(stock, 1, 100.0, None);
+        market.set_tick_size(stock, Some(0.05));
+        market.buy(stock, 1, Some(99.93), None, None);
+
+        let (bids, _) = market.get_depth(stock, 1);
+        assert!((bids[0].0 - 99.95).abs() < 1e-9, "Expected the bid on the tick grid, found {:?}", bids);
+    }
+}
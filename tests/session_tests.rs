@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use actix_web::test::{call_and_read_body_json, call_service, init_service, TestRequest};
+use actix_web::{http, web, App};
+
+use fssm::classes::api::{request_classes::*, response_classes::*};
+use fssm::classes::shared::order::Stock;
+use fssm::handlers::{admin_handler::{handle_halt, handle_snapshot}, api_handler::*, session_handler::*};
+use fssm::kernel::snapshot;
+use fssm::kernel::market::Market;
+use fssm::scenario::Scenario;
+use fssm::sessions::*;
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    async fn _price(market: SessionMarket, query: web::Query<StockQuery>) -> actix_web::Result<actix_web::HttpResponse> {
+        handle_price(market.0, query)
+    }
+
+    async fn _snapshot(market: SessionMarket, sessions: web::Data<Sessions>, details: web::Json<SnapshotDTO>) -> actix_web::Result<actix_web::HttpResponse> {
+        handle_snapshot(market.0, &sessions.snapshot_dir(&market.1), details)
+    }
+
+    #[test]
+    fn sessions_are_isolated() {
+        let sessions = Sessions::default();
+        let a = sessions.create("team-a", &common::scenario(5.0, false)).unwrap();
+        let b = sessions.create("team-b", &common::scenario(20.0, false)).unwrap();
+        assert_eq!(sessions.names(), vec!["team-a", "team-b"]);
+        assert!(!Arc::ptr_eq(&a, &b));
+
+        for (market, volume) in [(&a, 10), (&b, 3)] {
+            market.buy(Stock::MSFT, volume, None, None, None);
+            market.find_trades(Stock::MSFT);
+        }
+        assert_eq!(a.get_price(Stock::MSFT), 5.0);
+        assert_eq!(b.get_price(Stock::MSFT), 20.0);
+        assert_eq!(a.get_depth(Stock::MSFT, 1).1, vec![(5.0, 90)]);
+        assert_eq!(b.get_depth(Stock::MSFT, 1).1, vec![(20.0, 97)]);
+
+        let duplicate = sessions.create("team-a", &common::scenario(1.0, false)).err().unwrap();
+        assert!(duplicate.contains("already exists"), "{}", duplicate);
+        assert!(sessions.create("team a/..", &common::scenario(1.0, false)).is_err());
+        assert!(sessions.create("team-c", &Scenario { instruments: Vec::new(), ..Scenario::default() }).is_err());
+        // nothing that touches the server's files or address
+        for field in [
+            json!({ "bind": "0.0.0.0:9000" }),
+            json!({ "snapshot_dir": "/" }),
+            json!({ "journal": { "path": "/tmp/team-c.jsonl" } }),
+            json!({ "checkpoint": { "path": "/tmp/team-c.json", "interval_seconds": 60.0 } }),
+            json!({ "restore": "/etc/passwd", "instruments": [] }),
+            json!({ "replay": { "journal": "/etc/passwd" }, "instruments": [] }),
+            json!({ "instruments": [{ "symbol": "MSFT", "ipo_size": 100, "history": "/etc/passwd" }] })
+        ] {
+            let mut scenario = serde_json::to_value(common::scenario(1.0, false)).unwrap();
+            scenario.as_object_mut().unwrap().extend(field.as_object().unwrap().clone());
+            let refused = sessions.create("team-c", &serde_json::from_value(scenario).unwrap()).err().expect("Expected the session to be refused");
+            assert!(refused.contains("can't set"), "{}", refused);
+        }
+        assert!(sessions.get("team-c").is_none());
+
+        // removing joins the event loop, so nothing of the session is left running
+        assert!(matches!(sessions.remove("team-a"), Some(Ok(()))));
+        assert!(a.is_stopped());
+        assert_eq!(Arc::strong_count(&a), 1);
+        assert!(!b.is_stopped());
+        assert!(sessions.get("team-a").is_none());
+        assert!(sessions.remove("team-a").is_none());
+        // the name is free again once removed
+        sessions.create("team-a", &common::scenario(1.0, false)).unwrap();
+        sessions.remove_all().unwrap();
+        assert!(b.is_stopped());
+        assert!(sessions.names().is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn routes_trade_on_their_session() {
+        let sessions = web::Data::new(Sessions::default());
+        let default = Arc::new(Market::new());
+        default.ipo(Stock::MSFT, 100, 7.0, None);
+        sessions.insert(DEFAULT_SESSION, default);
+        sessions.create("team-a", &common::scenario(5.0, false)).unwrap();
+        for name in [DEFAULT_SESSION, "team-a"] {
+            let market = sessions.get(name).unwrap();
+            market.buy(Stock::MSFT, 1, None, None, None);
+            market.find_trades(Stock::MSFT);
+        }
+
+        let app = init_service(
+            App::new()
+                .app_data(sessions.clone())
+                .route("/price", web::get().to(_price))
+                .service(web::scope("/sessions/{name}").route("/price", web::get().to(_price)))
+        ).await;
+
+        for (uri, expected) in [("/price?stock_name=MSFT", 7.0), ("/sessions/team-a/price?stock_name=MSFT", 5.0)] {
+            let res: PriceDTO = call_and_read_body_json(&app, TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(res.price, expected, "{}", uri);
+        }
+
+        let missing = call_service(&app, TestRequest::get().uri("/sessions/team-b/price?stock_name=MSFT").to_request()).await;
+        assert_eq!(missing.status(), http::StatusCode::NOT_FOUND);
+        // a stock the server knows but the session doesn't list is just as missing
+        let unlisted = call_service(&app, TestRequest::get().uri("/sessions/team-a/price?stock_name=AAPL").to_request()).await;
+        assert_eq!(unlisted.status(), http::StatusCode::NOT_FOUND);
+        let team_a = web::Data::from(sessions.get("team-a").unwrap());
+        let depth = handle_depth(team_a.clone(), web::Query(DepthQuery { stock_name: "AAPL".to_string(), levels: None })).unwrap();
+        assert_eq!(depth.status(), http::StatusCode::NOT_FOUND);
+        let halt = handle_halt(team_a, web::Json(StockQuery { stock_name: "AAPL".to_string() })).unwrap();
+        assert_eq!(halt.status(), http::StatusCode::NOT_FOUND);
+
+        let removed = handle_remove_session(sessions.clone(), web::Path::from(DEFAULT_SESSION.to_string())).unwrap();
+        assert_eq!(removed.status(), http::StatusCode::BAD_REQUEST);
+        let removed = handle_remove_session(sessions.clone(), web::Path::from("team-a".to_string())).unwrap();
+        assert_eq!(removed.status(), http::StatusCode::OK);
+        let gone = call_service(&app, TestRequest::get().uri("/sessions/team-a/price?stock_name=MSFT").to_request()).await;
+        assert_eq!(gone.status(), http::StatusCode::NOT_FOUND);
+        sessions.remove_all().unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn sessions_snapshot_into_their_own_directories() {
+        let dir = std::env::temp_dir().join(format!("fssm_session_snapshots_{}", std::process::id()));
+        let sessions = web::Data::new(Sessions::new(dir.clone()));
+        sessions.create("team-a", &common::scenario(5.0, false)).unwrap();
+        sessions.create("team-b", &common::scenario(20.0, false)).unwrap();
+
+        let app = init_service(
+            App::new()
+                .app_data(sessions.clone())
+                .service(web::scope("/sessions/{name}").route("/admin/snapshot", web::post().to(_snapshot)))
+        ).await;
+        for name in ["team-a", "team-b"] {
+            let request = TestRequest::post().uri(&format!("/sessions/{}/admin/snapshot", name)).set_json(json!({ "path": "runs/market.json" }));
+            assert_eq!(call_service(&app, request.to_request()).await.status(), http::StatusCode::OK);
+        }
+
+        // the same name in two sessions is two files, neither overwrites the other
+        for (name, price) in [("team-a", 5.0), ("team-b", 20.0)] {
+            let path = dir.join(name).join("runs").join("market.json");
+            let restored = Market::new();
+            snapshot::restore(&restored, snapshot::load(&path).unwrap()).unwrap();
+            assert_eq!(restored.get_depth(Stock::MSFT, 1).1, vec![(price, 100)], "{}", path.display());
+        }
+        sessions.remove_all().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
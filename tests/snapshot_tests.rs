@@ -0,0 +1,87 @@
+use fssm::classes::shared::order::{OrderType, Stock};
+use fssm::kernel::agents::{agent::*, registry};
+use fssm::kernel::events::{self, MarketEvent};
+use fssm::kernel::market::Market;
+use fssm::kernel::snapshot;
+use fssm::classes::api::request_classes::SnapshotDTO;
+use fssm::handlers::admin_handler::handle_snapshot;
+use actix_web::{http::StatusCode, web};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn snapshot_restores_books_agents_and_ids() {
+        let market = Market::new();
+        let path = std::env::temp_dir().join(format!("fssm_snapshot_{}.json", std::process::id()));
+
+        let stock = Stock::MSFT;
+        market.ipo(stock, 10, 10.0, Some(1));
+        market.buy(stock, 4, None, None, Some(2));
+        market.find_trades(stock);
+        market.buy(stock, 5, Some(9.0), None, Some(3));
+        market.sell(stock, 2, Some(11.0), None, Some(4));
+        events::schedule(&market, stock, MarketEvent::PriceShock { pct: 5.0 }, 3600.0);
+
+        let mut maker = registry::create_agent("inventory_market_maker", &json!({ "base_size": 50 })).unwrap();
+        let ctx = AgentContext { id: 0, stock: stock, market: &market };
+        maker.on_fill(&ctx, &Fill { order_id: 0, order_type: OrderType::Buy, price: 10.0, volume: 30, timestamp: 0 });
+        let maker_id = registry::add(&market, stock, maker);
+        market.next_order_id();
+
+        snapshot::save(&market, &path).unwrap();
+        let depth = market.get_depth(stock, 5);
+        let next_order_id = market.peek_next_order_id();
+
+        // move everything on from the snapshot, then go back to it
+        market.cancel(stock, 3);
+        market.buy(stock, 100, None, None, Some(5));
+        market.find_trades(stock);
+        registry::remove(&market, maker_id);
+        events::set_scheduled(&market, Vec::new());
+        market.next_order_id();
+
+        let stocks = snapshot::restore(&market, snapshot::load(&path).unwrap()).unwrap();
+        assert_eq!(stocks, vec![stock]);
+        assert_eq!(market.get_depth(stock, 5), depth);
+        assert_eq!(market.get_price(stock), 10.0);
+        assert_eq!(market.peek_next_order_id(), next_order_id);
+
+        let info = registry::info(&market, maker_id).expect("Expected the maker to be restored under its old id");
+        assert_eq!(info.kind, "inventory_market_maker");
+        assert_eq!(info.config["base_size"], 50);
+        assert_eq!(info.state["inventory"], 30);
+        assert_eq!(info.state["cash"], -300.0);
+
+        assert_eq!(events::scheduled(&market).len(), 1, "Expected the scheduled shock to come back");
+
+        // nothing is lost on the way through, a fresh snapshot matches the file
+        let saved = snapshot::load(&path).unwrap();
+        assert_eq!(serde_json::to_value(snapshot::take(&market).stocks).unwrap(), serde_json::to_value(saved.stocks).unwrap());
+        assert_eq!(saved.agents.agents.len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn api_snapshots_stay_in_the_snapshot_directory() {
+        let market = web::Data::new(Market::new());
+        market.ipo(Stock::MSFT, 10, 10.0, Some(1));
+        let dir = std::env::temp_dir().join(format!("fssm_snapshots_{}", std::process::id()));
+        let outside = std::env::temp_dir().join(format!("fssm_outside_{}.json", std::process::id()));
+
+        for path in [outside.to_str().unwrap(), "../escaped.json", "runs/../../escaped.json", ""] {
+            let response = handle_snapshot(market.clone(), &dir, web::Json(SnapshotDTO { path: path.to_string() })).unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "Expected '{}' to be refused", path);
+        }
+        assert!(!outside.exists());
+
+        let response = handle_snapshot(market.clone(), &dir, web::Json(SnapshotDTO { path: "runs/market.json".to_string() })).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(snapshot::load(&dir.join("runs").join("market.json")).unwrap().stocks.len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}